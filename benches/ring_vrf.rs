@@ -0,0 +1,100 @@
+// Copyright (c) 2026 The ring-vrf contributors
+//
+// Licensed under the MIT license, see LICENSE.
+
+//! Benchmarks for CRS generation, proving, verification, and VRF evaluation.
+//!
+//! We generate one CRS per depth before measuring anything, because
+//! generation dominates everything else.  Run with `cargo bench`.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+use bellman::groth16;
+use pairing::bls12_381::Bls12;
+
+use ring_vrf::{
+    generate_crs, ring_vrf_verify_stream, signing_context, vrf::no_extra,
+    RingSRS, RingSecretCopath, SecretKey, VRFInput,
+};
+
+const DEPTHS: [u32; 3] = [4, 8, 12];
+const BATCH: usize = 16;
+
+fn input(i: usize) -> VRFInput<Bls12> {
+    VRFInput::new_malleable(signing_context(b"bench").bytes(&i.to_le_bytes()))
+}
+
+fn bench_generate_crs(c: &mut Criterion) {
+    let mut group = c.benchmark_group("generate_crs");
+    group.sample_size(10);
+    for depth in [2u32, 4].iter() {
+        group.bench_with_input(BenchmarkId::from_parameter(depth), depth, |b, depth| {
+            b.iter( || generate_crs::<Bls12>(*depth).unwrap() )
+        });
+    }
+    group.finish();
+}
+
+fn bench_prove_verify(c: &mut Criterion) {
+    let mut rng = ::rand_core::OsRng;
+    let sk = SecretKey::<Bls12>::from_rng(&mut rng);
+
+    for depth in DEPTHS.iter().cloned() {
+        let params = generate_crs::<Bls12>(depth).unwrap();
+        let pvk = groth16::prepare_verifying_key(&params.vk);
+        let srs = RingSRS { srs: &params, depth };
+        let copath = RingSecretCopath::random(depth, &mut rng);
+        let auth_root = copath.to_root(&sk.to_public());
+
+        let signed = (0..BATCH).map( |i| {
+            sk.ring_vrf_sign_first(input(i), no_extra(), copath.clone(), srs).unwrap()
+        } ).collect::<Vec<_>>();
+
+        let mut group = c.benchmark_group(format!("depth {}", depth));
+        group.sample_size(10);
+        group.throughput(Throughput::Elements(1));
+        group.bench_function("prove", |b| b.iter( ||
+            sk.ring_vrf_prove(input(0), no_extra(), copath.clone(), srs, &mut rng).unwrap()
+        ));
+        group.bench_function("verify", |b| b.iter( || {
+            let (inout, proof) = signed[0].clone();
+            assert!(auth_root.ring_vrf_verify(inout, no_extra(), proof, &pvk).unwrap());
+        }));
+        group.throughput(Throughput::Elements(BATCH as u64));
+        group.bench_function("verify stream", |b| b.iter( || {
+            let items = signed.iter().cloned().map( |(inout, proof)| (inout, no_extra(), proof) );
+            assert!(ring_vrf_verify_stream(items, &auth_root, &params.vk).all( |r| r.is_ok() ));
+        }));
+        group.finish();
+    }
+}
+
+fn bench_evaluate(c: &mut Criterion) {
+    let sk = SecretKey::<Bls12>::from_rng(::rand_core::OsRng);
+    let inputs = (0..256).map(input).collect::<Vec<_>>();
+
+    let mut group = c.benchmark_group("evaluate 256");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(inputs.len() as u64));
+    group.bench_function("individual", |b| b.iter( ||
+        inputs.iter().map( |i| i.to_preout(&sk) ).collect::<Vec<_>>()
+    ));
+    group.bench_function("batched", |b| b.iter( || sk.to_preouts(&inputs) ));
+    group.finish();
+}
+
+fn bench_output_bytes(c: &mut Criterion) {
+    let sk = SecretKey::<Bls12>::from_rng(::rand_core::OsRng);
+    let inout = input(0).to_inout(&sk);
+
+    // Compare with `cargo bench --features fast_transcript`.
+    let mut group = c.benchmark_group("output 32 bytes");
+    group.throughput(Throughput::Bytes(32));
+    group.bench_function("merlin", |b| b.iter( || inout.make_bytes::<[u8; 32]>(b"bench") ));
+    #[cfg(feature = "fast_transcript")]
+    group.bench_function("blake3", |b| b.iter( || inout.make_bytes_blake3::<[u8; 32]>(b"bench") ));
+    group.finish();
+}
+
+criterion_group!(benches, bench_generate_crs, bench_prove_verify, bench_evaluate, bench_output_bytes);
+criterion_main!(benches);
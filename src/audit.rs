@@ -0,0 +1,152 @@
+// Copyright (c) 2026 The ring-vrf contributors
+//
+// Licensed under the MIT license, see LICENSE.
+
+//! ### Ring positions disclosed only to a designated auditor
+//!
+//! We ElGamal encrypt the signer's ring index `i` to an auditor's
+//! public key `A`, producing `R = r G` and `C = i G + r A`, and the
+//! `RingVRFAudit` circuit proves this encryption uses the same index
+//! as the Merkle tree authentication path.
+//!
+//! An auditor recovers `i G = C - a R` and then searches the `2^depth`
+//! possible indices, so decryption costs grow with the ring.
+
+use std::io;
+
+use zcash_primitives::jubjub::{JubjubEngine, PrimeOrder, Unknown, edwards::Point};
+
+use crate::{
+    JubjubEngineWithParams, ReadWrite, SecretKey,
+    SignatureResult, signature_error,
+    Scalar, scalar_times_generator,
+};
+
+
+/// Ring index encrypted to an auditor, proven correct by the ring VRF
+/// signature from `SecretKey::ring_vrf_prove_with_audit`.
+#[derive(Debug, Clone)]
+pub struct AuditTag<E: JubjubEngine> {
+    /// Ephemeral key `R = r G`
+    pub(crate) ephemeral: Point<E, Unknown>,
+    /// Ciphertext `C = i G + r A`
+    pub(crate) ciphertext: Point<E, Unknown>,
+}
+
+impl<E: JubjubEngineWithParams> AuditTag<E> {
+    /// Encrypt `index` to `auditor` using randomness `r`.
+    pub(crate) fn new(index: usize, r: &Scalar<E>, auditor: &Point<E, PrimeOrder>) -> AuditTag<E> {
+        let params = E::params();
+        let ephemeral = scalar_times_generator(r).into();
+        let ciphertext = index_times_generator::<E>(index)
+            .add(&auditor.mul(r.clone(), params), params)
+            .into();
+        AuditTag { ephemeral, ciphertext }
+    }
+}
+
+impl<E: JubjubEngineWithParams> ReadWrite for AuditTag<E> {
+    fn read<R: io::Read>(mut reader: R) -> io::Result<Self> {
+        let ephemeral = Point::read(&mut reader, E::params()) ?;
+        let ciphertext = Point::read(&mut reader, E::params()) ?;
+        Ok(AuditTag { ephemeral, ciphertext })
+    }
+
+    fn write<W: io::Write>(&self, mut writer: W) -> io::Result<()> {
+        self.ephemeral.write(&mut writer) ?;
+        self.ciphertext.write(&mut writer)
+    }
+}
+
+/// Compute `index G` by double and add, mirroring the circuit's fixed
+/// base multiplication by the copath position bits.
+fn index_times_generator<E: JubjubEngineWithParams>(index: usize) -> Point<E, PrimeOrder> {
+    use zcash_primitives::jubjub::{FixedGenerators, JubjubParams};
+    let params = E::params();
+    let g = params.generator(FixedGenerators::SpendingKeyGenerator);
+    let bits = 8 * ::core::mem::size_of::<usize>();
+    (0..bits).rev().fold(Point::zero(), |acc, i| {
+        let acc = acc.double(params);
+        if (index >> i) & 1 == 1 { acc.add(g, params) } else { acc }
+    })
+}
+
+/// Recover the ring index from an `AuditTag` using the auditor's secret key.
+///
+/// We search all indices in a ring of the given `depth`, so an error
+/// indicates either the wrong auditor key or the wrong depth.
+pub fn auditor_decrypt<E: JubjubEngineWithParams>(tag: &AuditTag<E>, auditor: &SecretKey<E>, depth: u32)
+ -> SignatureResult<usize>
+{
+    use zcash_primitives::jubjub::{FixedGenerators, JubjubParams};
+    let params = E::params();
+    if depth as usize >= 8 * ::core::mem::size_of::<usize>() {
+        return Err(signature_error("Ring depth too large for auditor decryption."));
+    }
+    let target = tag.ciphertext.add(&tag.ephemeral.mul(auditor.key.clone(), params).negate(), params);
+    let g: Point<E, Unknown> = params.generator(FixedGenerators::SpendingKeyGenerator).clone().into();
+    let mut cur = Point::<E, Unknown>::zero();
+    for index in 0..(1usize << depth) {
+        if cur == target { return Ok(index); }
+        cur = cur.add(&g, params);
+    }
+    Err(signature_error("Audit tag was not encrypted to this auditor."))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use pairing::bls12_381::Bls12;
+
+    use super::*;
+    use crate::{testing, signing_context, generate_crs_audit, VRFInput, vrf::no_extra};
+
+    #[test]
+    fn auditor_recovers_index() {
+        let mut rng = ::rand_core::OsRng;
+        let auditor = SecretKey::<Bls12>::from_rng(&mut rng);
+        let other = SecretKey::<Bls12>::from_rng(&mut rng);
+        let auditor_pk = auditor.to_public().0.as_prime_order(Bls12::params()).unwrap();
+        let r = <Scalar<Bls12> as ::ff::Field>::random(&mut rng);
+
+        let tag = AuditTag::<Bls12>::new(11, &r, &auditor_pk);
+        let mut bytes = Vec::new();
+        tag.write(&mut bytes).unwrap();
+        let tag = AuditTag::<Bls12>::read(&bytes[..]).unwrap();
+
+        assert_eq!(auditor_decrypt(&tag, &auditor, 4).unwrap(), 11);
+        assert!(auditor_decrypt(&tag, &other, 4).is_err());
+        assert!(auditor_decrypt(&tag, &auditor, 3).is_err());
+    }
+
+    #[test]
+    fn test_audit_proof() {
+        let mut rng = ::rand_core::OsRng;
+        let depth = 2;
+        let srs = generate_crs_audit::<Bls12>(depth).expect("can't generate SRS");
+        let fx = testing::RingFixture::with_params(srs, depth);
+        let auditor = SecretKey::<Bls12>::from_rng(&mut rng);
+        let other = SecretKey::<Bls12>::from_rng(&mut rng);
+
+        let (sk, copath, auth_root) = fx.signer();
+        let index = copath.index();
+        let input = || VRFInput::<Bls12>::new_malleable(signing_context(b"audit").bytes(b"meow"));
+        let (proof, tag) = sk.ring_vrf_prove_with_audit(input(), no_extra(), copath.clone(), &auditor.to_public(), fx.srs(), &mut rng).unwrap();
+        let verify = |tag: &AuditTag<Bls12>, auditor: &SecretKey<Bls12>| auth_root.ring_vrf_verify_with_audit(
+            input().to_inout(&sk), no_extra(), proof.clone(), tag, &auditor.to_public(), &fx.pvk
+        ).unwrap();
+        assert!(verify(&tag, &auditor));
+        assert_eq!(auditor_decrypt(&tag, &auditor, depth).unwrap(), index);
+
+        // Tags encrypted to other auditors, or with other proofs, fail.
+        assert!(! verify(&tag, &other));
+        let (_, other_tag) = sk.ring_vrf_prove_with_audit(input(), no_extra(), copath, &other.to_public(), fx.srs(), &mut rng).unwrap();
+        assert!(! verify(&other_tag, &other));
+        assert!(auditor_decrypt(&other_tag, &auditor, depth).is_err());
+
+        match generate_crs_audit::<Bls12>(0) {
+            Err(bellman::SynthesisError::IoError(e)) => assert_eq!(e.kind(), ::std::io::ErrorKind::InvalidInput),
+            _ => panic!("Audit CRS generated at depth zero"),
+        }
+    }
+}
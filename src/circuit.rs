@@ -16,6 +16,59 @@ use bellman::gadgets::{boolean, num, Assignment};
 use crate::{JubjubEngineWithParams, merkle::MerkleSelection, RingSecretCopath, SecretKey};
 
 
+/// Description of one public input of the `RingVRF` circuit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PublicInputField {
+    /// Short name of this input
+    pub name: &'static str,
+    /// Number of consecutive field elements this input occupies
+    pub width: usize,
+    /// What this input means
+    pub meaning: &'static str,
+    /// How verifiers encode this input into field elements
+    pub encoding: &'static str,
+}
+
+/// Public inputs of the `RingVRF` circuit, in order.
+///
+/// All field elements lie in the BLS12-381 scalar field, aka the JubJub
+/// base field.  Groth16 verifiers prepend the constant one themselves.
+const PUBLIC_INPUTS: [PublicInputField; 4] = [
+    PublicInputField {
+        name: "vrf_input", width: 2,
+        meaning: "VRF input point, as Edwards x and y coordinates",
+        encoding: "Affine Edwards x then y of VRFInput::as_point",
+    },
+    PublicInputField {
+        name: "vrf_output", width: 2,
+        meaning: "VRF pre-output point, as Edwards x and y coordinates",
+        encoding: "Affine Edwards x then y of VRFPreOut::as_point, without cofactor clearing",
+    },
+    PublicInputField {
+        name: "extra", width: 1,
+        meaning: "Extra message transcript hashed by challenge_scalar",
+        encoding: "SigningTranscript::challenge_scalar of the extra transcript with label b\"extra-msg\", see vrf::extra_scalar",
+    },
+    PublicInputField {
+        name: "ring_root", width: 1,
+        meaning: "Merkle root of the ring's public keys",
+        encoding: "RingRoot field element, whose ReadWrite encoding is its little endian repr",
+    },
+];
+
+/// Describe the public inputs of the `RingVRF` circuit, in order.
+///
+/// A `RingVRFProof` reveals exactly these, while the secret key, public
+/// key, and ring index remain hidden witnesses.  We need no depth here
+/// because the Merkle tree depth changes only the witnesses.
+///
+/// Verifiers written outside this crate should assemble their public
+/// inputs by following each field's `encoding`.
+pub fn public_input_schema() -> Vec<PublicInputField> {
+    PUBLIC_INPUTS.to_vec()
+}
+
+
 /// A circuit for proving that the given vrf_preout is valid for the given vrf_input under
 /// a key from the predefined set. It formalizes the following language:
 ///
@@ -49,152 +102,459 @@ pub struct RingVRF<E: JubjubEngine> { // TODO: name
 
 impl<E: JubjubEngineWithParams> Circuit<E::Fr> for RingVRF<E> {
     fn synthesize<CS: ConstraintSystem<E::Fr>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
-        if let Some(copath) = self.copath.as_ref() {
-            if copath.depth() != self.depth {
-                return Err(SynthesisError::Unsatisfiable)
-            }
-        }
+        check_depth(self.copath.as_ref(), self.depth) ?;
 
-        let engine_params = E::params();
+        let pk = synthesize_vrf(cs, self.sk, self.vrf_input) ?;
+        synthesize_extra(cs, self.extra) ?;
+
+        // So the circuit is 6 (public inputs) + 252 (sk booleanity) + 750 (fixed-base mul)
+        //                 + 20 (on-curve + subgroup check) + 3252 (var-base mul)
+        //                 = 4280 constraints
+
+        // This is an injective encoding, as cur is a
+        // point in the prime order subgroup.
+        let (cur, _) = synthesize_merkle_root(cs, pk.get_x().clone(), self.copath.as_ref(), self.depth) ?;
+        cur.inputize(cs.namespace(|| "anchor"))?;
+
+        Ok(())
+    }
+}
+
+
+/// A circuit for proving that the given vrf_preout is valid for the given vrf_input under
+/// a key that lies in two sets, like the ring before and after an epoch transition.
+///
+/// We prove the same language as `RingVRF` except the public key must be in both sets,
+/// which requires its own CRS, see `generate_crs_transition`.
+pub struct RingVRFTransition<E: JubjubEngine> {
+    /// Merkle tree depth, which both rings share
+    pub depth: u32,
+
+    /// The secret key, an element of Jubjub scalar field.
+    pub sk: Option<SecretKey<E>>,
+
+    /// The VRF input, a point in Jubjub prime order subgroup.
+    pub vrf_input: Option<Point<E, PrimeOrder>>,
+
+    /// An extra message to sign along with the 
+    pub extra: Option<E::Fr>,
+
+    /// The authentication path of the public key in the old ring.
+    pub copath_old: Option<RingSecretCopath<E>>,
 
-        // Binary representation of the secret key, a prover's private input.
-        // fs_bits wires and fs_bits booleanity constraints, where fs_bits = 252 is Jubjub scalar field size.
-        // It isn't (range-)constrained to be an element of the field, so small values will have duplicate representations.
-        // That doesn't matter for the following reasons: // TODO: double-check
-        // 1. Knowledge of a congruence of the secret key is equivalent to the knowledge of the secret key,
-        //    and the check sk * G = PK passes for a congruent (sk + n|fs|) * G = sk * G + n|fs| * G == PK + O
-        // 2. Multiplication by a congruent secret key results in the same VRF output:
-        //    (sk + n|fs|) * H == sk * H, if ord(H) == |fs|
-        let sk_bits = boolean::field_into_boolean_vec_le(
-            cs.namespace(|| "sk"), self.sk.map(|sk| sk.key)
+    /// The authentication path of the public key in the new ring.
+    pub copath_new: Option<RingSecretCopath<E>>,
+}
+
+impl<E: JubjubEngineWithParams> Circuit<E::Fr> for RingVRFTransition<E> {
+    fn synthesize<CS: ConstraintSystem<E::Fr>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+        check_depth(self.copath_old.as_ref(), self.depth) ?;
+        check_depth(self.copath_new.as_ref(), self.depth) ?;
+
+        let pk = synthesize_vrf(cs, self.sk, self.vrf_input) ?;
+        synthesize_extra(cs, self.extra) ?;
+
+        // We ascend both authentication paths from the same witnessed public key,
+        // so both anchors commit to the one secret key.
+        let (old, _) = synthesize_merkle_root(
+            &mut cs.namespace(|| "old ring"),
+            pk.get_x().clone(), self.copath_old.as_ref(), self.depth
         ) ?;
+        old.inputize(cs.namespace(|| "old anchor"))?;
 
-        // Derives the public key from the secret key using the hardcoded generator,
-        // that is guaranteed to be in the primeorder subgroup,
-        // so no on-curve or subgroup checks are required //TODO: double-check
-        // 750 constraints according to Zcash spec A.3.3.7
-        let pk = ecc::fixed_base_multiplication::<E, _>(
-            cs.namespace(|| "PK = sk * G"),
-            FixedGenerators::SpendingKeyGenerator, //TODO: any NUMS point of full order
-            &sk_bits,
-            engine_params,
+        let (new, _) = synthesize_merkle_root(
+            &mut cs.namespace(|| "new ring"),
+            pk.get_x().clone(), self.copath_new.as_ref(), self.depth
         ) ?;
+        new.inputize(cs.namespace(|| "new anchor"))?;
+
+        Ok(())
+    }
+}
+
+
+/// Field element to which `RingVRFDomain` binds an application's `domain`.
+pub(crate) fn domain_scalar<E: JubjubEngine>(domain: &[u8]) -> E::Fr {
+    use crate::SigningTranscript;
+    let mut t = ::merlin::Transcript::new(b"RingVRFDomain");
+    t.append_message(b"domain", domain);
+    t.challenge_scalar(b"domain")
+}
+
+/// A circuit for proving the same language as `RingVRF`, but only for
+/// one application `domain`.
+///
+/// We add a public input constrained to equal a constant derived from
+/// `domain`, so this requires its own CRS, see `generate_crs_domain`,
+/// and verifiers must supply the same domain, so proofs never transfer
+/// between applications, even if their other public inputs coincide.
+pub struct RingVRFDomain<E: JubjubEngine> {
+    /// The `RingVRF` circuit we extend.
+    pub ring_vrf: RingVRF<E>,
+
+    /// The application domain, from `domain_scalar`, known to the CRS.
+    pub domain: E::Fr,
+}
+
+impl<E: JubjubEngineWithParams> Circuit<E::Fr> for RingVRFDomain<E> {
+    fn synthesize<CS: ConstraintSystem<E::Fr>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+        let domain = self.domain;
+        self.ring_vrf.synthesize(cs) ?;
+        let input = cs.alloc_input(|| "domain", || Ok(domain)) ?;
+        cs.enforce(
+            || "domain constant",
+            |lc| lc + input,
+            |lc| lc + CS::one(),
+            |lc| lc + (domain, CS::one()),
+        );
+        Ok(())
+    }
+}
+
+/// A circuit for proving the same language as `RingVRF`, while also
+/// encrypting our ring index to an auditor, see `crate::audit`.
+///
+/// We add public inputs for the ephemeral key `R = r G`, the auditor's
+/// public key `A`, and the ciphertext `C = index G + r A`, so this
+/// requires its own CRS, see `generate_crs_audit`.
+///
+/// We multiply `G` by the index bits, of which depth zero has none, so
+/// we reject depth zero with an `io::ErrorKind::InvalidInput` error.
+#[cfg(feature = "audit_tag")]
+pub struct RingVRFAudit<E: JubjubEngine> {
+    /// Merkle tree depth
+    pub depth: u32,
+
+    /// The secret key, an element of Jubjub scalar field.
+    pub sk: Option<SecretKey<E>>,
+
+    /// The VRF input, a point in Jubjub prime order subgroup.
+    pub vrf_input: Option<Point<E, PrimeOrder>>,
+
+    /// An extra message to sign along with the 
+    pub extra: Option<E::Fr>,
+
+    /// The authentication path of the public key in the ring.
+    pub copath: Option<RingSecretCopath<E>>,
 
-        // Defines first 2 public input wires for the coordinates of the public key in Jubjub base field (~ BLS scalar field)
-        // and assures their assignment matches the values calculated in the previous step in 2 constraints.
-        // These 2 constraints are not strictly required, just Bellman is implemented this way.
-        // TODO: x coordinate only
-        // pk.inputize(cs.namespace(|| "PK")) ?;
-
-        // Allocates VRF_BASE on the circuit and checks that it is a point on the curve
-        // adds 4 constraints (A.3.3.1) to check that it is indeed a point on Jubjub
-        let vrf_input = ecc::EdwardsPoint::witness(
-            cs.namespace(|| "VRF_INPUT"),
-            self.vrf_input,
+    /// The auditor's public key, a point in Jubjub prime order subgroup.
+    pub auditor: Option<Point<E, PrimeOrder>>,
+
+    /// Randomness for encrypting our index to the auditor.
+    pub audit_randomness: Option<E::Fs>,
+}
+
+#[cfg(feature = "audit_tag")]
+impl<E: JubjubEngineWithParams> Circuit<E::Fr> for RingVRFAudit<E> {
+    fn synthesize<CS: ConstraintSystem<E::Fr>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+        if self.depth == 0 {
+            return Err(SynthesisError::IoError(::std::io::Error::new(
+                ::std::io::ErrorKind::InvalidInput,
+                "Audit tags encrypt the ring index, so they require depth at least one."
+            )));
+        }
+        check_depth(self.copath.as_ref(), self.depth) ?;
+        let engine_params = E::params();
+
+        let pk = synthesize_vrf(cs, self.sk, self.vrf_input) ?;
+        synthesize_extra(cs, self.extra) ?;
+
+        let (cur, position) = synthesize_merkle_root(cs, pk.get_x().clone(), self.copath.as_ref(), self.depth) ?;
+        cur.inputize(cs.namespace(|| "anchor"))?;
+
+        let r_bits = boolean::field_into_boolean_vec_le(
+            cs.namespace(|| "audit randomness"), self.audit_randomness
+        ) ?;
+        let ephemeral = ecc::fixed_base_multiplication::<E, _>(
+            cs.namespace(|| "R = r * G"),
+            FixedGenerators::SpendingKeyGenerator,
+            &r_bits,
             engine_params,
         ) ?;
+        ephemeral.inputize(cs.namespace(|| "audit ephemeral")) ?;
 
-        // Checks that VRF_BASE lies in a proper subgroup of Jubjub. Not strictly required as it is the point provided
-        // externally as a public input, so MUST be previously checked by the verifier off-circuit.
-        // But why not double-check it in 16 = 3 * 5 (ec doubling) + 1 (!=0) constraints
-        // Moreover //TODO
-        vrf_input.assert_not_small_order(
-            cs.namespace(|| "VRF_BASE not small order"),
+        let auditor = ecc::EdwardsPoint::witness(
+            cs.namespace(|| "AUDITOR"),
+            self.auditor,
+            engine_params,
+        ) ?;
+        auditor.assert_not_small_order(
+            cs.namespace(|| "AUDITOR not small order"),
             engine_params,
         ) ?;
+        auditor.inputize(cs.namespace(|| "auditor")) ?;
+
+        // The position bits are exactly the index bits, so this
+        // ciphertext cannot encrypt any index besides the one used
+        // to reach the anchor.
+        let shared = auditor.mul(
+            cs.namespace(|| "r * AUDITOR"),
+            &r_bits,
+            engine_params
+        ) ?;
+        let index = ecc::fixed_base_multiplication::<E, _>(
+            cs.namespace(|| "index * G"),
+            FixedGenerators::SpendingKeyGenerator,
+            &position,
+            engine_params,
+        ) ?;
+        let ciphertext = index.add(
+            cs.namespace(|| "C = index * G + r * AUDITOR"),
+            &shared,
+            engine_params
+        ) ?;
+        ciphertext.inputize(cs.namespace(|| "audit ciphertext")) ?;
+
+        Ok(())
+    }
+}
+
+
+/// A circuit for proving the same language as `RingVRF`, except we
+/// reveal only a Pedersen commitment `C = VRF_OUTPUT + b B` to the VRF
+/// output, for a witnessed blinding `b` and the blinding generator `B`.
+///
+/// We inputize `C` in place of the VRF output, so observers cannot link
+/// two proofs by one key on one input, while parties who learn `b`
+/// recover the VRF output.  We require our own CRS, see `generate_crs_blinded`.
+pub struct RingVRFBlinded<E: JubjubEngine> {
+    /// The `RingVRF` circuit whose VRF output we hide.
+    pub ring_vrf: RingVRF<E>,
+
+    /// Blinding of the VRF output commitment.
+    pub blinding: Option<E::Fs>,
+}
 
-        // Defines the 3rd and the 4th input wires to be equal VRF_BASE coordinates,
-        // thus adding 2 more constraints
-        vrf_input.inputize(cs.namespace(|| "VRF_BASE input")) ?;
+impl<E: JubjubEngineWithParams> Circuit<E::Fr> for RingVRFBlinded<E> {
+    fn synthesize<CS: ConstraintSystem<E::Fr>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+        let RingVRF { depth, sk, vrf_input, extra, copath } = self.ring_vrf;
+        check_depth(copath.as_ref(), depth) ?;
+        let engine_params = E::params();
 
-        // Produces VRF output = sk * VRF_BASE, it is a variable base multiplication, thus
-        // 3252 constraints (A.3.3.8)
-        // TODO: actually it is 13 more as it is full-length (252 bits) multiplication below
-        let vrf = vrf_input.mul(
-            cs.namespace(|| "vrf = sk * VRF_BASE"),
-            &sk_bits,
+        let (pk, vrf) = synthesize_vrf_hidden(cs, sk, vrf_input) ?;
+
+        let b_bits = boolean::field_into_boolean_vec_le(
+            cs.namespace(|| "blinding"), self.blinding
+        ) ?;
+        let blinding = ecc::fixed_base_multiplication::<E, _>(
+            cs.namespace(|| "b * B"),
+            FixedGenerators::NullifierPosition,
+            &b_bits,
+            engine_params,
+        ) ?;
+        let commitment = vrf.add(
+            cs.namespace(|| "C = vrf + b * B"),
+            &blinding,
             engine_params
         ) ?;
+        commitment.inputize(cs.namespace(|| "vrf commitment")) ?;
 
-        // And 2 more constraints to verify the output
-        vrf.inputize(cs.namespace(|| "vrf")) ?;
+        synthesize_extra(cs, extra) ?;
+        let (cur, _) = synthesize_merkle_root(cs, pk.get_x().clone(), copath.as_ref(), depth) ?;
+        cur.inputize(cs.namespace(|| "anchor"))?;
 
-        // Add the extra message wire, which consists of one E::Fr scalar.
-        // see: https://docs.rs/zcash_proofs/0.2.0/src/zcash_proofs/circuit/ecc.rs.html#138-155
-        let owned_extra = self.extra;
-        let extra = num::AllocatedNum::alloc(
-            cs.namespace(|| "extra message"),
-            || Ok(*owned_extra.get()?)
-        ) ?;
-        extra.inputize(cs.namespace(|| "extra"))?;
+        Ok(())
+    }
+}
 
-        // So the circuit is 6 (public inputs) + 252 (sk booleanity) + 750 (fixed-base mul)
-        //                 + 20 (on-curve + subgroup check) + 3252 (var-base mul)
-        //                 = 4280 constraints
 
-        // This is an injective encoding, as cur is a
-        // point in the prime order subgroup.
-        let mut cur = pk.get_x().clone();
-
-        // Ascend the merkle tree authentication path
-        for i in 0..(self.depth as usize) {
-            let e: Option<(_,_)> = self.copath.as_ref().map(
-                |v| ( v.0[i].current_selection, v.0[i].sibling.unwrap_or(<E::Fr>::zero()) )
-            );
-
-            let cs = &mut cs.namespace(|| format!("merkle tree hash {}", i));
-
-            // Determines if the current subtree is the "right" leaf at this
-            // depth of the tree.
-            let cur_is_right = boolean::Boolean::from(boolean::AllocatedBit::alloc(
-                cs.namespace(|| "position bit"),
-                e.map(|e| e.0 == MerkleSelection::Right),
-            ) ?);
-
-            // Witness the authentication path element adjacent
-            // at this depth.
-            let path_element =
-                num::AllocatedNum::alloc(cs.namespace(|| "path element"), || Ok(e.get()?.1)) ?;
-
-            // Swap the two if the current subtree is on the right
-            let (xl, xr) = num::AllocatedNum::conditionally_reverse(
-                cs.namespace(|| "conditional reversal of preimage"),
-                &cur,
-                &path_element,
-                &cur_is_right,
-            ) ?;
-
-            // We don't need to be strict, because the function is
-            // collision-resistant. If the prover witnesses a congruency,
-            // they will be unable to find an authentication path in the
-            // tree with high probability.
-            let mut preimage = vec![];
-            preimage.extend(xl.to_bits_le(cs.namespace(|| "xl into bits"))?);
-            preimage.extend(xr.to_bits_le(cs.namespace(|| "xr into bits"))?);
-
-            // Compute the new subtree value
-            cur = pedersen_hash::pedersen_hash::<E, _>(
-                cs.namespace(|| "computation of pedersen hash"),
-                pedersen_hash::Personalization::MerkleTree(i),
-                &preimage,
-                engine_params,
-            )?.get_x().clone(); // Injective encoding
+/// Reject copaths whose length disagrees with the circuit's depth.
+fn check_depth<E: JubjubEngineWithParams>(copath: Option<&RingSecretCopath<E>>, depth: u32)
+ -> Result<(), SynthesisError>
+{
+    if let Some(copath) = copath {
+        if copath.depth() != depth {
+            return Err(SynthesisError::Unsatisfiable)
         }
-        cur.inputize(cs.namespace(|| "anchor"))?;
+    }
+    Ok(())
+}
 
-        Ok(())
+/// Synthesize the public key and VRF evaluation, inputizing only the
+/// VRF input as our first two public inputs.
+///
+/// We return the public key derived from the secret key for use
+/// in the Merkle tree membership proof, along with the VRF output,
+/// which callers inputize themselves, or else hide.
+fn synthesize_vrf_hidden<E,CS>(
+    cs: &mut CS,
+    sk: Option<SecretKey<E>>,
+    vrf_input: Option<Point<E, PrimeOrder>>,
+) -> Result<(ecc::EdwardsPoint<E>, ecc::EdwardsPoint<E>), SynthesisError>
+where E: JubjubEngineWithParams, CS: ConstraintSystem<E::Fr>,
+{
+    let engine_params = E::params();
+
+    // Binary representation of the secret key, a prover's private input.
+    // fs_bits wires and fs_bits booleanity constraints, where fs_bits = 252 is Jubjub scalar field size.
+    // It isn't (range-)constrained to be an element of the field, so small values will have duplicate representations.
+    // That doesn't matter for the following reasons: // TODO: double-check
+    // 1. Knowledge of a congruence of the secret key is equivalent to the knowledge of the secret key,
+    //    and the check sk * G = PK passes for a congruent (sk + n|fs|) * G = sk * G + n|fs| * G == PK + O
+    // 2. Multiplication by a congruent secret key results in the same VRF output:
+    //    (sk + n|fs|) * H == sk * H, if ord(H) == |fs|
+    let sk_bits = boolean::field_into_boolean_vec_le(
+        cs.namespace(|| "sk"), sk.map(|sk| sk.key)
+    ) ?;
+
+    // Derives the public key from the secret key using the hardcoded generator,
+    // that is guaranteed to be in the primeorder subgroup,
+    // so no on-curve or subgroup checks are required //TODO: double-check
+    // 750 constraints according to Zcash spec A.3.3.7
+    let pk = ecc::fixed_base_multiplication::<E, _>(
+        cs.namespace(|| "PK = sk * G"),
+        FixedGenerators::SpendingKeyGenerator, //TODO: any NUMS point of full order
+        &sk_bits,
+        engine_params,
+    ) ?;
+
+    // Defines first 2 public input wires for the coordinates of the public key in Jubjub base field (~ BLS scalar field)
+    // and assures their assignment matches the values calculated in the previous step in 2 constraints.
+    // These 2 constraints are not strictly required, just Bellman is implemented this way.
+    // TODO: x coordinate only
+    // pk.inputize(cs.namespace(|| "PK")) ?;
+
+    // Allocates VRF_BASE on the circuit and checks that it is a point on the curve
+    // adds 4 constraints (A.3.3.1) to check that it is indeed a point on Jubjub
+    let vrf_input = ecc::EdwardsPoint::witness(
+        cs.namespace(|| "VRF_INPUT"),
+        vrf_input,
+        engine_params,
+    ) ?;
+
+    // Checks that VRF_BASE lies in a proper subgroup of Jubjub. Not strictly required as it is the point provided
+    // externally as a public input, so MUST be previously checked by the verifier off-circuit.
+    // But why not double-check it in 16 = 3 * 5 (ec doubling) + 1 (!=0) constraints
+    // Moreover //TODO
+    vrf_input.assert_not_small_order(
+        cs.namespace(|| "VRF_BASE not small order"),
+        engine_params,
+    ) ?;
+
+    // Defines the 3rd and the 4th input wires to be equal VRF_BASE coordinates,
+    // thus adding 2 more constraints
+    vrf_input.inputize(cs.namespace(|| "VRF_BASE input")) ?;
+
+    // Produces VRF output = sk * VRF_BASE, it is a variable base multiplication, thus
+    // 3252 constraints (A.3.3.8)
+    // TODO: actually it is 13 more as it is full-length (252 bits) multiplication below
+    let vrf = vrf_input.mul(
+        cs.namespace(|| "vrf = sk * VRF_BASE"),
+        &sk_bits,
+        engine_params
+    ) ?;
+
+    Ok((pk, vrf))
+}
+
+/// Synthesize the public key and VRF evaluation, inputizing the
+/// VRF input and output as our first four public inputs.
+///
+/// We return the public key derived from the secret key for use
+/// in the Merkle tree membership proof.
+fn synthesize_vrf<E,CS>(
+    cs: &mut CS,
+    sk: Option<SecretKey<E>>,
+    vrf_input: Option<Point<E, PrimeOrder>>,
+) -> Result<ecc::EdwardsPoint<E>, SynthesisError>
+where E: JubjubEngineWithParams, CS: ConstraintSystem<E::Fr>,
+{
+    let (pk, vrf) = synthesize_vrf_hidden(cs, sk, vrf_input) ?;
+
+    // And 2 more constraints to verify the output
+    vrf.inputize(cs.namespace(|| "vrf")) ?;
+
+    Ok(pk)
+}
+
+/// Add the extra message wire, which consists of one E::Fr scalar.
+/// see: https://docs.rs/zcash_proofs/0.2.0/src/zcash_proofs/circuit/ecc.rs.html#138-155
+fn synthesize_extra<E,CS>(cs: &mut CS, extra: Option<E::Fr>) -> Result<(), SynthesisError>
+where E: JubjubEngineWithParams, CS: ConstraintSystem<E::Fr>,
+{
+    let extra = num::AllocatedNum::alloc(
+        cs.namespace(|| "extra message"),
+        || Ok(*extra.get()?)
+    ) ?;
+    extra.inputize(cs.namespace(|| "extra"))?;
+    Ok(())
+}
+
+/// Ascend the merkle tree authentication path from the leaf `cur`,
+/// returning the root without inputizing it.
+///
+/// We also return the position bits, starting from the leaves, which
+/// give our leaf index in little endian.
+fn synthesize_merkle_root<E,CS>(
+    cs: &mut CS,
+    mut cur: num::AllocatedNum<E::Fr>,
+    copath: Option<&RingSecretCopath<E>>,
+    depth: u32,
+) -> Result<(num::AllocatedNum<E::Fr>, Vec<boolean::Boolean>), SynthesisError>
+where E: JubjubEngineWithParams, CS: ConstraintSystem<E::Fr>,
+{
+    let engine_params = E::params();
+    let mut position = Vec::with_capacity(depth as usize);
+
+    for i in 0..(depth as usize) {
+        let e: Option<(_,_)> = copath.map(
+            |v| ( v.0[i].current_selection, v.0[i].sibling.unwrap_or(<E::Fr>::zero()) )
+        );
+
+        let cs = &mut cs.namespace(|| format!("merkle tree hash {}", i));
+
+        // Determines if the current subtree is the "right" leaf at this
+        // depth of the tree.
+        let cur_is_right = boolean::Boolean::from(boolean::AllocatedBit::alloc(
+            cs.namespace(|| "position bit"),
+            e.map(|e| e.0 == MerkleSelection::Right),
+        ) ?);
+
+        position.push(cur_is_right.clone());
+
+        // Witness the authentication path element adjacent
+        // at this depth.
+        let path_element =
+            num::AllocatedNum::alloc(cs.namespace(|| "path element"), || Ok(e.get()?.1)) ?;
+
+        // Swap the two if the current subtree is on the right
+        let (xl, xr) = num::AllocatedNum::conditionally_reverse(
+            cs.namespace(|| "conditional reversal of preimage"),
+            &cur,
+            &path_element,
+            &cur_is_right,
+        ) ?;
+
+        // We don't need to be strict, because the function is
+        // collision-resistant. If the prover witnesses a congruency,
+        // they will be unable to find an authentication path in the
+        // tree with high probability.
+        let mut preimage = vec![];
+        preimage.extend(xl.to_bits_le(cs.namespace(|| "xl into bits"))?);
+        preimage.extend(xr.to_bits_le(cs.namespace(|| "xr into bits"))?);
+
+        // Compute the new subtree value
+        cur = pedersen_hash::pedersen_hash::<E, _>(
+            cs.namespace(|| "computation of pedersen hash"),
+            pedersen_hash::Personalization::MerkleTree(i),
+            &preimage,
+            engine_params,
+        )?.get_x().clone(); // Injective encoding
     }
+
+    Ok((cur, position))
 }
 
 #[cfg(test)]
 mod tests {
     use bellman::gadgets::test::TestConstraintSystem;
+    use bellman::groth16;
     use pairing::bls12_381::{Bls12, Fr};
 
     use rand_core::{RngCore};
 
     use super::*;
-    use crate::{VRFInput, RingSecretCopath};
+    use crate::{testing, signing_context, ring_vrf_debug_public_inputs, VRFInput, RingSecretCopath, vrf::no_extra};
 
     #[test]
     fn test_ring() {
@@ -206,11 +566,12 @@ mod tests {
         let sk = SecretKey::<Bls12>::from_rng(&mut rng);
         let pk = sk.to_public();
 
-        let t = crate::signing_context(b"Hello World!").bytes(&rng.next_u64().to_le_bytes()[..]);
+        let seed = rng.next_u64();
+        let t = crate::signing_context(b"Hello World!").bytes(&seed.to_le_bytes()[..]);
         let vrf_input = VRFInput::<Bls12>::new_malleable(t);
 
         use crate::SigningTranscript;
-        let extra = ::merlin::Transcript::new(b"whatever").challenge_scalar(b"");
+        let extra = ::merlin::Transcript::new(b"whatever").challenge_scalar(b"extra-msg");
 
         let copath = RingSecretCopath::random(depth, &mut rng);
         let auth_root = copath.to_root(&pk);
@@ -241,5 +602,171 @@ mod tests {
         assert_eq!(cs.get_input(4, "vrf/y/input variable"), vrf_preout.as_point().to_xy().1);
         assert_eq!(cs.get_input(5, "extra/input variable"), extra );
         assert_eq!(cs.get_input(6, "anchor/input variable"), auth_root.0);
+
+        // The verifier reconstructs these same inputs.
+        let vrf_inout = vrf_preout.attach_input_malleable(
+            crate::signing_context(b"Hello World!").bytes(&seed.to_le_bytes()[..])
+        );
+        let mut t = ::merlin::Transcript::new(b"whatever");
+        let inputs = crate::ring_vrf_debug_public_inputs(&vrf_inout, &mut t, &auth_root);
+        let names = [
+            "VRF_BASE input/x/input variable", "VRF_BASE input/y/input variable",
+            "vrf/x/input variable", "vrf/y/input variable",
+            "extra/input variable", "anchor/input variable",
+        ];
+        assert_eq!(inputs.len(), names.len());
+        for (i,(x,name)) in inputs.iter().zip(names.iter()).enumerate() {
+            assert_eq!(cs.get_input(i + 1, name), *x);
+        }
+    }
+
+    #[test]
+    fn test_public_input_schema() {
+        let depth = 2;
+        let mut rng = ::rand_core::OsRng;
+        let sk = SecretKey::<Bls12>::from_rng(&mut rng);
+        let vrf_input = VRFInput::<Bls12>::new_malleable(crate::signing_context(b"schema").bytes(b"meow"));
+        let instance = RingVRF {
+            depth,
+            sk: Some(sk),
+            vrf_input: Some(vrf_input.as_point().clone()),
+            extra: Some(Fr::one()),
+            copath: Some(RingSecretCopath::random(depth, &mut rng)),
+        };
+        let mut cs = TestConstraintSystem::<Fr>::new();
+        instance.synthesize(&mut cs).unwrap();
+
+        let schema = public_input_schema();
+        assert_eq!(schema.iter().map( |f| f.width ).sum::<usize>() + 1, cs.num_inputs());
+    }
+
+    #[test]
+    fn test_ring_transition() {
+        let depth = 4;
+        let mut rng = ::rand_core::OsRng;
+
+        let sk = SecretKey::<Bls12>::from_rng(&mut rng);
+        let pk = sk.to_public();
+        let other = SecretKey::<Bls12>::from_rng(&mut rng).to_public();
+
+        let t = crate::signing_context(b"Hello World!").bytes(&rng.next_u64().to_le_bytes()[..]);
+        let vrf_input = VRFInput::<Bls12>::new_malleable(t);
+
+        let copath_old = RingSecretCopath::random(depth, &mut rng);
+        let copath_new = RingSecretCopath::random(depth, &mut rng);
+        let root_old = copath_old.to_root(&pk);
+        let root_new = copath_new.to_root(&pk);
+
+        let instance = |copath_old: &RingSecretCopath<Bls12>, copath_new: &RingSecretCopath<Bls12>| RingVRFTransition {
+            depth,
+            sk: Some(sk.clone()),
+            vrf_input: Some(vrf_input.as_point().clone()),
+            extra: Some(Fr::zero()),
+            copath_old: Some(copath_old.clone()),
+            copath_new: Some(copath_new.clone()),
+        };
+
+        let mut cs = TestConstraintSystem::<Fr>::new();
+        instance(&copath_old, &copath_new).synthesize(&mut cs).unwrap();
+        assert!(cs.is_satisfied());
+        assert_eq!(cs.num_inputs(), 7 + 1);
+        assert_eq!(cs.get_input(6, "old anchor/input variable"), root_old.0);
+        assert_eq!(cs.get_input(7, "new anchor/input variable"), root_new.0);
+
+        // Our key is not in the ring whose copath leads `other` to `root_other`,
+        // so claiming that root as the new anchor violates the constraints.
+        let copath_other = RingSecretCopath::random(depth, &mut rng);
+        let root_other = copath_other.to_root(&other);
+        let mut cs = TestConstraintSystem::<Fr>::new();
+        instance(&copath_old, &copath_other).synthesize(&mut cs).unwrap();
+        assert!(cs.is_satisfied());
+        cs.set("new anchor/input variable", root_other.0);
+        assert!(! cs.is_satisfied());
+
+        let mut cs = TestConstraintSystem::<Fr>::new();
+        let short = RingSecretCopath::random(depth - 1, &mut rng);
+        assert!(instance(&copath_old, &short).synthesize(&mut cs).is_err());
+    }
+
+    #[cfg(feature = "audit_tag")]
+    #[test]
+    fn test_ring_audit() {
+        use crate::audit::{AuditTag, auditor_decrypt};
+
+        let depth = 4;
+        let mut rng = ::rand_core::OsRng;
+        let params = <Bls12 as JubjubEngineWithParams>::params();
+
+        let sk = SecretKey::<Bls12>::from_rng(&mut rng);
+        let auditor = SecretKey::<Bls12>::from_rng(&mut rng);
+        let auditor_pk = auditor.to_public().0.as_prime_order(params).unwrap();
+
+        let t = crate::signing_context(b"Hello World!").bytes(&rng.next_u64().to_le_bytes()[..]);
+        let vrf_input = VRFInput::<Bls12>::new_malleable(t);
+
+        let pks = (0..11).map( |_| SecretKey::<Bls12>::from_rng(&mut rng).to_public() )
+            .chain(::std::iter::once(sk.to_public()))
+            .collect::<Vec<_>>();
+        let (copath, root) = RingSecretCopath::<Bls12>::from_publickeys(&pks, 11, depth as usize);
+        assert_eq!(copath.index(), 11);
+
+        let r = <<Bls12 as JubjubEngine>::Fs as Field>::random(&mut rng);
+        let tag = AuditTag::<Bls12>::new(copath.index(), &r, &auditor_pk);
+
+        let instance = RingVRFAudit {
+            depth,
+            sk: Some(sk.clone()),
+            vrf_input: Some(vrf_input.as_point().clone()),
+            extra: Some(Fr::zero()),
+            copath: Some(copath),
+            auditor: Some(auditor_pk.clone()),
+            audit_randomness: Some(r),
+        };
+
+        let mut cs = TestConstraintSystem::<Fr>::new();
+        instance.synthesize(&mut cs).unwrap();
+        assert!(cs.is_satisfied());
+        assert_eq!(cs.num_inputs(), 12 + 1);
+        assert_eq!(cs.get_input(6, "anchor/input variable"), root.0);
+        assert_eq!(cs.get_input(7, "audit ephemeral/x/input variable"), tag.ephemeral.to_xy().0);
+        assert_eq!(cs.get_input(9, "auditor/x/input variable"), auditor_pk.to_xy().0);
+        assert_eq!(cs.get_input(11, "audit ciphertext/x/input variable"), tag.ciphertext.to_xy().0);
+        assert_eq!(cs.get_input(12, "audit ciphertext/y/input variable"), tag.ciphertext.to_xy().1);
+
+        assert_eq!(auditor_decrypt(&tag, &auditor, depth).unwrap(), 11);
+        assert!(auditor_decrypt(&tag, &sk, depth).is_err());
+    }
+
+    #[test]
+    fn test_public_input_encoding() {
+        let fx = testing::RingFixture::shared();
+        let (sk, copath, auth_root) = fx.signer();
+        let extra = || signing_context(b"layout").bytes(b"extra");
+        let input = VRFInput::<Bls12>::new_malleable(signing_context(b"layout").bytes(b"meow"));
+        let (inout, proof) = sk.ring_vrf_sign_first(input, extra(), copath, fx.srs()).unwrap();
+
+        // Assemble the public inputs from the documented schema alone.
+        let mut manual = Vec::new();
+        for field in public_input_schema() {
+            let start = manual.len();
+            match field.name {
+                "vrf_input" => {
+                    let (x, y) = inout.input.as_point().to_xy();
+                    manual.extend_from_slice(&[x, y]);
+                },
+                "vrf_output" => {
+                    let (x, y) = inout.output.as_point().to_xy();
+                    manual.extend_from_slice(&[x, y]);
+                },
+                "extra" => manual.push(crate::vrf::extra_scalar::<Bls12,_>(extra())),
+                "ring_root" => manual.push(auth_root.0.clone()),
+                name => panic!("Undocumented public input {}", name),
+            }
+            assert_eq!(manual.len() - start, field.width);
+        }
+        assert_eq!(manual.len() + 1, fx.params.vk.ic.len());
+        assert_eq!(manual, ring_vrf_debug_public_inputs(&inout, extra(), &auth_root));
+        assert!(groth16::verify_proof(&fx.pvk, &proof, &manual[..]).is_ok());
+        assert!(auth_root.ring_vrf_verify(inout, extra(), proof, &fx.pvk).unwrap());
     }
 }
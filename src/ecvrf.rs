@@ -0,0 +1,254 @@
+// Copyright (c) 2026 The ring-vrf contributors
+//
+// Licensed under the MIT license, see LICENSE.
+
+//! ### Standalone ECVRF over JubJub
+//!
+//! We follow the ECVRF construction from RFC 9381, formerly
+//! https://datatracker.ietf.org/doc/draft-irtf-cfrg-vrf/ ,
+//! with its encodings of the proof `pi = Gamma || c || s`, its
+//! challenge generation, and its proof to hash, but instantiated for
+//! JubJub using SHA-512 and our RFC 9380 hash-to-curve from `crate::h2c`.
+//!
+//! We produce no anonymity here, unlike ring VRFs, and unlike our
+//! Schnorr VRF in `crate::schnorr` we never use merlin transcripts.
+//! Integers are little endian, as in the RFC's Edwards curve suites.
+//!
+//! RFC 9381 registers no suite for JubJub, so we take `SUITE_STRING`
+//! from the private use range.  We know of no other implementation of
+//! this suite, nor any published test vectors, so we promise only that
+//! we follow the RFC's steps, not interoperability.
+
+use std::io;
+use std::ops::{AddAssign, MulAssign};
+
+use sha2::{Digest, Sha512};
+use zcash_primitives::jubjub::{FixedGenerators, JubjubParams, Unknown, edwards::Point};
+
+use crate::{
+    JubjubEngineWithParams, ReadWrite, SignatureResult, signature_error,
+    Scalar, SecretKey, PublicKey, VRFPreOut,
+    scalar_times_generator, read_scalar, write_scalar, clear_cofactor,
+};
+
+
+/// Suite string, which we select from the private use range.
+pub const SUITE_STRING: u8 = 0xF0;
+
+/// Length in bytes of the challenge `c`
+const C_LEN: usize = 16;
+
+/// Length in bytes of the ECVRF output `beta`
+pub const BETA_LEN: usize = 64;
+
+/// Domain separation tag for `encode_to_curve`, which is
+/// `"ECVRF_" || h2c_suite_ID_string || suite_string` as RFC 9381 specifies.
+fn encode_dst() -> Vec<u8> {
+    let mut dst = b"ECVRF_".to_vec();
+    dst.extend_from_slice(crate::h2c::SUITE_ID);
+    dst.push(SUITE_STRING);
+    dst
+}
+
+/// ECVRF proof, consisting of the challenge `c` and response `s`,
+/// which accompanies the output `Gamma` carried by `VRFPreOut`.
+#[derive(Debug, Clone)]
+pub struct EcvrfProof<E: JubjubEngineWithParams> {
+    c: [u8; C_LEN],
+    s: Scalar<E>,
+}
+
+impl<E: JubjubEngineWithParams> ReadWrite for EcvrfProof<E> {
+    fn read<R: io::Read>(mut reader: R) -> io::Result<Self> {
+        let mut c = [0u8; C_LEN];
+        reader.read_exact(&mut c) ?;
+        let s = read_scalar::<E, &mut R>(&mut reader) ?;
+        Ok(EcvrfProof { c, s })
+    }
+
+    fn write<W: io::Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_all(&self.c) ?;
+        write_scalar::<E, &mut W>(&self.s, &mut writer)
+    }
+}
+
+impl<E: JubjubEngineWithParams> EcvrfProof<E> {
+    /// Encode `pi = Gamma || c || s` as RFC 9381 section 5.1 specifies.
+    pub fn to_pi(&self, output: &VRFPreOut<E>) -> Vec<u8> {
+        let mut pi = Vec::new();
+        output.write(&mut pi).expect("Writing to a Vec never fails.");
+        self.write(&mut pi).expect("Writing to a Vec never fails.");
+        pi
+    }
+
+    /// Decode `pi = Gamma || c || s`, rejecting trailing bytes.
+    pub fn from_pi(mut pi: &[u8]) -> io::Result<(VRFPreOut<E>, EcvrfProof<E>)> {
+        let output = VRFPreOut::read(&mut pi) ?;
+        let proof = EcvrfProof::read(&mut pi) ?;
+        if !pi.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "trailing bytes after ECVRF proof"));
+        }
+        Ok((output, proof))
+    }
+}
+
+fn point_to_string<E: JubjubEngineWithParams, Subgroup>(p: &Point<E, Subgroup>) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(32);
+    p.write(&mut bytes).expect("Writing to a Vec never fails.");
+    bytes
+}
+
+/// `ECVRF_encode_to_curve` from RFC 9381 section 5.4.1.2, with the
+/// public key as `encode_to_curve_salt`.
+fn encode_to_curve<E: JubjubEngineWithParams>(pk: &PublicKey<E>, alpha: &[u8]) -> Point<E, Unknown> {
+    let mut msg = point_to_string(&pk.0);
+    msg.extend_from_slice(alpha);
+    crate::h2c::hash_to_curve::<E>(&msg, &encode_dst()).into()
+}
+
+/// `ECVRF_challenge_generation` from RFC 9381 section 5.4.3.
+fn challenge_generation<E: JubjubEngineWithParams>(points: [&Point<E, Unknown>; 5]) -> [u8; C_LEN] {
+    let mut h = Sha512::new();
+    h.input(&[SUITE_STRING, 0x02]);
+    for p in points.iter() {
+        h.input(&point_to_string(*p));
+    }
+    h.input(&[0x00]);
+    let mut c = [0u8; C_LEN];
+    c.copy_from_slice(&h.result()[..C_LEN]);
+    c
+}
+
+/// `ECVRF_nonce_generation` from RFC 9381 section 5.4.2.2, with the
+/// encoding of our secret scalar as `SK`, since our keys hold no
+/// RFC 8032 seed.  We thus ignore our secret key's nonce seed.
+fn nonce_generation<E: JubjubEngineWithParams>(sk: &SecretKey<E>, h_string: &[u8]) -> Scalar<E> {
+    let mut sk_string = Vec::with_capacity(32);
+    write_scalar::<E,_>(&sk.key, &mut sk_string).expect("Writing to a Vec never fails.");
+    let hashed_sk_string = Sha512::digest(&sk_string);
+    let mut h = Sha512::new();
+    h.input(&hashed_sk_string[32..]);
+    h.input(h_string);
+    let mut k_string = h.result().to_vec();
+    k_string.reverse();
+    crate::h2c::field_from_be_bytes(&k_string)
+}
+
+/// `ECVRF_proof_to_hash` from RFC 9381 section 5.2, which multiplies
+/// `Gamma` by the cofactor.
+pub fn ecvrf_proof_to_hash<E: JubjubEngineWithParams>(output: &VRFPreOut<E>) -> [u8; BETA_LEN] {
+    let mut h = Sha512::new();
+    h.input(&[SUITE_STRING, 0x03]);
+    h.input(&point_to_string(&clear_cofactor(&output.0)));
+    h.input(&[0x00]);
+    let mut beta = [0u8; BETA_LEN];
+    beta.copy_from_slice(&h.result());
+    beta
+}
+
+impl<E: JubjubEngineWithParams> SecretKey<E> {
+    /// `ECVRF_prove` from RFC 9381 section 5.1.
+    #[allow(non_snake_case)]
+    pub fn ecvrf_prove(&self, alpha: &[u8]) -> (VRFPreOut<E>, EcvrfProof<E>) {
+        let params = E::params();
+        let pk = self.to_public();
+        let H = encode_to_curve(&pk, alpha);
+        let h_string = point_to_string(&H);
+        let Gamma: Point<E, Unknown> = H.mul(self.key.clone(), params);
+        let k = nonce_generation(self, &h_string);
+        let kB: Point<E, Unknown> = scalar_times_generator(&k).into();
+        let kH = H.mul(k.clone(), params);
+        let c = challenge_generation([&pk.0, &H, &Gamma, &kB, &kH]);
+        // s = k + c x
+        let mut s = crate::misc::scalar_from_u128::<E>(c);
+        s.mul_assign(&self.key);
+        s.add_assign(&k);
+        (VRFPreOut(Gamma), EcvrfProof { c, s })
+    }
+}
+
+impl<E: JubjubEngineWithParams> PublicKey<E> {
+    /// `ECVRF_verify` from RFC 9381 section 5.3, returning `beta`
+    /// if the proof is valid.
+    #[allow(non_snake_case)]
+    pub fn ecvrf_verify(&self, alpha: &[u8], output: &VRFPreOut<E>, proof: &EcvrfProof<E>)
+     -> SignatureResult<[u8; BETA_LEN]>
+    {
+        let params = E::params();
+        if clear_cofactor(&self.0) == Point::zero() {
+            return Err(signature_error("ECVRF public key has small order"));
+        }
+        let Gamma = &output.0;
+        let H = encode_to_curve(self, alpha);
+        let c = crate::misc::scalar_from_u128::<E>(proof.c);
+        let B: Point<E, Unknown> = params.generator(FixedGenerators::SpendingKeyGenerator).clone().into();
+        // U = s B - c Y, V = s H - c Gamma
+        let U = B.mul(proof.s.clone(), params).add(&self.0.mul(c.clone(), params).negate(), params);
+        let V = H.mul(proof.s.clone(), params).add(&Gamma.mul(c, params).negate(), params);
+        if challenge_generation([&self.0, &H, Gamma, &U, &V]) == proof.c {
+            Ok(ecvrf_proof_to_hash(output))
+        } else {
+            Err(signature_error("ECVRF proof validation failed"))
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use pairing::bls12_381::Bls12;
+
+    use super::*;
+
+    #[test]
+    fn ecvrf_roundtrip() {
+        let sk = SecretKey::<Bls12>::from_rng(::rand_core::OsRng);
+        let pk = sk.to_public();
+        let (output, proof) = sk.ecvrf_prove(b"sample");
+
+        let beta = pk.ecvrf_verify(b"sample", &output, &proof).unwrap();
+        assert_eq!(beta[..], ecvrf_proof_to_hash(&output)[..]);
+
+        // Proving is deterministic, so outputs and proofs repeat.
+        let (output2, proof2) = sk.ecvrf_prove(b"sample");
+        assert_eq!(proof.to_pi(&output), proof2.to_pi(&output2));
+
+        let pi = proof.to_pi(&output);
+        assert_eq!(pi.len(), 32 + C_LEN + 32);
+        let (output3, proof3) = EcvrfProof::<Bls12>::from_pi(&pi).unwrap();
+        assert_eq!(pk.ecvrf_verify(b"sample", &output3, &proof3).unwrap()[..], beta[..]);
+        assert!(EcvrfProof::<Bls12>::from_pi(&pi[..pi.len()-1]).is_err());
+    }
+
+    #[test]
+    fn ecvrf_nonce_from_secret_scalar() {
+        let sk = SecretKey::<Bls12>::from_rng(::rand_core::OsRng);
+        let mut reseeded = sk.clone();
+        reseeded.nonce_seed = [7u8; 32];
+
+        // RFC 9381 derives nonces from the secret scalar alone.
+        assert!(nonce_generation(&sk, b"h_string") == nonce_generation(&reseeded, b"h_string"));
+        assert!(nonce_generation(&sk, b"h_string") != nonce_generation(&sk, b"other"));
+
+        let (output, proof) = sk.ecvrf_prove(b"sample");
+        let (output2, proof2) = reseeded.ecvrf_prove(b"sample");
+        assert_eq!(proof.to_pi(&output), proof2.to_pi(&output2));
+    }
+
+    #[test]
+    fn ecvrf_rejects_forgeries() {
+        let sk = SecretKey::<Bls12>::from_rng(::rand_core::OsRng);
+        let other = SecretKey::<Bls12>::from_rng(::rand_core::OsRng);
+        let pk = sk.to_public();
+        let (output, proof) = sk.ecvrf_prove(b"sample");
+
+        assert!(pk.ecvrf_verify(b"test", &output, &proof).is_err());
+        assert!(other.to_public().ecvrf_verify(b"sample", &output, &proof).is_err());
+        let (other_output, _) = other.ecvrf_prove(b"sample");
+        assert!(pk.ecvrf_verify(b"sample", &other_output, &proof).is_err());
+
+        let mut bad = proof.clone();
+        bad.c[0] ^= 1;
+        assert!(pk.ecvrf_verify(b"sample", &output, &bad).is_err());
+    }
+}
@@ -0,0 +1,381 @@
+// Copyright (c) 2026 The ring-vrf contributors
+//
+// Licensed under the MIT license, see LICENSE.
+
+//! ### Simple ring VRF signing and verification
+//!
+//! We bundle the secret key, copath, and proving key into one
+//! `RingVrfSigner`, and the ring root and verifying key into one
+//! `RingVrfVerifier`, so that users who simply sign messages never
+//! assemble transcripts themselves.
+//!
+//! We derive VRF inputs using `VRFInput::new_ring_malleable` from the
+//! signing context and message, and sign no extra message.  Advanced
+//! users should call the methods on `SecretKey` and `RingRoot` directly.
+
+use std::io;
+use std::convert::{TryFrom, TryInto};
+
+use bellman::groth16;
+use pairing::MultiMillerLoop;
+
+use crate::{
+    JubjubEngineWithParams, ReadWrite, SynthesisResult, RingSRS,
+    SecretKey, RingSecretCopath, RingRoot, RingVRFProof,
+    VRFInput, VRFPreOut, signing_context, signature_error, vrf::no_extra,
+};
+
+
+/// Context for `VRFInOut::make_bytes` in `RingVrfVerifier::verify`.
+const OUTPUT_CONTEXT: &[u8] = b"ring-vrf-output";
+
+/// VRF pre-output along with its ring VRF proof, which serializes
+/// as one blob.
+#[derive(Clone)]
+pub struct SignedVrf<E: JubjubEngineWithParams> {
+    pub preout: VRFPreOut<E>,
+    pub proof: RingVRFProof<E>,
+}
+
+impl<E: JubjubEngineWithParams> ReadWrite for SignedVrf<E> {
+    fn read<R: io::Read>(mut reader: R) -> io::Result<Self> {
+        let preout = VRFPreOut::read(&mut reader) ?;
+        let proof = RingVRFProof::read(&mut reader) ?;
+        Ok(SignedVrf { preout, proof })
+    }
+
+    fn write<W: io::Write>(&self, mut writer: W) -> io::Result<()> {
+        self.preout.write(&mut writer) ?;
+        self.proof.write(&mut writer)
+    }
+}
+
+/// Version byte of our `SignedVrfBundle` framing.
+const BUNDLE_VERSION: u8 = 1;
+
+/// Largest proof length `SignedVrfBundle::read_framed` accepts, which
+/// exceeds any Groth16 proof, but limits allocations by peers.
+const MAX_FRAMED_PROOF: u32 = 1024;
+
+/// VRF pre-output, ring root, and ring VRF proof, which we usually
+/// transmit together.
+#[derive(Clone)]
+pub struct SignedVrfBundle<E: JubjubEngineWithParams> {
+    pub preout: VRFPreOut<E>,
+    pub auth_root: RingRoot<E>,
+    pub proof: RingVRFProof<E>,
+}
+
+/// Checksum over all preceding bytes of a frame.
+fn bundle_checksum(frame: &[u8]) -> [u8; 8] {
+    let mut t = ::merlin::Transcript::new(b"SignedVrfBundle");
+    t.append_message(b"frame", frame);
+    let mut checksum = [0u8; 8];
+    t.challenge_bytes(b"checksum", &mut checksum);
+    checksum
+}
+
+impl<E: JubjubEngineWithParams> SignedVrfBundle<E> {
+    /// Write a self describing frame consisting of a version byte,
+    /// the 32 byte pre-output and ring root, the proof prefixed by its
+    /// little endian `u32` length, and an 8 byte checksum.
+    pub fn write_framed<W: io::Write>(&self, mut writer: W) -> io::Result<()> {
+        let mut frame = vec![BUNDLE_VERSION];
+        self.preout.write(&mut frame) ?;
+        self.auth_root.write(&mut frame) ?;
+        let mut proof = Vec::new();
+        self.proof.write(&mut proof) ?;
+        frame.extend_from_slice(&(proof.len() as u32).to_le_bytes());
+        frame.extend_from_slice(&proof);
+        let checksum = bundle_checksum(&frame);
+        writer.write_all(&frame) ?;
+        writer.write_all(&checksum)
+    }
+
+    /// Read one frame written by `write_framed`, validating each field
+    /// and the checksum.
+    pub fn read_framed<R: io::Read>(mut reader: R) -> io::Result<Self> {
+        let invalid = |msg| io::Error::new(io::ErrorKind::InvalidData, msg);
+        let mut frame = vec![0u8; 1 + 32 + 32 + 4];
+        reader.read_exact(&mut frame) ?;
+        if frame[0] != BUNDLE_VERSION {
+            return Err(invalid("Unknown SignedVrfBundle version"));
+        }
+        let proof_len = u32::from_le_bytes(frame[65..69].try_into().expect("Four byte slice"));
+        if proof_len > MAX_FRAMED_PROOF {
+            return Err(invalid("SignedVrfBundle proof too long"));
+        }
+        let header_len = frame.len();
+        frame.resize(header_len + proof_len as usize, 0);
+        reader.read_exact(&mut frame[header_len..]) ?;
+        let mut checksum = [0u8; 8];
+        reader.read_exact(&mut checksum) ?;
+        if checksum != bundle_checksum(&frame) {
+            return Err(invalid("SignedVrfBundle checksum mismatch"));
+        }
+
+        let preout = VRFPreOut::read(&frame[1..33]) ?;
+        let auth_root = RingRoot::read(&frame[33..65]) ?;
+        let mut proof_bytes = &frame[header_len..];
+        let proof = RingVRFProof::read(&mut proof_bytes) ?;
+        if ! proof_bytes.is_empty() {
+            return Err(invalid("SignedVrfBundle proof has trailing bytes"));
+        }
+        Ok(SignedVrfBundle { preout, auth_root, proof })
+    }
+}
+
+/// Ring VRF signer holding everything required to sign messages.
+pub struct RingVrfSigner<E: JubjubEngineWithParams, P> {
+    context: Vec<u8>,
+    secret: SecretKey<E>,
+    copath: RingSecretCopath<E>,
+    auth_root: RingRoot<E>,
+    proving_key: RingSRS<P>,
+}
+
+impl<E,P> RingVrfSigner<E,P>
+where E: JubjubEngineWithParams, P: groth16::ParameterSource<E> + Copy,
+{
+    /// Prepare to sign messages in the signing `context` as the ring
+    /// member with `secret` and `copath`.
+    pub fn new(context: &[u8], secret: SecretKey<E>, copath: RingSecretCopath<E>, proving_key: RingSRS<P>) -> Self {
+        let auth_root = copath.to_root(&secret.to_public());
+        RingVrfSigner { context: context.to_vec(), secret, copath, auth_root, proving_key }
+    }
+
+    /// Ring root for which we sign.
+    pub fn auth_root(&self) -> &RingRoot<E> {  &self.auth_root  }
+
+    /// Sign `message`, producing the VRF pre-output and ring VRF proof.
+    pub fn sign(&self, message: &[u8]) -> SynthesisResult<SignedVrf<E>> {
+        let t = signing_context(&self.context).bytes(message);
+        let inout = VRFInput::new_ring_malleable(t, &self.auth_root).to_inout(&self.secret);
+        let (preout, proof) = self.secret.ring_vrf_sign_checked(
+            inout, no_extra(), self.copath.clone(), self.proving_key
+        ) ?;
+        Ok(SignedVrf { preout, proof })
+    }
+}
+
+/// Ring VRF verifier holding everything required to verify messages.
+pub struct RingVrfVerifier<E: JubjubEngineWithParams + MultiMillerLoop> {
+    context: Vec<u8>,
+    auth_root: RingRoot<E>,
+    verifying_key: groth16::PreparedVerifyingKey<E>,
+}
+
+impl<E: JubjubEngineWithParams + MultiMillerLoop> RingVrfVerifier<E> {
+    /// Prepare to verify messages signed in the signing `context` by
+    /// members of the ring with root `auth_root`.
+    pub fn new(context: &[u8], auth_root: RingRoot<E>, verifying_key: &groth16::VerifyingKey<E>) -> Self {
+        let verifying_key = groth16::prepare_verifying_key(verifying_key);
+        RingVrfVerifier { context: context.to_vec(), auth_root, verifying_key }
+    }
+
+    /// Verify `signed` on `message`, returning the 32 byte VRF output.
+    ///
+    /// We return an `io::ErrorKind::InvalidData` error for invalid proofs.
+    pub fn verify(&self, message: &[u8], signed: &SignedVrf<E>) -> SynthesisResult<[u8; 32]> {
+        let t = signing_context(&self.context).bytes(message);
+        let inout = signed.preout.attach_input_ring_malleable(t, &self.auth_root);
+        let output = inout.make_bytes::<[u8; 32]>(OUTPUT_CONTEXT);
+        if self.auth_root.ring_vrf_verify(inout, no_extra(), signed.proof.clone(), &self.verifying_key) ? {
+            Ok(output)
+        } else {
+            Err(crate::verifier::invalid_proof_error())
+        }
+    }
+}
+
+
+/// Largest message `verify_file_jsonl` accepts, which limits
+/// allocations from corrupt length prefixes.
+#[cfg(feature = "jsonl")]
+const MAX_RECORD_MESSAGE: u32 = 1 << 20;
+
+/// Write one record of the file format `verify_file_jsonl` reads,
+/// consisting of the little endian `u32` message length, the message,
+/// and then `bundle` framed by `SignedVrfBundle::write_framed`.
+///
+/// We reject messages longer than `verify_file_jsonl` accepts.
+#[cfg(feature = "jsonl")]
+pub fn write_signed_record<E,W>(mut writer: W, message: &[u8], bundle: &SignedVrfBundle<E>) -> io::Result<()>
+where E: JubjubEngineWithParams, W: io::Write,
+{
+    let len = u32::try_from(message.len()).ok()
+        .filter( |len| *len <= MAX_RECORD_MESSAGE )
+        .ok_or( signature_error("Record message too long") ) ?;
+    writer.write_all(&len.to_le_bytes()) ?;
+    writer.write_all(message) ?;
+    bundle.write_framed(writer)
+}
+
+/// Read one record written by `write_signed_record`, or `None` at a
+/// clean end of input.
+#[cfg(feature = "jsonl")]
+fn read_signed_record<E,R>(mut reader: R) -> io::Result<Option<(Vec<u8>, SignedVrfBundle<E>)>>
+where E: JubjubEngineWithParams, R: io::Read,
+{
+    let mut len = [0u8; 4];
+    let mut filled = 0;
+    while filled < len.len() {
+        match reader.read(&mut len[filled..]) ? {
+            0 if filled == 0 => return Ok(None),
+            0 => return Err(io::ErrorKind::UnexpectedEof.into()),
+            n => filled += n,
+        }
+    }
+    let len = u32::from_le_bytes(len);
+    if len > MAX_RECORD_MESSAGE {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "Record message too long"));
+    }
+    let mut message = vec![0u8; len as usize];
+    reader.read_exact(&mut message) ?;
+    Ok(Some((message, SignedVrfBundle::read_framed(reader) ?)))
+}
+
+/// Verify every record of `input`, as written by `write_signed_record`,
+/// writing one JSON object per line to `output`, and returning the
+/// number of records read.
+///
+/// We write `{"index":i,"ok":true,"randomness":hex}` for valid records,
+/// with `RingVrfVerifier::verify`'s output in lowercase hex, and
+/// `{"index":i,"ok":false,"error":msg}` for invalid ones.  We cannot
+/// find the next record after a malformed one, so we report it like an
+/// invalid record and stop.  We return only errors writing `output`.
+#[cfg(feature = "jsonl")]
+pub fn verify_file_jsonl<E,R,W>(mut input: R, mut output: W, verifier: &RingVrfVerifier<E>) -> io::Result<usize>
+where E: JubjubEngineWithParams + MultiMillerLoop, R: io::Read, W: io::Write,
+{
+    use ::serde_json::json;
+    use crate::misc::Hex;
+
+    let mut index = 0;
+    loop {
+        let line = match read_signed_record::<E,_>(&mut input) {
+            Ok(None) => return Ok(index),
+            Ok(Some((_, bundle))) if bundle.auth_root != verifier.auth_root =>
+                json!({ "index": index, "ok": false, "error": "Record signed for another ring" }),
+            Ok(Some((message, SignedVrfBundle { preout, proof, .. }))) => match verifier.verify(&message, &SignedVrf { preout, proof }) {
+                Ok(randomness) => json!({ "index": index, "ok": true, "randomness": Hex(&randomness).to_string() }),
+                Err(e) => json!({ "index": index, "ok": false, "error": e.to_string() }),
+            },
+            Err(e) => {
+                let line = json!({ "index": index, "ok": false, "error": e.to_string() });
+                ::serde_json::to_writer(&mut output, &line) ?;
+                output.write_all(b"\n") ?;
+                return Ok(index + 1);
+            },
+        };
+        ::serde_json::to_writer(&mut output, &line) ?;
+        output.write_all(b"\n") ?;
+        index += 1;
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use pairing::bls12_381::Bls12;
+
+    use super::*;
+
+    #[test]
+    fn bundle_framing() {
+        let mut rng = ::rand_core::OsRng;
+        let fx = crate::testing::RingFixture::shared();
+        let depth = fx.depth;
+        let sk = SecretKey::<Bls12>::from_rng(&mut rng);
+        let copath = RingSecretCopath::random(depth, &mut rng);
+        let signer = RingVrfSigner::new(b"bundle", sk, copath, fx.srs());
+        let SignedVrf { preout, proof } = signer.sign(b"meow").unwrap();
+        let bundle = SignedVrfBundle { preout, auth_root: signer.auth_root().clone(), proof };
+
+        let mut bytes = Vec::new();
+        bundle.write_framed(&mut bytes).unwrap();
+        let read = SignedVrfBundle::<Bls12>::read_framed(&bytes[..]).unwrap();
+        assert!(read.auth_root == bundle.auth_root);
+        assert!(read.proof == bundle.proof);
+        assert!(read.preout.as_point() == bundle.preout.as_point());
+
+        let mut corrupted = bytes.clone();
+        *corrupted.last_mut().unwrap() ^= 1;
+        assert!(SignedVrfBundle::<Bls12>::read_framed(&corrupted[..]).is_err());
+        let mut corrupted = bytes.clone();
+        corrupted[40] ^= 1;
+        assert!(SignedVrfBundle::<Bls12>::read_framed(&corrupted[..]).is_err());
+        for len in [0, 1, 60, bytes.len() - 1].iter() {
+            assert!(SignedVrfBundle::<Bls12>::read_framed(&bytes[..*len]).is_err());
+        }
+    }
+
+    #[test]
+    fn facade_round_trip() {
+        let mut rng = ::rand_core::OsRng;
+        let fx = crate::testing::RingFixture::shared();
+        let depth = fx.depth;
+
+        let sk = SecretKey::<Bls12>::from_rng(&mut rng);
+        let copath = RingSecretCopath::random(depth, &mut rng);
+        let signer = RingVrfSigner::new(b"facade", sk, copath, fx.srs());
+        let verifier = RingVrfVerifier::new(b"facade", signer.auth_root().clone(), &fx.params.vk);
+
+        let signed = signer.sign(b"meow").unwrap();
+        let mut bytes = Vec::new();
+        signed.write(&mut bytes).unwrap();
+        let signed = SignedVrf::<Bls12>::read(&bytes[..]).unwrap();
+
+        let output = verifier.verify(b"meow", &signed).unwrap();
+        assert_eq!(output, verifier.verify(b"meow", &signed).unwrap());
+        assert!(verifier.verify(b"woof", &signed).is_err());
+        let other = RingVrfVerifier::new(b"other", signer.auth_root().clone(), &fx.params.vk);
+        assert!(other.verify(b"meow", &signed).is_err());
+    }
+
+    #[cfg(feature = "jsonl")]
+    #[test]
+    fn verify_jsonl() {
+        let mut rng = ::rand_core::OsRng;
+        let fx = crate::testing::RingFixture::shared();
+        let depth = fx.depth;
+        let sk = SecretKey::<Bls12>::from_rng(&mut rng);
+        let copath = RingSecretCopath::random(depth, &mut rng);
+        let signer = RingVrfSigner::new(b"jsonl", sk, copath, fx.srs());
+        let verifier = RingVrfVerifier::new(b"jsonl", signer.auth_root().clone(), &fx.params.vk);
+
+        let meow = signer.sign(b"meow").unwrap();
+        let bundle = SignedVrfBundle { preout: meow.preout.clone(), auth_root: signer.auth_root().clone(), proof: meow.proof.clone() };
+        let mut file = Vec::new();
+        write_signed_record(&mut file, b"meow", &bundle).unwrap();
+        write_signed_record(&mut file, b"woof", &bundle).unwrap();
+        let mut lines = Vec::new();
+        assert_eq!(verify_file_jsonl(&file[..], &mut lines, &verifier).unwrap(), 2);
+
+        let lines = String::from_utf8(lines).unwrap();
+        let lines = lines.lines().map( |l| ::serde_json::from_str::<::serde_json::Value>(l).unwrap() ).collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+        let randomness = verifier.verify(b"meow", &meow).unwrap();
+        let hex = randomness.iter().map( |b| format!("{:02x}", b) ).collect::<String>();
+        assert_eq!(lines[0], ::serde_json::json!({ "index": 0, "ok": true, "randomness": hex }));
+        assert_eq!(lines[1]["index"], 1);
+        assert_eq!(lines[1]["ok"], false);
+        assert!(lines[1]["error"].is_string());
+
+        // A truncated final record yields one error line and stops.
+        file.truncate(file.len() - 1);
+        let mut lines = Vec::new();
+        assert_eq!(verify_file_jsonl(&file[..], &mut lines, &verifier).unwrap(), 2);
+        let lines = String::from_utf8(lines).unwrap();
+        let last = ::serde_json::from_str::<::serde_json::Value>(lines.lines().last().unwrap()).unwrap();
+        assert_eq!(last["index"], 1);
+        assert_eq!(last["ok"], false);
+
+        // Oversized messages never reach the file.
+        let long = vec![0u8; MAX_RECORD_MESSAGE as usize + 1];
+        match write_signed_record(&mut Vec::new(), &long, &bundle) {
+            Err(e) => assert_eq!(e.kind(), io::ErrorKind::InvalidInput),
+            Ok(()) => panic!("Wrote an unreadable record"),
+        }
+    }
+}
@@ -0,0 +1,98 @@
+// Copyright (c) 2026 The ring-vrf contributors
+//
+// Licensed under the MIT license, see LICENSE.
+
+//! ### Fuzzing entry points for our deserialization surface
+//!
+//! We only build these under `cargo fuzz`, which sets `--cfg fuzzing`,
+//! and for our own tests.
+//! All readers must return `Ok` or `Err` on arbitrary bytes, so any
+//! panic or abort reported by libfuzzer is a bug.
+//!
+//! We audited the read paths:  `Point::read` rejects non-canonical
+//! and off curve encodings with `io::Error`, `RingRoot::read` rejects
+//! non-canonical field elements, and `RingSecretCopath::read` no longer
+//! preallocates based upon the attacker supplied length.
+
+use pairing::bls12_381::Bls12;
+use bellman::groth16;
+
+use crate::{ReadWrite, VRFPreOut, RingRoot, RingSecretCopath};
+
+
+/// Feed arbitrary bytes into `VRFPreOut::read`.
+pub fn fuzz_vrf_output_read(data: &[u8]) {
+    let _ = VRFPreOut::<Bls12>::read(data);
+}
+
+/// Feed arbitrary bytes into `groth16::Proof::read`, aka `RingVRFProof`.
+pub fn fuzz_proof_read(data: &[u8]) {
+    let _ = groth16::Proof::<Bls12>::read(data);
+}
+
+/// Feed arbitrary bytes into `RingRoot::read` and `RingSecretCopath::read`.
+pub fn fuzz_auth_root_read(data: &[u8]) {
+    let _ = RingRoot::<Bls12>::read(data);
+    let _ = RingSecretCopath::<Bls12>::read(data);
+}
+
+
+#[cfg(test)]
+mod tests {
+    use rand_core::RngCore;
+
+    use super::*;
+    use crate::{testing, signing_context, VRFInput, vrf::no_extra};
+
+    #[test]
+    fn readers_reject_truncated_and_random_bytes() {
+        let mut rng = ::rand_core::OsRng;
+        let fx = testing::RingFixture::shared();
+        let (sk, copath, auth_root) = fx.signer();
+        let input = VRFInput::<Bls12>::new_malleable(signing_context(b"fuzz").bytes(b"meow"));
+        let (inout, proof) = sk.ring_vrf_sign_first(input, no_extra(), copath.clone(), fx.srs()).unwrap();
+
+        let mut preout_bytes = Vec::new();
+        inout.output.write(&mut preout_bytes).unwrap();
+        let mut proof_bytes = Vec::new();
+        proof.write(&mut proof_bytes).unwrap();
+        let mut root_bytes = Vec::new();
+        auth_root.write(&mut root_bytes).unwrap();
+        let mut copath_bytes = Vec::new();
+        copath.write(&mut copath_bytes).unwrap();
+
+        // Every reader, as an `is_err` check, with a valid encoding.
+        let readers: [(&str, fn(&[u8]) -> bool, Vec<u8>); 4] = [
+            ("VRFPreOut", |d| VRFPreOut::<Bls12>::read(d).is_err(), preout_bytes),
+            ("Proof", |d| groth16::Proof::<Bls12>::read(d).is_err(), proof_bytes),
+            ("RingRoot", |d| RingRoot::<Bls12>::read(d).is_err(), root_bytes),
+            ("RingSecretCopath", |d| RingSecretCopath::<Bls12>::read(d).is_err(), copath_bytes),
+        ];
+
+        for (name, is_err, bytes) in readers.iter() {
+            assert!(! is_err(bytes), "{} rejects its own encoding", name);
+            for len in 0..bytes.len() {
+                assert!(is_err(&bytes[..len]), "{} accepts {} of {} bytes", name, len, bytes.len());
+            }
+
+            for _ in 0..256 {
+                let mut random = vec![0u8; bytes.len()];
+                rng.fill_bytes(&mut random);
+                // Random bytes may encode valid points and field elements,
+                // so we make the leading element unrepresentable first.
+                match *name {
+                    "VRFPreOut" => random[31] |= 0x7f,
+                    "Proof" => random[0] = 0xff,
+                    "RingRoot" => random[31] = 0xff,
+                    _ => random[..4].copy_from_slice(&[0xff; 4]),
+                }
+                assert!(is_err(&random), "{} accepts random bytes", name);
+
+                // Arbitrary lengths must never panic.
+                let mut random = vec![0u8; (rng.next_u32() % 512) as usize];
+                rng.fill_bytes(&mut random);
+                let _ = is_err(&random);
+            }
+        }
+    }
+}
@@ -7,10 +7,17 @@
 //! ### Ring VRF zkSNARK SRS generator
 
 
+use std::{fs, io::{self, Write}, path::Path};
+
 use bellman::groth16;
 
-use crate::{rand_hack, JubjubEngineWithParams, SynthesisResult};
+use crate::{
+    rand_hack, JubjubEngineWithParams, SynthesisResult, RingSRS,
+    SecretKey, RingSecretCopath, VRFInput, vrf::no_extra,
+};
 use group::WnafGroup;
+use rand_core::RngCore;
+use pairing::MultiMillerLoop;
 
 
 /// Generates structured (meaning circuit-depending) Groth16
@@ -21,6 +28,24 @@ pub fn generate_crs<E: JubjubEngineWithParams>(depth: u32)
 where
     E::G1: WnafGroup,
     E::G2: WnafGroup,
+{
+    #[cfg(debug_assertions)]
+    E::self_check().map_err(::bellman::SynthesisError::IoError) ?;
+    generate_crs_with_rng::<E,_>(depth, &mut rand_hack())
+}
+
+/// Generates the CRS like `generate_crs`, but using the given randomness
+/// source, so tests may reproduce a CRS from a seed.
+///
+/// We caution that anyone who knows `rng`'s output learns the toxic
+/// waste, and may forge proofs, so never deploy a seeded CRS.
+pub fn generate_crs_with_rng<E, R>(depth: u32, rng: &mut R)
+ -> SynthesisResult<groth16::Parameters<E>>
+where
+    E: JubjubEngineWithParams,
+    E::G1: WnafGroup,
+    E::G2: WnafGroup,
+    R: RngCore,
 {
     let circuit = crate::circuit::RingVRF::<E> {
         depth,
@@ -29,5 +54,300 @@ where
         extra: None,
         copath: None,
     };
+    groth16::generate_random_parameters(circuit, rng)
+}
+
+/// Generates the CRS like `generate_crs`, unless a previous run already
+/// wrote the CRS to `checkpoint`, in which case we load it instead.
+///
+/// Bellman's `generate_random_parameters` cannot be stepped, so our only
+/// checkpoint is the final CRS, but we write it atomically by renaming
+/// a completed temporary file.  A crash during generation thus never
+/// leaves a truncated CRS at `checkpoint`, and simply rerunning resumes.
+///
+/// We reject a checkpoint generated for another depth with an
+/// `io::ErrorKind::InvalidInput` error, but never overwrite it.
+pub fn generate_crs_resumable<E, P>(depth: u32, checkpoint: P)
+ -> SynthesisResult<groth16::Parameters<E>>
+where
+    E: JubjubEngineWithParams,
+    E::G1: WnafGroup,
+    E::G2: WnafGroup,
+    P: AsRef<Path>,
+{
+    let checkpoint = checkpoint.as_ref();
+    if let Ok(f) = fs::File::open(checkpoint) {
+        let loaded = groth16::Parameters::read(io::BufReader::new(f), false) ?;
+        crs_check_depth(RingSRS { srs: &loaded, depth }) ?;
+        return Ok(loaded);
+    }
+
+    let crs = generate_crs::<E>(depth) ?;
+    let partial = checkpoint.with_extension("partial");
+    {
+        let mut f = io::BufWriter::new(fs::File::create(&partial) ?);
+        crs.write(&mut f) ?;
+        f.flush() ?;
+        f.get_ref().sync_all() ?;
+    }
+    fs::rename(&partial, checkpoint) ?;
+    Ok(crs)
+}
+
+/// Check end to end that a CRS can produce and verify a proof.
+///
+/// We prove and verify for a throwaway key in a random ring of the
+/// CRS' depth.  We therefore spend one proof and one verification,
+/// but catch corruption that structural checks on the CRS miss.
+/// We return an `io::ErrorKind::InvalidData` error if our proof fails
+/// to verify.
+pub fn crs_self_test<E>(crs: RingSRS<&groth16::Parameters<E>>) -> SynthesisResult<()>
+where E: JubjubEngineWithParams + MultiMillerLoop,
+{
+    let mut rng = rand_hack();
+    let sk = SecretKey::<E>::from_rng(&mut rng);
+    let copath = RingSecretCopath::random(crs.depth, &mut rng);
+    let auth_root = copath.to_root(&sk.to_public());
+
+    let input = VRFInput::<E>::new_malleable(crate::signing_context(b"CRS self test").bytes(b""));
+    let inout = input.to_inout(&sk);
+    let proof = sk.ring_vrf_prove(input, no_extra(), copath, crs, &mut rng) ?;
+
+    let pvk = groth16::prepare_verifying_key(&crs.srs.vk);
+    if auth_root.ring_vrf_verify(inout, no_extra(), proof, &pvk) ? {
+        Ok(())
+    } else {
+        Err(::bellman::SynthesisError::IoError(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "CRS self test proof failed to verify, so the proving and verifying keys disagree."
+        )))
+    }
+}
+
+/// Check that a CRS was generated for the depth claimed by `crs`.
+///
+/// We embed no depth in the CRS, but the length of its `l` query
+/// equals the number of auxiliary wires, which grows with the depth,
+/// so we compare against a synthesis of the circuit at `crs.depth`.
+/// We return an `io::ErrorKind::InvalidInput` error on mismatch,
+/// instead of failing inside bellman's prover.
+///
+/// Provers accept any `groth16::ParameterSource`, so they never run
+/// this check, but `read_crs` and `generate_crs_resumable` run it once
+/// when loading a CRS.
+pub fn crs_check_depth<E>(crs: RingSRS<&groth16::Parameters<E>>) -> SynthesisResult<()>
+where E: JubjubEngineWithParams,
+{
+    let expected = crate::r1cs::ring_vrf_num_aux::<E>(crs.depth) ?;
+    if crs.srs.l.len() != expected {
+        return Err(::bellman::SynthesisError::IoError(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("CRS has {} auxiliary wires, not the {} of depth {}.", crs.srs.l.len(), expected, crs.depth)
+        )));
+    }
+    Ok(())
+}
+
+/// Generates the Groth16 CRS for the `RingVRFTransition` circuit, which
+/// proves membership of one key in both an old and a new ring.
+///
+/// We cannot reuse the `generate_crs` CRS here because this circuit has
+/// an extra public input for the second ring root.
+pub fn generate_crs_transition<E: JubjubEngineWithParams>(depth: u32)
+ -> SynthesisResult<groth16::Parameters<E>>
+where
+    E::G1: WnafGroup,
+    E::G2: WnafGroup,
+{
+    let circuit = crate::circuit::RingVRFTransition::<E> {
+        depth,
+        sk: None,
+        vrf_input: None,
+        extra: None,
+        copath_old: None,
+        copath_new: None,
+    };
     groth16::generate_random_parameters(circuit, &mut rand_hack())
 }
+
+/// Generates the Groth16 CRS for the `RingVRFBlinded` circuit, which
+/// reveals only a commitment to the VRF output.
+pub fn generate_crs_blinded<E: JubjubEngineWithParams>(depth: u32)
+ -> SynthesisResult<groth16::Parameters<E>>
+where
+    E::G1: WnafGroup,
+    E::G2: WnafGroup,
+{
+    let circuit = crate::circuit::RingVRFBlinded::<E> {
+        ring_vrf: crate::circuit::RingVRF {
+            depth,
+            sk: None,
+            vrf_input: None,
+            extra: None,
+            copath: None,
+        },
+        blinding: None,
+    };
+    groth16::generate_random_parameters(circuit, &mut rand_hack())
+}
+
+/// Generates the Groth16 CRS for the `RingVRFDomain` circuit, which
+/// binds proofs to the application `domain`.
+pub fn generate_crs_domain<E: JubjubEngineWithParams>(depth: u32, domain: &[u8])
+ -> SynthesisResult<groth16::Parameters<E>>
+where
+    E::G1: WnafGroup,
+    E::G2: WnafGroup,
+{
+    let circuit = crate::circuit::RingVRFDomain::<E> {
+        ring_vrf: crate::circuit::RingVRF {
+            depth,
+            sk: None,
+            vrf_input: None,
+            extra: None,
+            copath: None,
+        },
+        domain: crate::circuit::domain_scalar::<E>(domain),
+    };
+    groth16::generate_random_parameters(circuit, &mut rand_hack())
+}
+
+/// Write `crs` preceded by a header naming its trusted setup `epoch`,
+/// so verifiers select and retire verifying keys by epoch, see
+/// `EpochVerifiers`.
+///
+/// We write the epoch as four little endian bytes, followed by the
+/// ordinary `groth16::Parameters` serialization.
+pub fn write_crs_with_epoch<E, W>(crs: &groth16::Parameters<E>, epoch: u32, mut writer: W) -> io::Result<()>
+where E: JubjubEngineWithParams, W: Write,
+{
+    writer.write_all(&epoch.to_le_bytes()) ?;
+    crs.write(writer)
+}
+
+/// Read a `RingVRF` CRS written by `groth16::Parameters::write`, which
+/// we check like `groth16::Parameters::read` if `checked`, and then
+/// check was generated for `depth` with `crs_check_depth`.
+pub fn read_crs<E, R>(reader: R, depth: u32, checked: bool) -> SynthesisResult<groth16::Parameters<E>>
+where E: JubjubEngineWithParams, R: io::Read,
+{
+    let crs = groth16::Parameters::read(reader, checked) ?;
+    crs_check_depth(RingSRS { srs: &crs, depth }) ?;
+    Ok(crs)
+}
+
+/// Read a CRS written by `write_crs_with_epoch`, returning its epoch
+/// along with the CRS, which we check like `groth16::Parameters::read`
+/// if `checked`.
+pub fn read_crs_with_epoch<E, R>(mut reader: R, checked: bool) -> io::Result<(u32, groth16::Parameters<E>)>
+where E: JubjubEngineWithParams, R: io::Read,
+{
+    let mut epoch = [0u8; 4];
+    reader.read_exact(&mut epoch) ?;
+    let crs = groth16::Parameters::read(reader, checked) ?;
+    Ok((u32::from_le_bytes(epoch), crs))
+}
+
+/// Generates the Groth16 CRS for the `RingVRFAudit` circuit, which
+/// also encrypts the signer's ring index to an auditor.
+///
+/// We reject depth zero, since single member rings have no index to
+/// encrypt.
+#[cfg(feature = "audit_tag")]
+pub fn generate_crs_audit<E: JubjubEngineWithParams>(depth: u32)
+ -> SynthesisResult<groth16::Parameters<E>>
+where
+    E::G1: WnafGroup,
+    E::G2: WnafGroup,
+{
+    let circuit = crate::circuit::RingVRFAudit::<E> {
+        depth,
+        sk: None,
+        vrf_input: None,
+        extra: None,
+        copath: None,
+        auditor: None,
+        audit_randomness: None,
+    };
+    groth16::generate_random_parameters(circuit, &mut rand_hack())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use pairing::bls12_381::Bls12;
+
+    use super::*;
+    use crate::{testing, signing_context};
+
+    #[test]
+    fn test_generate_crs_resumable() {
+        let path = ::std::env::temp_dir().join(format!("ring-vrf-resumable-{}.pk", ::std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        let crs = generate_crs_resumable::<Bls12,_>(2, &path).unwrap();
+        assert!( path.exists() );
+        assert!( !path.with_extension("partial").exists() );
+
+        // Resuming loads the checkpoint instead of generating fresh parameters.
+        let resumed = generate_crs_resumable::<Bls12,_>(2, &path).unwrap();
+        let (mut b0, mut b1) = (Vec::new(), Vec::new());
+        crs.write(&mut b0).unwrap();
+        resumed.write(&mut b1).unwrap();
+        assert!( b0 == b1 );
+
+        // A checkpoint for another depth fails, and survives.
+        match generate_crs_resumable::<Bls12,_>(3, &path) {
+            Err(::bellman::SynthesisError::IoError(e)) => assert_eq!(e.kind(), io::ErrorKind::InvalidInput),
+            _ => panic!("Resumed a checkpoint of the wrong depth"),
+        }
+        assert!( path.exists() );
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_crs_self_test() {
+        let fx = testing::RingFixture::shared();
+        let crs = &*fx.params;
+        crs_self_test(RingSRS { srs: crs, depth: 2 }).unwrap();
+        assert!( crs_self_test(RingSRS { srs: crs, depth: 3 }).is_err() );
+
+        // Tampering with the verifying key still parses, but fails.
+        let mut tampered = crs.clone();
+        tampered.vk.ic.swap(0, 1);
+        match crs_self_test(RingSRS { srs: &tampered, depth: 2 }) {
+            Err(::bellman::SynthesisError::IoError(e)) => assert_eq!(e.kind(), io::ErrorKind::InvalidData),
+            _ => panic!("Tampered CRS passed its self test"),
+        }
+    }
+
+    #[test]
+    fn test_depth_mismatch() {
+        let mut rng = ::rand_core::OsRng;
+        let fx = testing::RingFixture::shared();
+        let crs = &*fx.params;
+        assert!(crs_check_depth(RingSRS { srs: crs, depth: 2 }).is_ok());
+        match crs_check_depth(RingSRS { srs: crs, depth: 3 }) {
+            Err(bellman::SynthesisError::IoError(e)) => assert_eq!(e.kind(), ::std::io::ErrorKind::InvalidInput),
+            _ => panic!("depth 2 CRS accepted at depth 3"),
+        }
+
+        let sk = SecretKey::<Bls12>::from_rng(&mut rng);
+        let input = VRFInput::<Bls12>::new_malleable(signing_context(b"depth").bytes(b"meow"));
+        let copath = RingSecretCopath::random(3, &mut rng);
+        match sk.ring_vrf_prove(input, no_extra(), copath, fx.srs(), &mut rng) {
+            Err(bellman::SynthesisError::IoError(e)) => assert!(e.to_string().contains("crs_check_depth")),
+            _ => panic!("ring VRF prover accepted a copath of the wrong depth"),
+        }
+
+        // We check the depth of a CRS once when reading it.
+        let mut bytes = Vec::new();
+        crs.write(&mut bytes).unwrap();
+        assert!(read_crs::<Bls12,_>(&bytes[..], 2, false).is_ok());
+        match read_crs::<Bls12,_>(&bytes[..], 8, false) {
+            Err(bellman::SynthesisError::IoError(e)) => assert_eq!(e.kind(), ::std::io::ErrorKind::InvalidInput),
+            _ => panic!("read a depth 2 CRS at depth 8"),
+        }
+    }
+}
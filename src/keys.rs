@@ -3,6 +3,8 @@
 //! ### Ring VRF keys
 
 use std::io;
+use core::cmp::Ordering;
+use core::hash::{Hash, Hasher};
 // use core::fmt::{Debug};
 
 // use subtle::{Choice,ConstantTimeEq};
@@ -15,27 +17,64 @@ use zcash_primitives::jubjub::{
 
 use zeroize::Zeroize;
 
-use crate::{JubjubEngineWithParams, ReadWrite, Scalar};
+use crate::{JubjubEngineWithParams, ReadWrite, Scalar, SignatureResult, signature_error, clear_cofactor};
 
 
 /// Public key consisting of a JubJub point
-#[derive(Debug,Clone)] // Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash
+#[derive(Debug,Clone)] // Copy, Default
 pub struct PublicKey<E: JubjubEngine>(pub(crate) Point<E,Unknown>);
 
 // serde_boilerplate!(PublicKey);
 
 impl<E: JubjubEngineWithParams> PartialEq for PublicKey<E> {
     fn eq(&self, other: &PublicKey<E>) -> bool {
-        let params = E::params();
-        self.0.mul_by_cofactor(params) == other.0.mul_by_cofactor(params) 
+        clear_cofactor(&self.0) == clear_cofactor(&other.0)
     }
 }
 impl<E: JubjubEngineWithParams> Eq for PublicKey<E> { }
 
+impl<E: JubjubEngineWithParams> Hash for PublicKey<E> {
+    /// We hash the canonical encoding, so keys equal up to torsion hash equally.
+    ///
+    /// We perform two scalar multiplications per hash, so key large
+    /// maps by `canonical_bytes` instead, like `RingBuilder` does.
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.canonical_bytes().hash(state)
+    }
+}
+
+impl<E: JubjubEngineWithParams> PartialOrd for PublicKey<E> {
+    fn partial_cmp(&self, other: &PublicKey<E>) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<E: JubjubEngineWithParams> Ord for PublicKey<E> {
+    /// We order keys lexicographically by their canonical encoding,
+    /// which agrees with `write` for keys in the prime order subgroup.
+    ///
+    /// We perform two scalar multiplications per comparison, so sort
+    /// large rings by `sort_by_cached_key` with `canonical_bytes`.
+    fn cmp(&self, other: &PublicKey<E>) -> Ordering {
+        self.canonical_bytes().cmp(&other.canonical_bytes())
+    }
+}
+
 impl<E: JubjubEngineWithParams> PublicKey<E> {
     fn from_secret_scalar(secret: &Scalar<E>) -> PublicKey<E> {
         PublicKey( crate::scalar_times_generator(secret).into() )
     }
+
+    /// Compressed encoding of our prime order component, which agrees
+    /// with `write` for keys in the prime order subgroup, and which
+    /// agrees for two keys if and only if they are equal.
+    pub fn canonical_bytes(&self) -> [u8; 32] {
+        let cofactor_inverse = <E::Fs as ::ff::Field>::invert(&E::Fs::from(E::COFACTOR)).unwrap();
+        let prime = clear_cofactor(&self.0).mul(cofactor_inverse, E::params());
+        let mut bytes = [0u8; 32];
+        prime.write(&mut bytes[..]).expect("Jubjub points encode as 32 bytes.");
+        bytes
+    }
 }
 
 impl<E: JubjubEngineWithParams> ReadWrite for PublicKey<E>  {
@@ -62,8 +101,8 @@ impl<E: JubjubEngineWithParams> PublicKeyUnblinding<E> {
 
     pub fn verify(&self, blinded: PublicKey<E>, unblinded: PublicKey<E>) -> bool {
         let params = E::params();
-        unblinded.0.add(& crate::scalar_times_generator(&self.0).into(), params).mul_by_cofactor(params)
-        == blinded.0.mul_by_cofactor(params)
+        clear_cofactor(&unblinded.0.add(& crate::scalar_times_generator(&self.0).into(), params))
+        == clear_cofactor(&blinded.0)
     }
 }
 
@@ -78,6 +117,28 @@ impl<E: JubjubEngine> ReadWrite for PublicKeyUnblinding<E>  {
 }
 
 
+/// Version byte prefixing our `SecretKey::to_bytes` serialization.
+pub const SECRET_KEY_VERSION: u8 = 0;
+
+/// Length of our `SecretKey::to_bytes` serialization, consisting of the
+/// version byte, the 32 byte scalar, and the 32 byte nonce seed.
+pub const SECRET_KEY_LENGTH: usize = 65;
+
+/// Secret key scalars below this bound are weak, since an adversary
+/// recovers them from the public key by brute force.
+///
+/// We reject zero in particular, whose VRF outputs are all the identity.
+const WEAK_KEY_BOUND_BYTES: usize = 2;
+
+/// Returns true if `key` lies below `2^(8 WEAK_KEY_BOUND_BYTES)`,
+/// without branching on the secret key.
+fn is_weak_key<E: JubjubEngine>(key: &Scalar<E>) -> bool {
+    use subtle::ConstantTimeEq;
+    let repr = <Scalar<E> as ::ff::PrimeField>::to_repr(key);
+    let high = &repr.as_ref()[WEAK_KEY_BOUND_BYTES..];
+    high.ct_eq(&[0u8; 32][..high.len()]).into()
+}
+
 /// Seceret key consisting of a JubJub scalar and a secret nonce seed.
 #[derive(Clone)] // Debug
 pub struct SecretKey<E: JubjubEngine> {
@@ -105,8 +166,10 @@ pub struct SecretKey<E: JubjubEngine> {
 impl<E: JubjubEngine> Zeroize for SecretKey<E> {
     fn zeroize(&mut self) {
         self.nonce_seed.zeroize();
-        // self.key.zeroize();
-        // self.key = <E:Fs as PrimeField>::from_repr(<E:Fs as PrimeField>::Repr::default());
+        // Jubjub scalars implement no `Zeroize`, so we overwrite ours
+        // with a volatile write, like `zeroize` does internally.
+        unsafe {  ::core::ptr::write_volatile(&mut self.key, <Scalar<E> as ::ff::Field>::zero());  }
+        ::core::sync::atomic::compiler_fence(::core::sync::atomic::Ordering::SeqCst);
     }
 }
 impl<E: JubjubEngine> Drop for SecretKey<E> {
@@ -144,7 +207,11 @@ impl<E: JubjubEngineWithParams> SecretKey<E> {
     {
         let mut nonce_seed: [u8; 32] = [0u8; 32];
         rng.fill_bytes(&mut nonce_seed);
-        let key = <E::Fs as ::ff::Field>::random(&mut rng);
+        // We resample weak keys, which occur with negligible probability.
+        let mut key = <E::Fs as ::ff::Field>::random(&mut rng);
+        while is_weak_key::<E>(&key) {
+            key = <E::Fs as ::ff::Field>::random(&mut rng);
+        }
         let public = PublicKey::from_secret_scalar(&key);
         SecretKey { key, nonce_seed, public, }
     }
@@ -166,12 +233,41 @@ impl<E: JubjubEngineWithParams> SecretKey<E> {
     pub fn to_public(&self) -> PublicKey<E> {
         self.public.clone()
     }
+
+    /// Serialize as one `SECRET_KEY_VERSION` byte, followed by the
+    /// canonical little endian scalar, and then the nonce seed.
+    ///
+    /// Handle with care:  These bytes are the secret key, so you should
+    /// zeroize them once they reach their more permanent storage.
+    pub fn to_bytes(&self) -> [u8; SECRET_KEY_LENGTH] {
+        let mut bytes = [0u8; SECRET_KEY_LENGTH];
+        bytes[0] = SECRET_KEY_VERSION;
+        crate::write_scalar::<E, &mut [u8]>(&self.key, &mut bytes[1..33])
+            .expect("Jubjub scalars serialize to 32 bytes");
+        bytes[33..].copy_from_slice(&self.nonce_seed);
+        bytes
+    }
+
+    /// Deserialize the `SecretKey::to_bytes` format, rejecting unknown
+    /// versions, non-canonical scalars, and weak scalars like zero.
+    pub fn from_bytes(bytes: &[u8]) -> SignatureResult<SecretKey<E>> {
+        if bytes.len() != SECRET_KEY_LENGTH {
+            return Err( signature_error("SecretKey has wrong length") );
+        }
+        if bytes[0] != SECRET_KEY_VERSION {
+            return Err( signature_error("SecretKey has unknown version") );
+        }
+        SecretKey::read(&bytes[1..])
+    }
 }
 // TODO:  Convert to/from zcash_primitives::redjubjub::PrivateKey
 
 impl<E: JubjubEngineWithParams> ReadWrite for SecretKey<E>  {
     fn read<R: io::Read>(mut reader: R) -> io::Result<Self> {
         let key = crate::read_scalar::<E, &mut R>(&mut reader) ?;
+        if is_weak_key::<E>(&key) {
+            return Err( signature_error("SecretKey scalar is weak") );
+        }
         let mut nonce_seed = [0u8; 32];
         reader.read_exact(&mut nonce_seed) ?;
         let public = PublicKey::from_secret_scalar(&key);
@@ -185,3 +281,84 @@ impl<E: JubjubEngineWithParams> ReadWrite for SecretKey<E>  {
     }
 }
 
+
+
+#[cfg(test)]
+mod tests {
+    use pairing::bls12_381::Bls12;
+
+    use super::*;
+
+    #[test]
+    fn public_key_hash_and_order() {
+        use std::collections::HashSet;
+
+        let mut rng = ::rand_core::OsRng;
+        let mut pks = (0..8).map( |_| SecretKey::<Bls12>::from_rng(&mut rng).to_public() ).collect::<Vec<_>>();
+        let mut set = pks.iter().cloned().collect::<HashSet<_>>();
+        assert_eq!(set.len(), 8);
+
+        let mut bytes = Vec::new();
+        pks[0].write(&mut bytes).unwrap();
+        let copy = PublicKey::<Bls12>::read(&bytes[..]).unwrap();
+        assert_eq!(&copy.canonical_bytes()[..], &bytes[..]);
+        assert!(! set.insert(copy));
+
+        let encoding = |pk: &PublicKey<Bls12>| {  let mut v = Vec::new();  pk.write(&mut v).unwrap();  v  };
+        pks.sort();
+        for w in pks.windows(2) {
+            assert!(w[0] < w[1]);
+            assert!(encoding(&w[0]) < encoding(&w[1]));
+        }
+    }
+
+    #[test]
+    fn secret_key_bytes() {
+        let sk = SecretKey::<Bls12>::from_rng(::rand_core::OsRng);
+        let bytes = sk.to_bytes();
+        assert_eq!(bytes[0], SECRET_KEY_VERSION);
+        let sk2 = SecretKey::<Bls12>::from_bytes(&bytes).unwrap();
+        assert_eq!(&sk2.to_bytes()[..], &bytes[..]);
+        assert!(sk2.to_public() == sk.to_public());
+
+        let mut bad = bytes;
+        bad[0] = SECRET_KEY_VERSION + 1;
+        assert!(SecretKey::<Bls12>::from_bytes(&bad).is_err());
+
+        let mut bad = bytes;
+        for b in bad[1..33].iter_mut() {  *b = 0xff;  }
+        assert!(SecretKey::<Bls12>::from_bytes(&bad).is_err());
+
+        assert!(SecretKey::<Bls12>::from_bytes(&bytes[..64]).is_err());
+    }
+
+    #[test]
+    fn weak_secret_keys_rejected() {
+        let sk = SecretKey::<Bls12>::from_rng(::rand_core::OsRng);
+        assert!(! is_weak_key::<Bls12>(&sk.key));
+        let bytes = sk.to_bytes();
+
+        let mut zero = bytes;
+        for b in zero[1..33].iter_mut() {  *b = 0;  }
+        assert!(SecretKey::<Bls12>::from_bytes(&zero).is_err());
+        assert!(SecretKey::<Bls12>::read(&zero[1..]).is_err());
+
+        let mut small = zero;
+        small[1] = 1;
+        small[2] = 0xff;
+        assert!(SecretKey::<Bls12>::from_bytes(&small).is_err());
+
+        let mut boundary = zero;
+        boundary[1 + WEAK_KEY_BOUND_BYTES] = 1;
+        assert!(SecretKey::<Bls12>::from_bytes(&boundary).is_ok());
+        assert!(SecretKey::<Bls12>::from_seed([0u8; 32]).to_bytes()[1..33] != zero[1..33]);
+    }
+
+    #[test]
+    fn secret_key_zeroize() {
+        let mut sk = SecretKey::<Bls12>::from_rng(::rand_core::OsRng);
+        sk.zeroize();
+        assert!(sk.key == <Scalar<Bls12> as ::ff::Field>::zero());
+        assert_eq!(sk.nonce_seed, [0u8; 32]);
+    }
+}
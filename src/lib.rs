@@ -16,9 +16,6 @@ use zcash_primitives::jubjub::JubjubEngine;
 #[macro_use]
 extern crate lazy_static;
 
-#[macro_use]
-extern crate arrayref;
-
 #[macro_use]
 extern crate bench_utils;
 
@@ -31,22 +28,49 @@ mod circuit;
 mod generator;
 mod prover;
 mod verifier;
+mod r1cs;
+mod facade;
 pub mod vrf;
 pub mod schnorr;
+pub mod oblivious;
+pub mod policy;
 pub mod bls12_381;
+#[cfg(feature = "audit_tag")]
+pub mod audit;
+#[cfg(feature = "h2c")]
+pub mod h2c;
+#[cfg(feature = "ecvrf")]
+pub mod ecvrf;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+#[cfg(any(fuzzing, test))]
+pub mod fuzz;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
 
 
 use crate::misc::{
     SignatureResult, signature_error, ReadWrite,
     Scalar, read_scalar, write_scalar,
-    scalar_times_generator, scalar_times_blinding_generator
+    scalar_times_generator, scalar_times_blinding_generator, clear_cofactor, mul_by_small,
 };
-pub use crate::keys::{SecretKey, PublicKey, PublicKeyUnblinding};
+pub use crate::keys::{SecretKey, PublicKey, PublicKeyUnblinding, SECRET_KEY_VERSION, SECRET_KEY_LENGTH};
 pub use crate::context::{signing_context, SigningTranscript};
 
-pub use crate::merkle::{RingSecretCopath, RingRoot, auth_hash};
-pub use crate::generator::generate_crs;
-pub use vrf::{VRFInOut, VRFInput, VRFPreOut, vrfs_merge};
+pub use crate::merkle::{RingSecretCopath, RingRoot, RingBuilder, SparseRing, IncrementalRing, RingSnapshot, write_snapshot, read_snapshot, read_snapshot_root_only, auth_hash, ring_capacity};
+pub use crate::generator::{generate_crs, generate_crs_with_rng, generate_crs_resumable, generate_crs_transition, generate_crs_domain, generate_crs_blinded, crs_self_test, crs_check_depth, read_crs, write_crs_with_epoch, read_crs_with_epoch};
+pub use crate::circuit::{PublicInputField, public_input_schema};
+pub use crate::r1cs::export_r1cs;
+pub use crate::facade::{RingVrfSigner, RingVrfVerifier, SignedVrf, SignedVrfBundle};
+#[cfg(feature = "jsonl")]
+pub use crate::facade::{verify_file_jsonl, write_signed_record};
+pub use crate::prover::{RingVRFProof, RingVRFProofBytes, RingVRFProofHex};
+pub use crate::verifier::{verifying_key_hash, ring_vrf_debug_public_inputs, ring_vrf_verify_stream, verify_proofs_batch, ring_vrf_verify_members, ring_vrf_verify_bytes, ring_vrf_verify_expected, LotteryResult, VerifierCache, EpochVerifiers, CircuitVersion, VerifyReport, VerifyStage, SubgroupCache, SUBGROUP_CACHE_CAPACITY};
+#[cfg(feature = "audit_tag")]
+pub use crate::generator::generate_crs_audit;
+pub use vrf::{VRFInOut, VRFInput, VRFInputSpec, VRFInputBuilder, VRFPreOut, VRFOutputCommitment, ByteOrder, PreHashedExtra, Role, vrfs_merge, vrfs_merge_with_context, try_vrfs_merge, vrfs_merge_weights, threshold_from_probability};
+#[cfg(feature = "versioned_output")]
+pub use vrf::VRF_OUTPUT_VERSION;
 
 
 /// Ugly hack until we can unify error handling
@@ -56,19 +80,162 @@ fn rand_hack() -> impl RngCore+CryptoRng {
     ::rand_core::OsRng
 }
 
+/// Object safe union of `RngCore` and `CryptoRng`, so provers may
+/// accept type erased randomness sources, like HSM or TPM drivers.
+pub trait CryptoRngCore: RngCore+CryptoRng {}
+
+impl<R: RngCore+CryptoRng+?Sized> CryptoRngCore for R {}
+
 /// Fix ZCash's curve paramater handling
 pub trait JubjubEngineWithParams : JubjubEngine {
+    /// JubJub parameters for this engine.
+    ///
+    /// We call this on every hot path, like hashing to the curve and
+    /// each scalar multiplication, so implementations must construct
+    /// their parameters at most once, like with `lazy_static!`, and
+    /// then return the same cached reference.  Constructing JubJub
+    /// parameters costs milliseconds, far more than a VRF evaluation.
     fn params() -> &'static <Self as JubjubEngine>::Params;
+
+    /// Cofactor of our Edwards curve, by which `clear_cofactor` multiplies
+    /// points into the prime order subgroup, so 8 for JubJub.
+    ///
+    /// We multiply by 8 using zcash's `mul_by_cofactor`, but engines with
+    /// other cofactors pay for a subgroup check after multiplication.
+    const COFACTOR: u64 = 8;
+
+    /// Panic unless our parameters' generators agree with the lookup
+    /// tables the circuit hardcodes, see `params_match_circuit`.
+    fn assert_params_match_circuit() {
+        assert!(
+            params_match_circuit::<Self>(Self::params()),
+            "JubJub generators disagree with the circuit's fixed base tables"
+        );
+    }
+
+    /// Check our `COFACTOR`, subgroup order, and generators agree, see
+    /// `engine_self_check`.
+    ///
+    /// We run this once per engine in debug builds of `ring_vrf_prove`.
+    fn self_check() -> SignatureResult<()> {
+        engine_self_check::<Self>(Self::COFACTOR)
+    }
+}
+
+/// Number of curve points on which `engine_self_check` tests cofactor clearing.
+const SELF_CHECK_SAMPLES: usize = 16;
+
+/// Check that `cofactor` and the parameters of engine `E` agree, which
+/// custom engines could otherwise break silently.
+///
+/// We require that both generators have the subgroup order, meaning the
+/// characteristic of `E::Fs`, and match the circuit's tables, and that
+/// `cofactor` times sampled curve points lands in that subgroup, so
+/// `cofactor` times the subgroup order annihilates the whole curve.
+/// We sample the same points from a fixed seed on every run, so we
+/// reject exactly those `cofactor`s that leave torsion in some sample.
+/// For JubJub, whose torsion has order 8, these samples include a point
+/// of full torsion order, so we reject every `cofactor` not divisible
+/// by 8, as `test_engine_self_check` pins.
+pub fn engine_self_check<E: JubjubEngineWithParams>(cofactor: u64) -> SignatureResult<()> {
+    use ff::Field;
+    use rand_core::SeedableRng;
+    use zcash_primitives::jubjub::{FixedGenerators, JubjubParams, Unknown, edwards::Point};
+
+    let params = E::params();
+    if E::Fs::from(cofactor) == E::Fs::zero() {
+        return Err(signature_error("Engine cofactor must be invertible in the scalar field"));
+    }
+    for g in [FixedGenerators::SpendingKeyGenerator, FixedGenerators::NullifierPosition].iter() {
+        let generator: Point<E, Unknown> = params.generator(*g).clone().into();
+        if generator == Point::zero() || generator.as_prime_order(params).is_none() {
+            return Err(signature_error("Engine generator lacks the subgroup order"));
+        }
+    }
+    if ! params_match_circuit::<E>(params) {
+        return Err(signature_error("Engine generators disagree with the circuit's fixed base tables"));
+    }
+    let mut rng = ::rand_chacha::ChaChaRng::from_seed([0u8; 32]);
+    for _ in 0..SELF_CHECK_SAMPLES {
+        let p = Point::<E, Unknown>::rand(&mut rng, params);
+        if mul_by_small(&p, cofactor).as_prime_order(params).is_none() {
+            return Err(signature_error("Engine cofactor fails to clear the cofactor"));
+        }
+    }
+    Ok(())
+}
+
+/// Run `E::self_check` until it first succeeds for `E`, and then never again.
+#[cfg(debug_assertions)]
+pub(crate) fn self_check_once<E: JubjubEngineWithParams>() -> SignatureResult<()> {
+    lazy_static! {
+        /// Engines which passed `engine_self_check`.
+        static ref SELF_CHECKED: std::sync::Mutex<std::collections::HashSet<std::any::TypeId>> =
+            std::sync::Mutex::new(std::collections::HashSet::new());
+    }
+    let id = std::any::TypeId::of::<E>();
+    if SELF_CHECKED.lock().unwrap_or_else( |e| e.into_inner() ).contains(&id) {
+        return Ok(());
+    }
+    E::self_check() ?;
+    SELF_CHECKED.lock().unwrap_or_else( |e| e.into_inner() ).insert(id);
+    Ok(())
+}
+
+/// Check that the JubJub generators used outside the circuit match the
+/// fixed base lookup tables that the circuit's gadgets use.
+///
+/// We evaluate public keys and blinded public keys outside the circuit
+/// using `JubjubParams::generator`, but the circuit multiplies using
+/// `JubjubParams::circuit_generators`, so any custom parameters for which
+/// these diverge yield proofs that never verify against honest outputs.
+pub fn params_match_circuit<E: JubjubEngine>(params: &E::Params) -> bool {
+    use zcash_primitives::jubjub::{FixedGenerators, JubjubParams};
+    [FixedGenerators::SpendingKeyGenerator, FixedGenerators::NullifierPosition].iter().all( |g|
+        generator_matches_windows::<E>(params.generator(*g), params.circuit_generators(*g), params)
+    )
+}
+
+/// We check the first window of the circuit table contains `[0, G, 2 G, .., 7 G]`.
+fn generator_matches_windows<E: JubjubEngine>(
+    generator: &zcash_primitives::jubjub::edwards::Point<E, zcash_primitives::jubjub::PrimeOrder>,
+    windows: &[Vec<(E::Fr, E::Fr)>],
+    params: &E::Params,
+) -> bool {
+    use zcash_primitives::jubjub::edwards::Point;
+    let window = match windows.first() {  Some(w) => w,  None => return false,  };
+    let mut p = Point::zero();
+    window.len() == 8 && window.iter().all( |xy| {
+        let b = *xy == p.to_xy();
+        p = p.add(generator, params);
+        b
+    } )
 }
 
 /// RingVRF SRS consisting of the Merkle tree depth, our only runtime 
 /// configuration parameters for the system, attached to an appropirate
-/// `&'a Parameters<E>` or some other `P: groth16::ParameterSource<E>`.
+/// `&'a Parameters<E>` or some other `P: ParameterSource<E>`.
+///
+/// We never pass JubJub parameters explicitly, but obtain them from
+/// `JubjubEngineWithParams::params`, while provers require
+/// `P: ParameterSource<E>` for the same engine `E` as the
+/// secret key, so mixing engines already fails to compile.
 #[derive(Clone,Copy)]
 pub struct RingSRS<SRS> {
     pub srs: SRS,
     pub depth: u32,
 }
+
+impl<SRS: core::ops::Deref> RingSRS<SRS> {
+    /// Borrow an owned SRS, like `Arc<groth16::Parameters<E>>`, for proving.
+    ///
+    /// We never clone the parameters, so threads proving in `'static`
+    /// closures should each hold a clone of some `RingSRS<Arc<_>>`
+    /// and call this inside the closure.
+    pub fn as_deref(&self) -> RingSRS<&SRS::Target> {
+        RingSRS { srs: &*self.srs, depth: self.depth }
+    }
+}
 /*
 We could make it clone if SRS is Copy, but we'd rather make up for zcash's limited impls here.
 impl<SRS: Copy+Clone> Copy for RingSRS<SRS> { }
@@ -92,6 +259,113 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn test_params_cached() {
+        let first = Bls12::params() as *const _;
+        let sk = SecretKey::<Bls12>::from_rng(::rand_core::OsRng);
+        for i in 0..8u8 {
+            VRFInput::<Bls12>::new_malleable(signing_context(b"params").bytes(&[i])).to_inout(&sk);
+            assert!(::core::ptr::eq(first, Bls12::params()));
+        }
+    }
+
+    #[test]
+    fn test_engine_self_check() {
+        Bls12::self_check().unwrap();
+        // A cofactor of 16 still clears JubJub's cofactor of 8.
+        engine_self_check::<Bls12>(16).unwrap();
+        // We cannot implement another pairing engine here, but an engine
+        // claiming the wrong cofactor fails the same way.
+        assert!(engine_self_check::<Bls12>(4).is_err());
+        assert!(engine_self_check::<Bls12>(1).is_err());
+        assert!(engine_self_check::<Bls12>(0).is_err());
+    }
+
+    #[test]
+    fn test_params_match_circuit() {
+        use zcash_primitives::jubjub::{FixedGenerators, JubjubParams};
+
+        Bls12::assert_params_match_circuit();
+        let params = Bls12::params();
+        let windows = params.circuit_generators(FixedGenerators::SpendingKeyGenerator);
+        let wrong = params.generator(FixedGenerators::NullifierPosition);
+        assert!( !generator_matches_windows::<Bls12>(wrong, windows, params) );
+        assert!( !generator_matches_windows::<Bls12>(wrong, &[], params) );
+    }
+
+    /// Write a digest of a seeded CRS and proof to the file named by
+    /// `RING_VRF_FINGERPRINT`, which `test_thread_count_independent`
+    /// compares across thread counts.
+    #[test]
+    #[ignore]
+    fn thread_count_fingerprint() {
+        use rand_core::SeedableRng;
+
+        let depth = 2;
+        let sk = SecretKey::<Bls12>::from_seed([3u8; 32]);
+        let copath = RingSecretCopath::random(depth, &mut ::rand_chacha::ChaChaRng::from_seed([4u8; 32]));
+        let input = VRFInput::<Bls12>::new_malleable(signing_context(b"threads").bytes(b"meow"));
+        let params = generator::generate_crs_with_rng::<Bls12,_>(depth, &mut ::rand_chacha::ChaChaRng::from_seed([5u8; 32])).unwrap();
+        let proof = sk.ring_vrf_prove(input, vrf::no_extra(), copath, RingSRS { srs: &params, depth }, &mut ::rand_chacha::ChaChaRng::from_seed([6u8; 32])).unwrap();
+        let mut crs_bytes = Vec::new();
+        params.write(&mut crs_bytes).unwrap();
+
+        let mut t = ::merlin::Transcript::new(b"thread count fingerprint");
+        t.append_message(b"crs", &crs_bytes);
+        t.append_message(b"proof", &proof.canonical_bytes());
+        let mut digest = [0u8; 32];
+        t.challenge_bytes(b"", &mut digest);
+        if let Some(path) = ::std::env::var_os("RING_VRF_FINGERPRINT") {
+            ::std::fs::write(path, &digest).unwrap();
+        }
+    }
+
+    /// Run with `cargo test -- --ignored test_thread_count_independent`,
+    /// since this generates a CRS and proves four times.
+    #[test]
+    #[ignore]
+    fn test_thread_count_independent() {
+        // Group arithmetic is exact, so neither CRS generation nor proving
+        // should depend upon how many threads split the multiexps.  Bellman
+        // sizes its worker pool from BELLMAN_NUM_CPUS once per process,
+        // so we rerun this test binary for each thread count.
+        let run = |threads: usize| {
+            let path = ::std::env::temp_dir().join(format!("ring-vrf-fingerprint-{}-{}", ::std::process::id(), threads));
+            let status = ::std::process::Command::new(::std::env::current_exe().unwrap())
+                .args(&["tests::thread_count_fingerprint", "--exact", "--ignored", "--quiet"])
+                .env("BELLMAN_NUM_CPUS", threads.to_string())
+                .env("RING_VRF_FINGERPRINT", &path)
+                .stdout(::std::process::Stdio::null())
+                .status().unwrap();
+            assert!(status.success(), "{} threads failed", threads);
+            let digest = ::std::fs::read(&path).expect("fingerprint missing");
+            ::std::fs::remove_file(&path).unwrap();
+            digest
+        };
+        let expected = run(1);
+        for threads in [2, 4, 8].iter() {
+            assert_eq!(run(*threads), expected, "{} threads diverge from 1 thread", threads);
+        }
+    }
+
+    #[test]
+    fn test_prove_on_thread() {
+        use std::sync::Arc;
+
+        // We copy the shared CRS, since other tests hold references too.
+        let fx = testing::RingFixture::shared();
+        let srs = RingSRS { srs: Arc::new((*fx.params).clone()), depth: fx.depth };
+
+        let (sk, copath, auth_root) = fx.signer();
+        let job = srs.clone();
+        let (inout, proof) = ::std::thread::spawn(move || {
+            let input = VRFInput::<Bls12>::new_malleable(signing_context(b"thread").bytes(b"meow"));
+            sk.ring_vrf_sign_first(input, vrf::no_extra(), copath, job.as_deref()).unwrap()
+        }).join().unwrap();
+        assert!(auth_root.ring_vrf_verify(inout, vrf::no_extra(), proof, &fx.pvk).unwrap());
+        assert_eq!(Arc::strong_count(&srs.srs), 1);
+    }
+
     #[test]
     fn test_completeness() {
         let depth = 10;
@@ -10,7 +10,10 @@
 
 
 use std::io;
+use std::collections::{BTreeMap, HashMap};
 
+use core::fmt;
+use core::hash::{Hash, Hasher};
 use core::ops::{Deref, DerefMut};
 use core::iter::IntoIterator;
 use core::borrow::Borrow;
@@ -19,7 +22,7 @@ use ff::{PrimeField, BitIterator, Field};
 use pairing::bls12_381::Fr;
 use zcash_primitives::jubjub::JubjubEngine;
 use zcash_primitives::pedersen_hash;
-use crate::{JubjubEngineWithParams, PublicKey};
+use crate::{JubjubEngineWithParams, PublicKey, ReadWrite, SignatureResult, signature_error};
 
 
 /// Direction of the binary Merkle path, either going left or right.
@@ -109,9 +112,9 @@ where E: JubjubEngineWithParams,
 
     for depth_to_bottom in 0..depth {
         let (current_selection, sibling) = if index % 2 == 0 {
-            (MerkleSelection::Left, list.get(index).cloned())
+            (MerkleSelection::Left, list.get(index+1).cloned())
         } else {
-            (MerkleSelection::Right, list.get(index).cloned())
+            (MerkleSelection::Right, list.get(index-1).cloned())
         };
         f(CopathPoint { current_selection, sibling, });
 
@@ -130,6 +133,22 @@ where E: JubjubEngineWithParams,
     list[0].clone()
 }
 
+/// Check `len` members fit a ring of `depth`, which `merkleize` requires
+/// to hold at least two members.
+fn check_ring_len(len: usize, depth: usize) -> SignatureResult<()> {
+    if len < 2 {
+        return Err(signature_error("Rings require at least two members"));
+    }
+    let fits = depth >= 64 || (len as u64) <= 1u64 << depth;
+    if ! fits {
+        return Err(signature_error("Ring members do not fit a ring of this depth"));
+    }
+    Ok(())
+}
+
+/// Largest copath depth we preallocate for when deserializing.
+const MAX_PREALLOCATED_DEPTH: usize = 64;
+
 /// The authentication path of the merkle tree.
 #[derive(Clone, Debug)]
 pub struct RingSecretCopath<E: JubjubEngine>(pub(crate) Vec<CopathPoint<E>>);
@@ -148,6 +167,15 @@ impl<E: JubjubEngineWithParams> RingSecretCopath<E> {
         self.0.len().try_into().unwrap()
     }
 
+    /// Index of our leaf within the ring, as determined by the
+    /// selections along the path, starting from the leaves.
+    pub fn index(&self) -> usize {
+        self.0.iter().enumerate().fold(0, |index, (level, p)| match p.current_selection {
+            MerkleSelection::Left => index,
+            MerkleSelection::Right => index | (1 << level),
+        })
+    }
+
     /// Create a path from a given plain list, of target specified as `list_index`.
     /// Panic if `list_index` is out of bound.
     pub fn from_publickeys<B,I>(iter: I, index: usize, depth: usize) -> (RingSecretCopath<E>,RingRoot<E>) 
@@ -161,11 +189,45 @@ impl<E: JubjubEngineWithParams> RingSecretCopath<E> {
         (RingSecretCopath(copath), RingRoot(root))
     }
 
+    /// Create a path like `from_publickeys`, but return an error
+    /// instead of panicking for fewer than two members, and for
+    /// members or `index` exceeding the ring.
+    pub fn try_from_publickeys<B,I>(iter: I, index: usize, depth: usize) -> SignatureResult<(RingSecretCopath<E>,RingRoot<E>)>
+    where B: Borrow<PublicKey<E>>, I: IntoIterator<Item=B>
+    {
+        let list = iter.into_iter().collect::<Vec<_>>();
+        check_ring_len(list.len(), depth) ?;
+        if index >= list.len() {
+            return Err(signature_error("Ring index exceeds the number of members"));
+        }
+        Ok(RingSecretCopath::from_publickeys(list, index, depth))
+    }
+
+    /// Create a path for the leaf at `index` by querying `oracle` for
+    /// the sibling at each level, starting from the leaves.
+    ///
+    /// We envision `oracle` fetching siblings from some external storage
+    /// of a large ring, so `None` denotes an empty sibling subtree.
+    pub fn from_oracle<F>(depth: u32, index: usize, mut oracle: F) -> RingSecretCopath<E>
+    where F: FnMut(u32) -> Option<E::Fr>
+    {
+        RingSecretCopath( (0..depth).map( |level| {
+            let current_selection = if (index >> level) % 2 == 0 {
+                MerkleSelection::Left
+            } else {
+                MerkleSelection::Right
+            };
+            CopathPoint { current_selection, sibling: oracle(level) }
+        } ).collect() )
+    }
+
     pub fn read<R: io::Read>(mut reader: R) -> io::Result<Self> {
         let mut len = [0u8; 4];
         reader.read_exact(&mut len) ?;
         let len = u32::from_le_bytes(len) as usize;
-        let mut copath = Vec::with_capacity(len);
+        // We never trust `len` for allocation because it comes off the wire,
+        // so an attacker supplied length cannot abort us before `read_exact` fails.
+        let mut copath = Vec::with_capacity(len.min(MAX_PREALLOCATED_DEPTH));
         for _ in 0..len {
             copath.push( CopathPoint::read(&mut reader) ? );
         }
@@ -235,6 +297,23 @@ impl<E: JubjubEngine> DerefMut for RingRoot<E> {
     fn deref_mut(&mut self) -> &mut E::Fr { &mut self.0 }
 }
 
+impl<E: JubjubEngine> Clone for RingRoot<E> {
+    fn clone(&self) -> Self { RingRoot(self.0) }
+}
+
+impl<E: JubjubEngine> PartialEq for RingRoot<E> {
+    fn eq(&self, other: &Self) -> bool { self.0 == other.0 }
+}
+
+impl<E: JubjubEngine> Eq for RingRoot<E> { }
+
+impl<E: JubjubEngine> Hash for RingRoot<E> {
+    /// We hash the canonical serialization, so equal roots hash equally.
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.to_repr().as_ref().hash(state)
+    }
+}
+
 impl<E: JubjubEngineWithParams> RingRoot<E> {
     /// Get the merkle root from a list of public keys. Panic if length of the list is zero.
     ///
@@ -247,6 +326,17 @@ impl<E: JubjubEngineWithParams> RingRoot<E> {
         RingRoot(merkleize( depth, list.as_mut_slice(), 0 , |_: CopathPoint<E>| () ))
     }
 
+    /// Get the merkle root like `from_publickeys`, but return an error
+    /// instead of panicking for fewer than two members, and for more
+    /// members than the ring holds.
+    pub fn try_from_publickeys<B,I>(iter: I, depth: usize) -> SignatureResult<Self>
+    where B: Borrow<PublicKey<E>>, I: IntoIterator<Item=B>
+    {
+        let list = iter.into_iter().collect::<Vec<_>>();
+        check_ring_len(list.len(), depth) ?;
+        Ok(RingRoot::from_publickeys(list, depth))
+    }
+
     pub fn read<R: io::Read>(mut reader: R) -> io::Result<Self> {
         let mut repr = <E::Fr as PrimeField>::Repr::default();
         reader.read_exact(repr.as_mut()) ?;
@@ -257,6 +347,421 @@ impl<E: JubjubEngineWithParams> RingRoot<E> {
     pub fn write<W: io::Write>(&self, mut writer: W) -> io::Result<()> {
         writer.write_all(self.0.to_repr().as_ref())
     }
+
+    /// Parse the hex produced by our `Display` implementation.
+    pub fn from_hex(s: &str) -> io::Result<Self> {
+        crate::misc::read_hex(s, |r| RingRoot::read(r))
+    }
+}
+
+impl<E: JubjubEngine> fmt::Display for RingRoot<E> {
+    /// Lowercase hex of the canonical serialization.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        crate::misc::fmt_hex(self.0.to_repr().as_ref(), f)
+    }
+}
+
+/// Number of members a ring of the given depth supports, or `None`
+/// if this exceeds `u64::MAX`, which happens past depth 63.
+pub fn ring_capacity(depth: u32) -> Option<u64> {
+    2u64.checked_pow(depth)
+}
+
+/// Collects the public keys of a ring with a fixed depth, so that
+/// callers never exceed the capacity of that depth.
+pub struct RingBuilder<E: JubjubEngine> {
+    depth: u32,
+    members: Vec<PublicKey<E>>,
+    /// Index of each member by `PublicKey::canonical_bytes`, or
+    /// `None` if we allow duplicates.
+    indices: Option<HashMap<[u8; 32], usize>>,
+}
+
+impl<E: JubjubEngineWithParams> RingBuilder<E> {
+    /// Start an empty ring of the given depth, which rejects duplicate members.
+    pub fn new(depth: u32) -> RingBuilder<E> {
+        RingBuilder { depth, members: Vec::new(), indices: Some(HashMap::new()) }
+    }
+
+    /// Accept duplicate members, like when intentionally padding a ring.
+    ///
+    /// We caution that duplicates overstate the anonymity set size.
+    pub fn allow_duplicates(self) -> RingBuilder<E> {
+        RingBuilder { indices: None, ..self }
+    }
+
+    /// Depth of the ring we build.
+    pub fn depth(&self) -> u32 { self.depth }
+
+    /// Number of members added so far.
+    pub fn len(&self) -> usize { self.members.len() }
+
+    /// Returns true if we have no members yet.
+    pub fn is_empty(&self) -> bool { self.members.is_empty() }
+
+    /// Number of free slots remaining, saturating at `u64::MAX` for
+    /// depths beyond `ring_capacity`.
+    pub fn remaining(&self) -> u64 {
+        ring_capacity(self.depth).unwrap_or(u64::max_value())
+            .saturating_sub(self.members.len() as u64)
+    }
+
+    /// Add a member, returning their index, or an error if full.
+    ///
+    /// We return an `io::ErrorKind::AlreadyExists` error naming the
+    /// existing index if `publickey` is already a member, unless
+    /// created with `allow_duplicates`.
+    pub fn push(&mut self, publickey: PublicKey<E>) -> SignatureResult<usize> {
+        if self.remaining() == 0 {
+            return Err(signature_error("Ring is full at this depth"));
+        }
+        let index = self.members.len();
+        if let Some(indices) = self.indices.as_mut() {
+            let canonical = publickey.canonical_bytes();
+            if let Some(existing) = indices.get(&canonical) {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    format!("Duplicate ring member, already at index {}", existing)
+                ));
+            }
+            indices.insert(canonical, index);
+        }
+        self.members.push(publickey);
+        Ok(index)
+    }
+
+    /// Members added so far, by index.
+    pub fn members(&self) -> &[PublicKey<E>] { &self.members }
+
+    /// Compute the ring root.  Panic with fewer than two members,
+    /// like `RingRoot::from_publickeys`.
+    pub fn root(&self) -> RingRoot<E> {
+        RingRoot::from_publickeys(&self.members, self.depth as usize)
+    }
+
+    /// Compute the copath for the member at `index`, along with the root.
+    pub fn copath(&self, index: usize) -> (RingSecretCopath<E>, RingRoot<E>) {
+        RingSecretCopath::from_publickeys(&self.members, index, self.depth as usize)
+    }
+
+    /// Compute the ring root like `root`, but return an error instead
+    /// of panicking with fewer than two members.
+    pub fn try_root(&self) -> SignatureResult<RingRoot<E>> {
+        RingRoot::try_from_publickeys(&self.members, self.depth as usize)
+    }
+
+    /// Compute the copath like `copath`, but return an error instead of
+    /// panicking with fewer than two members or `index` out of bounds.
+    pub fn try_copath(&self, index: usize) -> SignatureResult<(RingSecretCopath<E>, RingRoot<E>)> {
+        RingSecretCopath::try_from_publickeys(&self.members, index, self.depth as usize)
+    }
+
+    /// Recompute the copath of `publickey` after the ring changed,
+    /// returning its current index along with the fresh copath.
+    ///
+    /// We check `old_index` first, since members rarely move, and then
+    /// search the whole ring.  We return an error if `publickey` left the ring.
+    pub fn refresh_copath(&self, old_index: usize, publickey: &PublicKey<E>) -> SignatureResult<(usize, RingSecretCopath<E>)> {
+        let index = if self.members.get(old_index) == Some(publickey) {
+            old_index
+        } else {
+            self.members.iter().position( |pk| pk == publickey )
+                .ok_or_else( || signature_error("Public key is not a ring member") ) ?
+        };
+        Ok((index, self.try_copath(index) ?.0))
+    }
+}
+
+/// Ring represented as a sparse Merkle tree, in which members occupy
+/// arbitrary positions, like positions derived from their public keys.
+///
+/// We treat every empty subtree as zero, like `auth_hash` does for
+/// missing siblings, so sparse rings need no changes to the circuit,
+/// and produce ordinary `RingRoot`s and `RingSecretCopath`s.
+pub struct SparseRing<E: JubjubEngine> {
+    depth: u32,
+    leaves: BTreeMap<u64, E::Fr>,
+}
+
+impl<E: JubjubEngineWithParams> SparseRing<E> {
+    /// Start an empty sparse ring of the given depth, at most 64.
+    pub fn new(depth: u32) -> SparseRing<E> {
+        assert!(depth <= 64, "Sparse rings support depths up to 64");
+        SparseRing { depth, leaves: BTreeMap::new() }
+    }
+
+    /// Depth of this sparse ring.
+    pub fn depth(&self) -> u32 { self.depth }
+
+    /// Number of members.
+    pub fn len(&self) -> usize { self.leaves.len() }
+
+    /// Returns true if we have no members.
+    pub fn is_empty(&self) -> bool { self.leaves.is_empty() }
+
+    /// Position of `publickey`, derived by hashing it, for `insert`.
+    pub fn position(&self, publickey: &PublicKey<E>) -> u64 {
+        let mut t = ::merlin::Transcript::new(b"SparseRingPosition");
+        t.append_message(b"pk", publickey.0.to_xy().0.to_repr().as_ref());
+        let mut bytes = [0u8; 8];
+        t.challenge_bytes(b"", &mut bytes);
+        let position = u64::from_le_bytes(bytes);
+        if self.depth >= 64 { position } else { position & ((1u64 << self.depth) - 1) }
+    }
+
+    /// Insert `publickey` at `position`, returning an error if the
+    /// position lies outside the tree or holds another member.
+    pub fn insert_at(&mut self, position: u64, publickey: &PublicKey<E>) -> SignatureResult<()> {
+        if ring_capacity(self.depth).map_or(false, |c| position >= c) {
+            return Err(signature_error("Position lies outside the sparse ring"));
+        }
+        let leaf = publickey.0.to_xy().0;
+        match self.leaves.get(&position) {
+            Some(l) if *l != leaf => Err(signature_error("Position holds another member")),
+            _ => {  self.leaves.insert(position, leaf);  Ok(())  },
+        }
+    }
+
+    /// Insert `publickey` at its hashed `position`, which we return.
+    pub fn insert(&mut self, publickey: &PublicKey<E>) -> SignatureResult<u64> {
+        let position = self.position(publickey);
+        self.insert_at(position, publickey) ?;
+        Ok(position)
+    }
+
+    /// Occupied nodes at each level, starting from the leaves, with
+    /// the root alone at the final level.
+    fn levels(&self) -> Vec<BTreeMap<u64, E::Fr>> {
+        let mut levels = vec![self.leaves.clone()];
+        for depth_to_bottom in 0..self.depth as usize {
+            let below = levels.last().unwrap();
+            let mut above = BTreeMap::new();
+            for index in below.keys() {
+                let parent = index / 2;
+                if above.contains_key(&parent) {  continue;  }
+                let left = below.get(&(parent * 2));
+                let right = below.get(&(parent * 2 + 1));
+                above.insert(parent, auth_hash::<E>(left, right, depth_to_bottom));
+            }
+            levels.push(above);
+        }
+        levels
+    }
+
+    /// Compute the ring root.
+    pub fn root(&self) -> RingRoot<E> {
+        let levels = self.levels();
+        RingRoot( levels.last().unwrap().get(&0).cloned().unwrap_or_else(E::Fr::zero) )
+    }
+
+    /// Compute the copath for the member at `position`, or `None` if
+    /// no member occupies `position`.
+    pub fn copath(&self, position: u64) -> Option<RingSecretCopath<E>> {
+        if ! self.leaves.contains_key(&position) {  return None;  }
+        let levels = self.levels();
+        Some( RingSecretCopath( (0..self.depth as usize).map( |level| {
+            let index = position >> level;
+            let current_selection = if index % 2 == 0 {
+                MerkleSelection::Left
+            } else {
+                MerkleSelection::Right
+            };
+            CopathPoint { current_selection, sibling: levels[level].get(&(index ^ 1)).cloned() }
+        } ).collect() ) )
+    }
+}
+
+/// Ring root maintained as members append, storing only the frontier
+/// of one pending left subtree root per level, not the members.
+///
+/// We produce the same root as `RingRoot::from_publickeys` for the same
+/// members in the same order, but provide no copaths, so services that
+/// ingest members from a stream use this for the root alone.
+pub struct IncrementalRing<E: JubjubEngine> {
+    depth: u32,
+    len: u64,
+    /// Completed left subtree awaiting its right sibling at each level,
+    /// present exactly when that bit of `len` is set.
+    frontier: Vec<Option<E::Fr>>,
+    /// Root once the tree becomes full.
+    full: Option<E::Fr>,
+}
+
+impl<E: JubjubEngineWithParams> IncrementalRing<E> {
+    /// Start an empty incremental ring of the given depth, at most 64.
+    pub fn new(depth: u32) -> IncrementalRing<E> {
+        assert!(depth <= 64, "Incremental rings support depths up to 64");
+        IncrementalRing { depth, len: 0, frontier: vec![None; depth as usize], full: None }
+    }
+
+    /// Depth of this ring.
+    pub fn depth(&self) -> u32 { self.depth }
+
+    /// Number of members appended so far.
+    pub fn len(&self) -> u64 { self.len }
+
+    /// Returns true if we have no members yet.
+    pub fn is_empty(&self) -> bool { self.len == 0 }
+
+    /// Append a member, returning their index, or an error if full.
+    pub fn push(&mut self, publickey: &PublicKey<E>) -> SignatureResult<u64> {
+        if ring_capacity(self.depth).map_or(false, |c| self.len >= c) {
+            return Err(signature_error("Ring is full at this depth"));
+        }
+        let index = self.len;
+        let mut node = publickey.0.to_xy().0;
+        let mut carried = true;
+        for depth_to_bottom in 0..self.depth as usize {
+            if (index >> depth_to_bottom) & 1 == 0 {
+                self.frontier[depth_to_bottom] = Some(node);
+                carried = false;
+                break;
+            }
+            let left = self.frontier[depth_to_bottom].take();
+            node = auth_hash::<E>(left.as_ref(), Some(&node), depth_to_bottom);
+        }
+        if carried {  self.full = Some(node);  }
+        self.len += 1;
+        Ok(index)
+    }
+
+    /// Append every member from `iter`, updating the root as we go.
+    ///
+    /// We stop pulling from `iter` upon the first member that exceeds
+    /// the ring's capacity, and return an error, but retain all members
+    /// appended before it.
+    pub fn extend_from_iter<I>(&mut self, iter: I) -> SignatureResult<()>
+    where I: Iterator<Item = PublicKey<E>>
+    {
+        for publickey in iter {
+            self.push(&publickey) ?;
+        }
+        Ok(())
+    }
+
+    /// Compute the ring root, treating empty subtrees as zero like
+    /// `merkleize`, in time linear in the depth.
+    ///
+    /// We caution that provers require rings of at least two members.
+    pub fn root(&self) -> RingRoot<E> {
+        if let Some(full) = self.full.as_ref() {
+            return RingRoot(full.clone());
+        }
+        let mut node: Option<E::Fr> = None;
+        for (depth_to_bottom, left) in self.frontier.iter().enumerate() {
+            node = match (left, node) {
+                (Some(left), right) => Some(auth_hash::<E>(Some(left), right.as_ref(), depth_to_bottom)),
+                (None, Some(partial)) => Some(auth_hash::<E>(Some(&partial), None, depth_to_bottom)),
+                (None, None) => None,
+            };
+        }
+        RingRoot( node.unwrap_or_else(E::Fr::zero) )
+    }
+}
+
+/// Version byte of our ring snapshot files.
+const RING_SNAPSHOT_VERSION: u8 = 0;
+
+/// Members we preallocate for when reading ring snapshots, so corrupt
+/// member counts cannot exhaust memory before reading fails.
+const MAX_PREALLOCATED_MEMBERS: usize = 1024;
+
+/// Deepest ring we accept in snapshot files, so corrupt depths cannot
+/// make us hash billions of empty levels.  Rings of depth 64 already
+/// hold more members than any `u64` count.
+const MAX_SNAPSHOT_DEPTH: u32 = 64;
+
+/// Ring loaded from a file written by `write_snapshot`, which supplies
+/// both the root for verifiers and copaths for provers.
+pub struct RingSnapshot<E: JubjubEngine> {
+    depth: u32,
+    auth_root: RingRoot<E>,
+    members: Vec<PublicKey<E>>,
+}
+
+impl<E: JubjubEngineWithParams> RingSnapshot<E> {
+    /// Depth of the ring.
+    pub fn depth(&self) -> u32 { self.depth }
+
+    /// Ring root, which we checked against the members when reading.
+    pub fn root(&self) -> &RingRoot<E> { &self.auth_root }
+
+    /// Members by index.
+    pub fn members(&self) -> &[PublicKey<E>] { &self.members }
+
+    /// Copath for the member at `index`, or an error if out of bounds.
+    pub fn auth_path(&self, index: usize) -> SignatureResult<RingSecretCopath<E>> {
+        Ok(RingSecretCopath::try_from_publickeys(&self.members, index, self.depth as usize) ?.0)
+    }
+}
+
+/// Write `ring` as a snapshot file, consisting of a version byte, the
+/// little endian `u32` depth, the ring root, the little endian `u64`
+/// member count, and then each member's public key.
+///
+/// We place the root first, so verifiers may use `read_snapshot_root_only`.
+/// We return an error for rings with fewer than two members.
+pub fn write_snapshot<E,W>(ring: &RingBuilder<E>, mut writer: W) -> io::Result<()>
+where E: JubjubEngineWithParams, W: io::Write,
+{
+    let auth_root = ring.try_root() ?;
+    writer.write_all(&[RING_SNAPSHOT_VERSION]) ?;
+    writer.write_all(&ring.depth().to_le_bytes()) ?;
+    auth_root.write(&mut writer) ?;
+    writer.write_all(&(ring.len() as u64).to_le_bytes()) ?;
+    for pk in ring.members() {
+        pk.write(&mut writer) ?;
+    }
+    Ok(())
+}
+
+/// Read the version, depth, and root of a snapshot file.
+fn read_snapshot_header<E,R>(mut reader: R) -> io::Result<(u32, RingRoot<E>)>
+where E: JubjubEngineWithParams, R: io::Read,
+{
+    let mut version = [0u8; 1];
+    reader.read_exact(&mut version) ?;
+    if version[0] != RING_SNAPSHOT_VERSION {
+        return Err( signature_error("Ring snapshot has unknown version") );
+    }
+    let mut depth = [0u8; 4];
+    reader.read_exact(&mut depth) ?;
+    let depth = u32::from_le_bytes(depth);
+    if depth > MAX_SNAPSHOT_DEPTH {
+        return Err( signature_error("Ring snapshot is deeper than any ring we support") );
+    }
+    let auth_root = RingRoot::read(&mut reader) ?;
+    Ok((depth, auth_root))
+}
+
+/// Read only the ring root from a snapshot file, without reading or
+/// checking any members, like verifiers who trust the file's source.
+pub fn read_snapshot_root_only<E,R>(reader: R) -> io::Result<RingRoot<E>>
+where E: JubjubEngineWithParams, R: io::Read,
+{
+    Ok(read_snapshot_header(reader) ?.1)
+}
+
+/// Read a whole snapshot file written by `write_snapshot`, rejecting
+/// files whose members do not hash to the stated root.
+pub fn read_snapshot<E,R>(mut reader: R) -> io::Result<RingSnapshot<E>>
+where E: JubjubEngineWithParams, R: io::Read,
+{
+    let (depth, auth_root) = read_snapshot_header(&mut reader) ?;
+    let mut len = [0u8; 8];
+    reader.read_exact(&mut len) ?;
+    let len = u64::from_le_bytes(len);
+    if ring_capacity(depth).map_or(false, |c| len > c) {
+        return Err( signature_error("Ring snapshot has more members than its depth allows") );
+    }
+    let mut members = Vec::with_capacity((len as usize).min(MAX_PREALLOCATED_MEMBERS));
+    for _ in 0..len {
+        members.push(PublicKey::read(&mut reader) ?);
+    }
+    if RingRoot::try_from_publickeys(&members, depth as usize) ? != auth_root {
+        return Err( signature_error("Ring snapshot members do not match its root") );
+    }
+    Ok(RingSnapshot { depth, auth_root, members })
 }
 
 /// Hash function used to create the authenticated Merkle tree.
@@ -289,6 +794,7 @@ mod tests {
 
     use super::*;
     use pairing::bls12_381::{Bls12, Fr};
+    use crate::{testing, signing_context, SecretKey, VRFInput, vrf::no_extra};
 
 
     impl PartialEq for CopathPoint<Bls12> {
@@ -298,6 +804,10 @@ mod tests {
         }
     }
 
+    impl PartialEq for RingSecretCopath<Bls12> {
+        fn eq(&self, other: &Self) -> bool {  self.0 == other.0  }
+    }
+
     #[test]
     fn test_serialization() {
         let p = CopathPoint::<Bls12> {
@@ -313,4 +823,246 @@ mod tests {
         let de_p: CopathPoint::<Bls12> = CopathPoint::read(&v[..]).unwrap();
         assert_eq!(p, de_p);
     }
+
+    #[test]
+    fn test_copath_length_little_endian() {
+        let mut rng = ::rand_core::OsRng;
+        let copath = RingSecretCopath::<Bls12>::random(0x0102, &mut rng);
+        let mut v = vec![];
+        copath.write(&mut v).unwrap();
+        assert_eq!(v[..4], [0x02, 0x01, 0x00, 0x00]);
+    }
+
+    #[test]
+    fn test_sparse_ring() {
+        let mut rng = ::rand_core::OsRng;
+        let pks = (0..5).map( |_| crate::SecretKey::<Bls12>::from_rng(&mut rng).to_public() ).collect::<Vec<_>>();
+
+        // A full tree agrees with RingRoot::from_publickeys.
+        let mut full = SparseRing::<Bls12>::new(2);
+        for (i,pk) in pks[..4].iter().enumerate() {  full.insert_at(i as u64, pk).unwrap();  }
+        assert!(full.root() == RingRoot::from_publickeys(&pks[..4], 2));
+        let (copath, _) = RingSecretCopath::from_publickeys(&pks[..4], 3, 2);
+        assert!(full.copath(3).unwrap() == copath);
+
+        let mut sparse = SparseRing::<Bls12>::new(32);
+        let positions = pks.iter().map( |pk| sparse.insert(pk).unwrap() ).collect::<Vec<_>>();
+        assert_eq!(sparse.len(), 5);
+        let root = sparse.root();
+        for (pk,position) in pks.iter().zip(positions.iter()) {
+            assert_eq!(*position, sparse.position(pk));
+            let copath = sparse.copath(*position).unwrap();
+            assert_eq!(copath.depth(), 32);
+            assert_eq!(copath.index() as u64, *position);
+            assert!(copath.to_root(pk) == root);
+        }
+
+        // Non-members have no copath, and no member's copath authenticates them.
+        let outsider = crate::SecretKey::<Bls12>::from_rng(&mut rng).to_public();
+        assert!(sparse.copath(sparse.position(&outsider)).is_none());
+        assert!(sparse.copath(positions[0]).unwrap().to_root(&outsider) != root);
+        assert!(sparse.insert_at(positions[0], &outsider).is_err());
+        assert!(SparseRing::<Bls12>::new(2).insert_at(4, &outsider).is_err());
+    }
+
+    #[test]
+    fn test_ring_capacity() {
+        assert_eq!(ring_capacity(0), Some(1));
+        assert_eq!(ring_capacity(10), Some(1024));
+        assert_eq!(ring_capacity(20), Some(1 << 20));
+        assert_eq!(ring_capacity(63), Some(1 << 63));
+        assert_eq!(ring_capacity(64), None);
+        assert_eq!(ring_capacity(u32::max_value()), None);
+
+        let mut rng = ::rand_core::OsRng;
+        let mut builder = RingBuilder::<Bls12>::new(2);
+        assert_eq!(builder.remaining(), 4);
+        for i in 0..4 {
+            let pk = crate::SecretKey::<Bls12>::from_rng(&mut rng).to_public();
+            assert_eq!(builder.push(pk).unwrap(), i);
+        }
+        assert_eq!(builder.remaining(), 0);
+        assert!(builder.push(crate::SecretKey::<Bls12>::from_rng(&mut rng).to_public()).is_err());
+        let (copath, root) = builder.copath(3);
+        assert!(root == builder.root());
+        assert!(copath.to_root(&builder.members()[3]) == root);
+
+        assert_eq!(RingBuilder::<Bls12>::new(64).remaining(), u64::max_value());
+    }
+
+    #[test]
+    fn test_refresh_copath() {
+        let mut rng = ::rand_core::OsRng;
+        let mut pk = || crate::SecretKey::<Bls12>::from_rng(&mut rng).to_public();
+        let mut builder = RingBuilder::<Bls12>::new(4);
+        builder.push(pk()).unwrap();
+        let me = pk();
+        let index = builder.push(me.clone()).unwrap();
+        let (copath, old_root) = builder.copath(index);
+        assert!(copath.to_root(&me) == old_root);
+
+        for _ in 0..5 {  builder.push(pk()).unwrap();  }
+        let root = builder.root();
+        assert!(copath.to_root(&me) != root);
+        let (refreshed_index, refreshed) = builder.refresh_copath(index, &me).unwrap();
+        assert_eq!(refreshed_index, index);
+        assert!(refreshed.to_root(&me) == root);
+
+        // A stale index still finds the member.
+        let (found, refreshed) = builder.refresh_copath(5, &me).unwrap();
+        assert_eq!(found, index);
+        assert!(refreshed.to_root(&me) == root);
+        assert!(builder.refresh_copath(index, &pk()).is_err());
+    }
+
+    #[test]
+    fn test_try_from_publickeys() {
+        let mut rng = ::rand_core::OsRng;
+        let pks = (0..5).map( |_| crate::SecretKey::<Bls12>::from_rng(&mut rng).to_public() ).collect::<Vec<_>>();
+
+        assert!(RingRoot::try_from_publickeys(&pks[..0], 2).is_err());
+        assert!(RingRoot::try_from_publickeys(&pks[..1], 2).is_err());
+        assert!(RingRoot::try_from_publickeys(&pks, 2).is_err());
+        assert!(RingRoot::try_from_publickeys(&pks[..4], 2).unwrap() == RingRoot::from_publickeys(&pks[..4], 2));
+
+        assert!(RingSecretCopath::try_from_publickeys(&pks[..1], 0, 2).is_err());
+        assert!(RingSecretCopath::try_from_publickeys(&pks[..4], 4, 2).is_err());
+        let (copath, root) = RingSecretCopath::try_from_publickeys(&pks[..4], 3, 2).unwrap();
+        assert!(copath.to_root(&pks[3]) == root);
+
+        let mut builder = RingBuilder::<Bls12>::new(2);
+        assert!(builder.try_root().is_err());
+        builder.push(pks[0].clone()).unwrap();
+        assert!(builder.try_copath(0).is_err());
+        assert!(builder.refresh_copath(0, &pks[0]).is_err());
+        builder.push(pks[1].clone()).unwrap();
+        assert!(builder.try_copath(2).is_err());
+        assert!(builder.try_copath(1).unwrap().0.to_root(&pks[1]) == builder.try_root().unwrap());
+    }
+
+    #[test]
+    fn test_incremental_ring() {
+        let (_, ring) = crate::testing::deterministic_ring::<Bls12>(100, 7);
+        let mut incremental = IncrementalRing::<Bls12>::new(7);
+        for (i, pk) in ring.members().iter().enumerate() {
+            assert_eq!(incremental.push(pk).unwrap(), i as u64);
+            if i > 0 {
+                assert!(incremental.root() == RingRoot::from_publickeys(&ring.members()[..=i], 7));
+            }
+        }
+
+        let mut streamed = IncrementalRing::<Bls12>::new(7);
+        streamed.extend_from_iter(ring.members().iter().cloned()).unwrap();
+        assert_eq!(streamed.len(), 100);
+        assert!(streamed.root() == ring.root());
+
+        // We fill depth 3 with eight members, and stop upon the ninth.
+        let mut small = IncrementalRing::<Bls12>::new(3);
+        let mut members = ring.members().iter().cloned();
+        assert!(small.extend_from_iter(members.by_ref().take(10)).is_err());
+        assert_eq!(small.len(), 8);
+        assert!(small.root() == RingRoot::from_publickeys(&ring.members()[..8], 3));
+        assert!(members.next().unwrap() == ring.members()[9]);
+    }
+
+    #[test]
+    fn test_ring_snapshot() {
+        let mut rng = ::rand_core::OsRng;
+        let mut builder = RingBuilder::<Bls12>::new(3);
+        for _ in 0..5 {
+            builder.push(crate::SecretKey::<Bls12>::from_rng(&mut rng).to_public()).unwrap();
+        }
+        let mut bytes = Vec::new();
+        write_snapshot(&builder, &mut bytes).unwrap();
+
+        let root = read_snapshot_root_only::<Bls12,_>(&bytes[..]).unwrap();
+        assert!(root == builder.root());
+        let snapshot = read_snapshot::<Bls12,_>(&bytes[..]).unwrap();
+        assert!(*snapshot.root() == root);
+        assert_eq!(snapshot.depth(), 3);
+        for (i,pk) in snapshot.members().iter().enumerate() {
+            assert!(snapshot.auth_path(i).unwrap().to_root(pk) == root);
+        }
+        assert!(snapshot.auth_path(5).is_err());
+
+        // Tampering with the root or truncating members fails.
+        let mut tampered = bytes.clone();
+        tampered[5] ^= 1;
+        assert!(read_snapshot::<Bls12,_>(&tampered[..]).is_err());
+        assert!(read_snapshot::<Bls12,_>(&bytes[..bytes.len() - 1]).is_err());
+        let mut versioned = bytes.clone();
+        versioned[0] = RING_SNAPSHOT_VERSION + 1;
+        assert!(read_snapshot_root_only::<Bls12,_>(&versioned[..]).is_err());
+
+        // Hostile depths fail before we hash any levels.
+        for depth in [MAX_SNAPSHOT_DEPTH + 1, u32::MAX].iter() {
+            let mut deep = bytes.clone();
+            deep[1..5].copy_from_slice(&depth.to_le_bytes());
+            assert!(read_snapshot_root_only::<Bls12,_>(&deep[..]).is_err());
+            assert!(read_snapshot::<Bls12,_>(&deep[..]).is_err());
+        }
+        assert!(write_snapshot(&RingBuilder::<Bls12>::new(3), &mut Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_duplicate_members() {
+        let mut rng = ::rand_core::OsRng;
+        let pk = crate::SecretKey::<Bls12>::from_rng(&mut rng).to_public();
+        let other = crate::SecretKey::<Bls12>::from_rng(&mut rng).to_public();
+
+        let mut builder = RingBuilder::<Bls12>::new(2);
+        builder.push(other.clone()).unwrap();
+        builder.push(pk.clone()).unwrap();
+        let e = builder.push(pk.clone()).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::AlreadyExists);
+        assert!(e.to_string().contains("index 1"));
+        assert_eq!(builder.len(), 2);
+
+        let mut padded = RingBuilder::<Bls12>::new(2).allow_duplicates();
+        padded.push(pk.clone()).unwrap();
+        assert_eq!(padded.push(pk).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_copath_from_oracle() {
+        let mut rng = ::rand_core::OsRng;
+        let depth = 4;
+        let pks = (0..5).map( |_| crate::SecretKey::<Bls12>::from_rng(&mut rng).to_public() ).collect::<Vec<_>>();
+        let root = RingRoot::<Bls12>::from_publickeys(&pks, depth);
+
+        for (index,pk) in pks.iter().enumerate() {
+            let (copath, root2) = RingSecretCopath::<Bls12>::from_publickeys(&pks, index, depth);
+            assert_eq!(root.0, root2.0);
+            assert_eq!(copath.to_root(pk).0, root.0);
+
+            let oracle = |level: u32| copath.0[level as usize].sibling.clone();
+            let copath2 = RingSecretCopath::<Bls12>::from_oracle(depth as u32, index, oracle);
+            assert_eq!(copath, copath2);
+            assert_eq!(copath2.to_root(pk).0, root.0);
+        }
+    }
+
+    #[test]
+    fn test_sparse_ring_proof() {
+        let mut rng = ::rand_core::OsRng;
+        let fx = testing::RingFixture::shared();
+        let (depth, srs) = (fx.depth, fx.srs());
+
+        let sks = (0..3).map( |_| SecretKey::<Bls12>::from_rng(&mut rng) ).collect::<Vec<_>>();
+        let mut ring = SparseRing::<Bls12>::new(depth);
+        ring.insert_at(0, &sks[0].to_public()).unwrap();
+        ring.insert_at(3, &sks[1].to_public()).unwrap();
+        let auth_root = ring.root();
+        let input = || VRFInput::<Bls12>::new_malleable(signing_context(b"sparse").bytes(b"meow"));
+
+        let copath = ring.copath(3).unwrap();
+        let (inout, proof) = sks[1].ring_vrf_sign_first(input(), no_extra(), copath, srs).unwrap();
+        assert!(auth_root.ring_vrf_verify(inout, no_extra(), proof, &fx.pvk).unwrap());
+
+        // A non-member borrowing a member's copath proves membership in another ring.
+        let copath = ring.copath(0).unwrap();
+        assert!(copath.to_root(&sks[2].to_public()) != auth_root);
+        let (inout, proof) = sks[2].ring_vrf_sign_first(input(), no_extra(), copath, srs).unwrap();
+        assert!(! auth_root.ring_vrf_verify(inout, no_extra(), proof, &fx.pvk).unwrap());
+    }
 }
@@ -0,0 +1,119 @@
+// Copyright (c) 2026 The ring-vrf contributors
+//
+// Licensed under the MIT license, see LICENSE.
+
+//! ### Verification counters for monitoring
+//!
+//! We only maintain counters here, which operators may export to
+//! Prometheus or similar themselves.
+
+use core::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+
+/// Why a verifier rejected a proof.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RejectReason {
+    /// The pairing check failed.
+    InvalidProof,
+    /// The ring root lies outside the verifier's allowlist.
+    UnknownRoot,
+    /// Verification failed with some other error.
+    Error,
+}
+
+/// Counters updated atomically by `VerifierCache`.
+#[derive(Debug, Default)]
+pub struct VerifierMetrics {
+    verified: AtomicU64,
+    invalid_proof: AtomicU64,
+    unknown_root: AtomicU64,
+    error: AtomicU64,
+    verify_nanos: AtomicU64,
+    verify_count: AtomicU64,
+    batches: AtomicU64,
+    batched_proofs: AtomicU64,
+}
+
+/// Point in time copy of `VerifierMetrics`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct VerifierMetricsSnapshot {
+    /// Proofs verified successfully
+    pub verified: u64,
+    /// Proofs rejected because the pairing check failed
+    pub invalid_proof: u64,
+    /// Proofs rejected because of their ring root
+    pub unknown_root: u64,
+    /// Proofs rejected by some other error
+    pub error: u64,
+    /// Proofs that reached the pairing check, whether accepted or not
+    pub pairing_checks: u64,
+    /// Average time spent in the pairing check, over all proofs
+    /// that reached it
+    pub average_latency: Duration,
+    /// Batches passed to `VerifierCache::ring_vrf_verify_batch`
+    pub batches: u64,
+    /// Proofs submitted in those batches, whatever their outcome
+    pub batched_proofs: u64,
+}
+
+impl VerifierMetricsSnapshot {
+    /// Total proofs rejected for any reason
+    pub fn rejected(&self) -> u64 {
+        self.invalid_proof + self.unknown_root + self.error
+    }
+
+    /// Average number of proofs per batch, rounded down
+    pub fn average_batch_size(&self) -> u64 {
+        if self.batches == 0 { 0 } else { self.batched_proofs / self.batches }
+    }
+}
+
+impl VerifierMetrics {
+    /// Record one successfully verified proof.
+    pub(crate) fn record_verified(&self) {
+        self.verified.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record one rejected proof.
+    pub(crate) fn record_rejected(&self, reason: RejectReason) {
+        let counter = match reason {
+            RejectReason::InvalidProof => &self.invalid_proof,
+            RejectReason::UnknownRoot => &self.unknown_root,
+            RejectReason::Error => &self.error,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record the time spent in one pairing check.
+    pub(crate) fn record_latency(&self, latency: Duration) {
+        let nanos = latency.as_nanos().min(u64::max_value() as u128) as u64;
+        self.verify_nanos.fetch_add(nanos, Ordering::Relaxed);
+        self.verify_count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record one batch of `size` proofs.
+    pub(crate) fn record_batch(&self, size: usize) {
+        self.batches.fetch_add(1, Ordering::Relaxed);
+        self.batched_proofs.fetch_add(size as u64, Ordering::Relaxed);
+    }
+
+    /// Copy all counters.
+    ///
+    /// We load each counter separately, so concurrent verifications
+    /// may leave a snapshot slightly inconsistent.
+    pub fn snapshot(&self) -> VerifierMetricsSnapshot {
+        let count = self.verify_count.load(Ordering::Relaxed);
+        let nanos = self.verify_nanos.load(Ordering::Relaxed);
+        VerifierMetricsSnapshot {
+            verified: self.verified.load(Ordering::Relaxed),
+            invalid_proof: self.invalid_proof.load(Ordering::Relaxed),
+            unknown_root: self.unknown_root.load(Ordering::Relaxed),
+            error: self.error.load(Ordering::Relaxed),
+            pairing_checks: count,
+            average_latency: Duration::from_nanos(if count == 0 { 0 } else { nanos / count }),
+            batches: self.batches.load(Ordering::Relaxed),
+            batched_proofs: self.batched_proofs.load(Ordering::Relaxed),
+        }
+    }
+}
@@ -1,10 +1,11 @@
 
 use std::io;
+use core::fmt;
 
 use ff::PrimeField;
 use zcash_primitives::jubjub::{
     JubjubEngine, FixedGenerators, JubjubParams,
-    PrimeOrder, edwards::Point
+    PrimeOrder, Unknown, edwards::Point
 };
 
 use crate::JubjubEngineWithParams;
@@ -34,6 +35,46 @@ impl ReadWrite for () {
 }
 
 
+/// Write lowercase hex, for use in `Display` implementations.
+pub(crate) fn fmt_hex(bytes: &[u8], f: &mut fmt::Formatter) -> fmt::Result {
+    for b in bytes {  write!(f, "{:02x}", b) ?;  }
+    Ok(())
+}
+
+/// Bytes displayed as lowercase hex.
+pub(crate) struct Hex<'a>(pub &'a [u8]);
+
+impl<'a> fmt::Display for Hex<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt_hex(self.0, f)
+    }
+}
+
+/// Decode hex, either case, rejecting odd lengths and non-hex characters.
+pub(crate) fn decode_hex(s: &str) -> io::Result<Vec<u8>> {
+    if s.len() % 2 != 0 {
+        return Err( signature_error("hex string has odd length") );
+    }
+    s.as_bytes().chunks(2).map( |c| {
+        let c = core::str::from_utf8(c).map_err( |_| signature_error("invalid hex character") ) ?;
+        u8::from_str_radix(c, 16).map_err( |_| signature_error("invalid hex character") )
+    } ).collect()
+}
+
+/// Decode hex and then deserialize using `read`, rejecting any trailing bytes.
+pub(crate) fn read_hex<T,F>(s: &str, read: F) -> io::Result<T>
+where F: FnOnce(&mut &[u8]) -> io::Result<T>
+{
+    let bytes = decode_hex(s) ?;
+    let mut reader = &bytes[..];
+    let t = read(&mut reader) ?;
+    if !reader.is_empty() {
+        return Err( signature_error("trailing bytes after hex encoded value") );
+    }
+    Ok(t)
+}
+
+
 pub(crate) type Scalar<E> = <E as JubjubEngine>::Fs;
 
 pub fn read_scalar<E: JubjubEngine, R: io::Read>(mut reader: R) -> io::Result<E::Fs> {
@@ -50,15 +91,146 @@ pub fn write_scalar<E: JubjubEngine, W: io::Write>(s: &E::Fs, mut writer: W) ->
 
 
 /// Create a 128 bit `Scalar` for delinearization
+///
+/// We read `s` as a little endian integer, regardless of the target's
+/// endianness, because `PrimeField::Repr` is a little endian byte array.
 pub(crate) fn scalar_from_u128<E>(s: [u8; 16]) -> Scalar<E> 
 where E: JubjubEngine
 {
     let mut repr = <Scalar<E> as PrimeField>::Repr::default();
-    repr.as_mut().copy_from_slice(&s);
+    repr.as_mut()[..16].copy_from_slice(&s);
     Scalar::<E>::from_repr(repr).unwrap()
 }
 
 
+/// Compute `∑ z[i] points[i]` for 128 bit little endian scalars `z[i]`,
+/// using variable time arithmetic.
+///
+/// We interleave 4 bit windows across all points, so we perform only
+/// 128 doublings in total, instead of roughly 252 doublings per point
+/// when multiplying by each `scalar_from_u128` separately.
+pub(crate) fn multiply_u128s<'a,E,Subgroup,I>(points: I, zs: &[[u8; 16]]) -> Point<E,Subgroup>
+where
+    E: JubjubEngineWithParams,
+    Subgroup: 'a,
+    I: IntoIterator<Item=&'a Point<E,Subgroup>>,
+{
+    let params = E::params();
+
+    // Precompute [0, P, 2 P, .., 15 P] for each point.
+    let tables = points.into_iter().map( |p| {
+        let mut table = Vec::with_capacity(16);
+        table.push(Point::zero());
+        for i in 1..16 {
+            let next = p.add(&table[i-1], params);
+            table.push(next);
+        }
+        table
+    } ).collect::<Vec<_>>();
+    assert!(tables.len() == zs.len());
+
+    let mut acc = Point::zero();
+    for byte in (0..16).rev() {
+        for shift in [4u8, 0].iter() {
+            for _ in 0..4 {  acc = acc.double(params);  }
+            for (table,z) in tables.iter().zip(zs) {
+                let nibble = ((z[byte] >> shift) & 0x0f) as usize;
+                if nibble != 0 {  acc = acc.add(&table[nibble], params);  }
+            }
+        }
+    }
+    acc
+}
+
+/// Compute `scalar point` with uniform operations, regardless of `scalar`.
+///
+/// We use fixed 4 bit windows, always performing four doublings and one
+/// addition per window, and we select each table entry by scanning the
+/// whole table with `subtle`.  We cannot select zcash's points without
+/// branching directly, so we scan their compressed encodings and decode
+/// the selected entry, which costs one square root per window.
+///
+/// We cannot make guarantees about zcash's field arithmetic, which
+/// looks uniform, nor about the compiler, so this only reduces timing
+/// leakage relative to `Point::mul`, which branches on every bit.
+pub(crate) fn mul_consttime<E,Subgroup>(point: &Point<E,Subgroup>, scalar: &Scalar<E>) -> Point<E,Unknown>
+where E: JubjubEngineWithParams,
+{
+    let mut nibbles = scalar_nibbles::<E>(scalar);
+    let p = mul_consttime_nibbles(point, &nibbles);
+    ::zeroize::Zeroize::zeroize(&mut nibbles);
+    p
+}
+
+/// Split `scalar` into the 4 bit windows `mul_consttime_nibbles` consumes,
+/// most significant first, so callers multiplying many points by one
+/// scalar split it only once.
+pub(crate) fn scalar_nibbles<E: JubjubEngine>(scalar: &Scalar<E>) -> [u8; 64] {
+    let repr = scalar.to_repr();
+    let mut nibbles = [0u8; 64];
+    for (i,byte) in repr.as_ref().iter().rev().enumerate() {
+        nibbles[2*i] = byte >> 4;
+        nibbles[2*i+1] = byte & 0x0f;
+    }
+    nibbles
+}
+
+/// Compute `scalar point` like `mul_consttime`, given `scalar_nibbles(scalar)`.
+pub(crate) fn mul_consttime_nibbles<E,Subgroup>(point: &Point<E,Subgroup>, nibbles: &[u8; 64]) -> Point<E,Unknown>
+where E: JubjubEngineWithParams,
+{
+    use subtle::{ConditionallySelectable, ConstantTimeEq};
+    let params = E::params();
+
+    // Encode [0, P, 2 P, .., 15 P] once.
+    let mut table = [[0u8; 32]; 16];
+    let mut cur = Point::<E,Subgroup>::zero();
+    for entry in table.iter_mut() {
+        cur.write(&mut entry[..]).expect("Points encode into 32 bytes");
+        cur = cur.add(point, params);
+    }
+
+    let mut acc = Point::<E,Unknown>::zero();
+    for nibble in nibbles.iter() {
+        for _ in 0..4 {  acc = acc.double(params);  }
+        let mut selected = [0u8; 32];
+        for (i,entry) in table.iter().enumerate() {
+            let choice = nibble.ct_eq(&(i as u8));
+            for (s,e) in selected.iter_mut().zip(entry.iter()) {
+                s.conditional_assign(e, choice);
+            }
+        }
+        let q = Point::<E,Unknown>::read(&selected[..], params)
+            .expect("Our table encodes valid points");
+        acc = acc.add(&q, params);
+    }
+    acc
+}
+
+/// Multiply `point` by `JubjubEngineWithParams::COFACTOR`, landing
+/// in the prime order subgroup.
+pub(crate) fn clear_cofactor<E,Subgroup>(point: &Point<E,Subgroup>) -> Point<E,PrimeOrder>
+where E: JubjubEngineWithParams,
+{
+    let params = E::params();
+    if E::COFACTOR == 8 {
+        return point.mul_by_cofactor(params);
+    }
+    mul_by_small(point, E::COFACTOR).as_prime_order(params)
+        .expect("Multiplying by the cofactor lands in the prime order subgroup")
+}
+
+/// Compute `k point` for a small public `k` by double and add.
+pub(crate) fn mul_by_small<E,Subgroup>(point: &Point<E,Subgroup>, k: u64) -> Point<E,Subgroup>
+where E: JubjubEngineWithParams,
+{
+    let params = E::params();
+    (0..64).rev().fold(Point::zero(), |acc, i| {
+        let acc = acc.double(params);
+        if (k >> i) & 1 == 1 { acc.add(point, params) } else { acc }
+    })
+}
+
 pub(crate) fn scalar_times_generator<E>(scalar: &Scalar<E>)
  -> Point<E,PrimeOrder> 
 where E: JubjubEngineWithParams,
@@ -99,3 +271,78 @@ pub(crate) fn scalar_to_bytes<E: JubjubEngine>(s: &E::Fs)
 }
 */
 
+
+#[cfg(test)]
+mod tests {
+    use ff::Field;
+    use pairing::bls12_381::Bls12;
+    use zcash_primitives::jubjub::edwards::Point;
+
+    use super::*;
+
+    /// Compute the scalar for `x` using only field arithmetic, so our
+    /// expected values never touch any byte order.
+    fn scalar_by_double_and_add(x: u128) -> Scalar<Bls12> {
+        let mut acc = Scalar::<Bls12>::zero();
+        for i in (0..128).rev() {
+            acc = acc.double();
+            if (x >> i) & 1 == 1 {  acc.add_assign(&Scalar::<Bls12>::one());  }
+        }
+        acc
+    }
+
+    #[test]
+    fn mul_consttime_matches_mul() {
+        let mut rng = ::rand_core::OsRng;
+        let params = Bls12::params();
+        let p = Point::<Bls12,_>::rand(&mut rng, params);
+        let scalars = [
+            Scalar::<Bls12>::zero(),
+            Scalar::<Bls12>::one(),
+            -Scalar::<Bls12>::one(),
+            Scalar::<Bls12>::random(&mut rng),
+            Scalar::<Bls12>::random(&mut rng),
+        ];
+        for s in scalars.iter() {
+            assert!(mul_consttime(&p, s) == p.mul(s.clone(), params));
+        }
+    }
+
+    /// We cannot construct a mock engine with another cofactor, as
+    /// zcash implements JubJub only, so we check the generic path.
+    #[test]
+    fn clear_cofactor_generic_path() {
+        let mut rng = ::rand_core::OsRng;
+        let params = Bls12::params();
+        let p = Point::<Bls12,_>::rand(&mut rng, params);
+        assert!(mul_by_small(&p, 8) == p.mul_by_cofactor(params).into());
+        assert!(clear_cofactor(&p) == p.mul_by_cofactor(params));
+        // Any multiple of the cofactor also clears it.
+        assert!(mul_by_small(&p, 24).as_prime_order(params).is_some());
+    }
+
+    #[test]
+    fn scalar_from_u128_is_little_endian() {
+        let xs = [0u128, 1, 0x0102, 1 << 64, (1 << 64) - 1, 0x0123_4567_89ab_cdef_fedc_ba98_7654_3210, u128::max_value()];
+        for x in xs.iter() {
+            // Assemble the little endian bytes by shifting, not `to_le_bytes`,
+            // so this test means the same on big endian targets.
+            let mut s = [0u8; 16];
+            for (i,b) in s.iter_mut().enumerate() {  *b = (x >> (8*i)) as u8;  }
+            assert_eq!(s, x.to_le_bytes());
+            assert!(scalar_from_u128::<Bls12>(s) == scalar_by_double_and_add(*x));
+        }
+    }
+
+    #[test]
+    fn multiply_u128s_matches_scalar_multiplication() {
+        let params = Bls12::params();
+        let mut rng = ::rand_core::OsRng;
+        let points = (0..3).map( |_| Point::<Bls12,_>::rand(&mut rng, params).mul_by_cofactor(params) ).collect::<Vec<_>>();
+        let zs = [[0u8; 16], [0xffu8; 16], 0x0123_4567_89ab_cdef_fedc_ba98_7654_3210u128.to_le_bytes()];
+        let expected = points.iter().zip(zs.iter()).fold(Point::zero(), |acc, (p,z)|
+            acc.add(&p.mul(scalar_by_double_and_add(u128::from_le_bytes(*z)), params), params)
+        );
+        assert!(multiply_u128s(points.iter(), &zs) == expected);
+    }
+}
@@ -0,0 +1,157 @@
+// Copyright (c) 2026 The ring-vrf contributors
+//
+// Licensed under the MIT license, see LICENSE.
+
+//! ### Oblivious VRF evaluation through re-blinding relays
+//!
+//! A client blinds its VRF input `H` by a secret scalar `b`, using
+//! `VRFInput::blind`, the signer evaluates on `b H`, and the client
+//! recovers `sk H` with `VRFPreOut::unblind`.
+//!
+//! A relay between them may re-blind the blinded pair by another scalar
+//! `d`, producing `(d b H, d b sk H)`, and sending `d` to the client
+//! privately.  We prove with a Schnorr DLEQ proof that the relay
+//! multiplied both input and output by the same `d`, so a malicious
+//! relay cannot substitute another output.  We never prove here that
+//! the signer evaluated correctly, for which use `dleq_proove` on the
+//! blinded `VRFInOut`.
+
+use std::io;
+
+use std::ops::{SubAssign, MulAssign};
+
+use rand_core::{RngCore, CryptoRng};
+
+use crate::{
+    JubjubEngineWithParams, ReadWrite, SigningTranscript,
+    Scalar, VRFInOut, read_scalar, write_scalar,
+};
+
+
+/// Re-blind both the input and output of `inout` by `r_delta`.
+pub fn reblind<E: JubjubEngineWithParams>(inout: &VRFInOut<E>, r_delta: &Scalar<E>) -> VRFInOut<E> {
+    VRFInOut {
+        input: inout.input.blind(r_delta),
+        output: inout.output.blind(r_delta),
+    }
+}
+
+/// Schnorr DLEQ proof that a relay re-blinded one `VRFInOut` into
+/// another, consisting of the challenge and response.
+#[derive(Debug, Clone)]
+pub struct ReblindProof<E: JubjubEngineWithParams> {
+    c: Scalar<E>,
+    s: Scalar<E>,
+}
+
+/// Commit both pairs to `t`, identically for prover and verifier.
+fn commit_pairs<E,T>(t: &mut T, old: &VRFInOut<E>, new: &VRFInOut<E>)
+where E: JubjubEngineWithParams, T: SigningTranscript,
+{
+    t.proto_name(b"ReblindProof");
+    t.commit_point(b"old:h", old.input.as_point());
+    t.commit_point(b"old:h^sk", old.output.as_point());
+    t.commit_point(b"new:h", new.input.as_point());
+    t.commit_point(b"new:h^sk", new.output.as_point());
+}
+
+/// Prove that `new` equals `reblind(old, r_delta)`.
+///
+/// We derive the nonce from `r_delta` along with `rng`, like our
+/// other Schnorr proofs derive nonces from secret key nonce seeds.
+pub fn prove_reblind<E,T,R>(mut t: T, old: &VRFInOut<E>, new: &VRFInOut<E>, r_delta: &Scalar<E>, rng: R)
+ -> ReblindProof<E>
+where E: JubjubEngineWithParams, T: SigningTranscript, R: RngCore+CryptoRng,
+{
+    let params = E::params();
+    commit_pairs(&mut t, old, new);
+
+    let mut seed = [0u8; 32];
+    write_scalar::<E,_>(r_delta, &mut seed[..]).expect("Scalars encode as 32 bytes.");
+    let [k] : [Scalar<E>;1] = t.witness_scalars(b"reblinding\00", &[&seed], rng);
+    t.commit_point(b"old:h^k", &old.input.as_point().mul(k.clone(), params));
+    t.commit_point(b"old:h^sk^k", &old.output.as_point().mul(k.clone(), params));
+
+    let c: Scalar<E> = t.challenge_scalar(b"prove");
+    let mut s = k;
+    let mut tmp = r_delta.clone();
+    tmp.mul_assign(&c);
+    s.sub_assign(&tmp);
+    ReblindProof { c, s }
+}
+
+impl<E: JubjubEngineWithParams> ReblindProof<E> {
+    /// Verify that `new` equals `old` re-blinded by some scalar.
+    pub fn verify<T: SigningTranscript>(&self, mut t: T, old: &VRFInOut<E>, new: &VRFInOut<E>) -> bool {
+        let params = E::params();
+        commit_pairs(&mut t, old, new);
+
+        // s h + c d h = (k - c d) h + c d h = k h
+        let input = old.input.as_point().mul(self.s.clone(), params)
+            .add(&new.input.as_point().mul(self.c.clone(), params), params);
+        let output = old.output.as_point().mul(self.s.clone(), params)
+            .add(&new.output.as_point().mul(self.c.clone(), params), params);
+        t.commit_point(b"old:h^k", &input);
+        t.commit_point(b"old:h^sk^k", &output);
+
+        let c: Scalar<E> = t.challenge_scalar(b"prove");
+        c == self.c
+    }
+}
+
+impl<E: JubjubEngineWithParams> ReadWrite for ReblindProof<E> {
+    fn read<R: io::Read>(mut reader: R) -> io::Result<Self> {
+        let c = read_scalar::<E,_>(&mut reader) ?;
+        let s = read_scalar::<E,_>(&mut reader) ?;
+        Ok(ReblindProof { c, s })
+    }
+
+    fn write<W: io::Write>(&self, mut writer: W) -> io::Result<()> {
+        write_scalar::<E,_>(&self.c, &mut writer) ?;
+        write_scalar::<E,_>(&self.s, &mut writer)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use ff::Field;
+    use pairing::bls12_381::Bls12;
+
+    use super::*;
+    use crate::{SecretKey, VRFInput, signing_context};
+
+    #[test]
+    fn reblind_round_trip() {
+        let mut rng = ::rand_core::OsRng;
+        let sk = SecretKey::<Bls12>::from_rng(&mut rng);
+        let input = VRFInput::<Bls12>::new_malleable(signing_context(b"oblivious").bytes(b"meow"));
+        let expected = input.to_preout(&sk);
+
+        // Client blinds, signer evaluates, relay re-blinds.
+        let b = <Scalar<Bls12> as Field>::random(&mut rng);
+        let old = input.blind(&b).to_inout(&sk);
+        let d = <Scalar<Bls12> as Field>::random(&mut rng);
+        let new = reblind(&old, &d);
+        let proof = prove_reblind(signing_context(b"relay").bytes(b""), &old, &new, &d, &mut rng);
+
+        let mut bytes = Vec::new();
+        proof.write(&mut bytes).unwrap();
+        let proof = ReblindProof::<Bls12>::read(&bytes[..]).unwrap();
+        assert!(proof.verify(signing_context(b"relay").bytes(b""), &old, &new));
+        assert!(! proof.verify(signing_context(b"other").bytes(b""), &old, &new));
+
+        let mut bd = b.clone();
+        bd.mul_assign(&d);
+        let recovered = new.output.unblind(&bd).unwrap();
+        assert!(recovered.as_point() == expected.as_point());
+        assert!(new.output.unblind(&Scalar::<Bls12>::zero()).is_none());
+
+        // A relay substituting another signer's output fails.
+        let other = SecretKey::<Bls12>::from_rng(&mut rng);
+        let substituted = reblind(&input.blind(&b).to_inout(&other), &d);
+        let forged = prove_reblind(signing_context(b"relay").bytes(b""), &old, &substituted, &d, &mut rng);
+        assert!(! forged.verify(signing_context(b"relay").bytes(b""), &old, &substituted));
+        assert!(! proof.verify(signing_context(b"relay").bytes(b""), &old, &substituted));
+    }
+}
@@ -0,0 +1,158 @@
+// Copyright (c) 2026 The ring-vrf contributors
+//
+// Licensed under the MIT license, see LICENSE.
+
+//! ### Key usage policies for ring VRF signing
+//!
+//! Deployments with key usage policies, like HSM backed rate limits,
+//! wrap their `SecretKey` in a `PolicySigner`, which consults its
+//! `UsagePolicy` before every signature, and so before any proving work.
+//! Signing with a bare `SecretKey` consults no policy and costs nothing extra.
+
+use std::io;
+
+use bellman::{groth16, SynthesisError};
+
+use crate::{
+    JubjubEngineWithParams, SynthesisResult, RingSRS, SigningTranscript,
+    SecretKey, RingSecretCopath, RingVRFProof,
+    VRFInput, VRFInOut, VRFPreOut,
+};
+
+
+/// Refusal by a `UsagePolicy` to permit another signature.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PolicyDenied(pub &'static str);
+
+impl From<PolicyDenied> for SynthesisError {
+    fn from(denied: PolicyDenied) -> SynthesisError {
+        SynthesisError::IoError(io::Error::new(io::ErrorKind::PermissionDenied, denied.0))
+    }
+}
+
+/// Policy consulted before each ring VRF signature by a `PolicySigner`,
+/// which may record usage or deny signing.
+pub trait UsagePolicy {
+    /// Permit or deny one more signature.
+    fn before_sign(&mut self) -> Result<(), PolicyDenied>;
+}
+
+/// Secret key whose ring VRF signatures require permission from a
+/// `UsagePolicy`.
+///
+/// We return an `io::ErrorKind::PermissionDenied` error whenever the
+/// policy denies signing.
+pub struct PolicySigner<E: JubjubEngineWithParams, P: UsagePolicy> {
+    secret: SecretKey<E>,
+    policy: P,
+}
+
+impl<E: JubjubEngineWithParams, P: UsagePolicy> PolicySigner<E,P> {
+    /// Attach `policy` to `secret`.
+    pub fn new(secret: SecretKey<E>, policy: P) -> PolicySigner<E,P> {
+        PolicySigner { secret, policy }
+    }
+
+    /// Our policy, like for reading its usage counters.
+    pub fn policy(&self) -> &P { &self.policy }
+
+    /// Detach our policy, returning the secret key and policy.
+    pub fn into_inner(self) -> (SecretKey<E>, P) {
+        (self.secret, self.policy)
+    }
+
+    /// Like `SecretKey::ring_vrf_sign_simple`, once our policy permits.
+    pub fn ring_vrf_sign_simple<Q>(
+        &mut self,
+        input: VRFInput<E>,
+        copath: RingSecretCopath<E>,
+        proving_key: RingSRS<Q>,
+    ) -> SynthesisResult<(VRFInOut<E>, RingVRFProof<E>)>
+    where Q: groth16::ParameterSource<E>,
+    {
+        self.policy.before_sign() ?;
+        self.secret.ring_vrf_sign_simple(input, copath, proving_key)
+    }
+
+    /// Like `SecretKey::ring_vrf_sign_first`, once our policy permits.
+    pub fn ring_vrf_sign_first<T,Q>(
+        &mut self,
+        input: VRFInput<E>,
+        extra: T,
+        copath: RingSecretCopath<E>,
+        proving_key: RingSRS<Q>,
+    ) -> SynthesisResult<(VRFInOut<E>, RingVRFProof<E>)>
+    where T: SigningTranscript,
+          Q: groth16::ParameterSource<E>,
+    {
+        self.policy.before_sign() ?;
+        self.secret.ring_vrf_sign_first(input, extra, copath, proving_key)
+    }
+
+    /// Like `SecretKey::ring_vrf_sign_checked`, once our policy permits.
+    ///
+    /// We consult our policy only here, so callers may evaluate
+    /// `VRFInput::to_inout` freely before deciding to sign.
+    pub fn ring_vrf_sign_checked<T,Q>(
+        &mut self,
+        inout: VRFInOut<E>,
+        extra: T,
+        copath: RingSecretCopath<E>,
+        proving_key: RingSRS<Q>,
+    ) -> SynthesisResult<(VRFPreOut<E>, RingVRFProof<E>)>
+    where T: SigningTranscript,
+          Q: groth16::ParameterSource<E>,
+    {
+        self.policy.before_sign() ?;
+        self.secret.ring_vrf_sign_checked(inout, extra, copath, proving_key)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use pairing::bls12_381::Bls12;
+
+    use super::*;
+    use crate::{signing_context, vrf::no_extra};
+
+    /// Permit `limit` signatures, counting attempts.
+    struct SigningLimit {
+        limit: usize,
+        attempts: usize,
+    }
+
+    impl UsagePolicy for SigningLimit {
+        fn before_sign(&mut self) -> Result<(), PolicyDenied> {
+            self.attempts += 1;
+            if self.attempts > self.limit {
+                return Err(PolicyDenied("Signing limit reached"));
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn policy_denies_after_limit() {
+        let mut rng = ::rand_core::OsRng;
+        let fx = crate::testing::RingFixture::shared();
+        let depth = fx.depth;
+        let sk = SecretKey::<Bls12>::from_rng(&mut rng);
+        let copath = RingSecretCopath::random(depth, &mut rng);
+        let input = |i: u8| VRFInput::<Bls12>::new_malleable(signing_context(b"policy").bytes(&[i]));
+
+        let mut signer = PolicySigner::new(sk, SigningLimit { limit: 2, attempts: 0 });
+        for i in 0..2 {
+            signer.ring_vrf_sign_first(input(i), no_extra(), copath.clone(), fx.srs()).unwrap();
+        }
+
+        // A copath of the wrong depth would fail proving with
+        // InvalidInput, so PermissionDenied shows we never started.
+        let wrong_depth = RingSecretCopath::random(depth + 1, &mut rng);
+        match signer.ring_vrf_sign_simple(input(2), wrong_depth, fx.srs()) {
+            Err(SynthesisError::IoError(e)) => assert_eq!(e.kind(), io::ErrorKind::PermissionDenied),
+            _ => panic!("Policy should deny the third signature"),
+        }
+        assert_eq!(signer.policy().attempts, 3);
+    }
+}
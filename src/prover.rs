@@ -6,7 +6,9 @@
 
 //! ### Ring VRF zk SNARK prover
 
-use bellman::groth16;
+use std::{fmt, io, sync::atomic::{AtomicBool, Ordering}};
+
+use bellman::{groth16, Circuit, ConstraintSystem, SynthesisError};
 pub use groth16::Proof as RingVRFProof;
 
 use rand_core::{RngCore,CryptoRng};
@@ -17,16 +19,112 @@ use crate::{
     RingSRS, SigningTranscript, 
     SecretKey, RingSecretCopath, 
     VRFInput, VRFPreOut, VRFInOut,
-    vrf::{no_extra, VRFExtraMessage},
+    vrf::{no_extra, extra_scalar, VRFExtraMessage},
 };
 
 
+/// Canonical encoding of ring VRF proofs, so mempools may deduplicate
+/// proofs by hashing bytes.
+///
+/// We encode the compressed points of the Groth16 proof, which are
+/// unique since decoding rejects non-canonical field elements, so any
+/// two encodings that decode to equal proofs yield the same bytes here.
+/// `RingVRFProof` already implements `PartialEq` by comparing points.
+///
+/// We cannot implement `Display` for bellman's proof type ourselves, so
+/// `hex` returns a `RingVRFProofHex` adapter instead.
+pub trait RingVRFProofBytes {
+    /// Canonical serialization of this proof.
+    fn canonical_bytes(&self) -> Vec<u8>;
+
+    /// Display this proof as lowercase hex of `canonical_bytes`.
+    fn hex(&self) -> RingVRFProofHex {
+        RingVRFProofHex(self.canonical_bytes())
+    }
+
+    /// Parse the hex produced by `hex`, rejecting invalid points.
+    fn from_hex(s: &str) -> io::Result<Self> where Self: Sized;
+}
+
+impl<E: ::pairing::Engine> RingVRFProofBytes for RingVRFProof<E> {
+    fn canonical_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(192);
+        self.write(&mut bytes).expect("Writing to a Vec never fails.");
+        bytes
+    }
+
+    fn from_hex(s: &str) -> io::Result<Self> {
+        crate::misc::read_hex(s, |r| RingVRFProof::read(r))
+    }
+}
+
+/// Lowercase hex of a proof's canonical serialization, from `RingVRFProofBytes::hex`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RingVRFProofHex(Vec<u8>);
+
+impl fmt::Display for RingVRFProofHex {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        crate::misc::fmt_hex(&self.0, f)
+    }
+}
+
+
+/// Reject VRF inputs from `VRFInput::new_nonmalleable`, whose proofs
+/// could never verify against a ring VRF verifier.
+fn check_ring_compatible<E: JubjubEngineWithParams>(vrf_input: &VRFInput<E>) -> SynthesisResult<()> {
+    if vrf_input.is_nonmalleable() {
+        return Err(SynthesisError::IoError(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Non-malleable VRF inputs commit to a public key, which ring VRFs hide, use VRFInput::new_ring_malleable instead."
+        )));
+    }
+    Ok(())
+}
+
+/// Reject copaths whose depth differs from the proving key's depth,
+/// which otherwise fail inside bellman.
+fn check_depth<E: JubjubEngineWithParams, P>(copath: &RingSecretCopath<E>, proving_key: &RingSRS<P>) -> SynthesisResult<()> {
+    if copath.depth() != proving_key.depth {
+        return Err(SynthesisError::IoError(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Copath depth differs from the proving key depth, see crs_check_depth."
+        )));
+    }
+    Ok(())
+}
+
+/// Return an `io::ErrorKind::Interrupted` error once `cancel` gets set.
+fn check_cancelled(cancel: &AtomicBool) -> SynthesisResult<()> {
+    if cancel.load(Ordering::Acquire) {
+        return Err(SynthesisError::IoError(io::Error::new(
+            io::ErrorKind::Interrupted,
+            "Ring VRF proving cancelled"
+        )));
+    }
+    Ok(())
+}
+
+/// Circuit wrapper that checks a cancellation flag before and after
+/// synthesis, so bellman abandons proving before its FFTs and multiexps.
+struct Cancellable<'a, C> {
+    circuit: C,
+    cancel: &'a AtomicBool,
+}
+
+impl<'a, S: ::ff::PrimeField, C: Circuit<S>> Circuit<S> for Cancellable<'a, C> {
+    fn synthesize<CS: ConstraintSystem<S>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+        check_cancelled(self.cancel) ?;
+        self.circuit.synthesize(cs) ?;
+        check_cancelled(self.cancel)
+    }
+}
+
 impl<E: JubjubEngineWithParams> SecretKey<E> {
     /// Create ring VRF signature using specified randomness source.
     pub fn ring_vrf_prove<T,R,P>(
         &self,
         vrf_input: VRFInput<E>,
-        mut extra: T,
+        extra: T,
         copath: RingSecretCopath<E>,
         proving_key: RingSRS<P>,
         rng: &mut R,
@@ -36,16 +134,314 @@ impl<E: JubjubEngineWithParams> SecretKey<E> {
         P: groth16::ParameterSource<E>, 
         R: RngCore+CryptoRng,
     {
+        #[cfg(debug_assertions)]
+        crate::self_check_once::<E>().map_err(SynthesisError::IoError) ?;
+        check_ring_compatible(&vrf_input) ?;
+        check_depth(&copath, &proving_key) ?;
         let instance = crate::circuit::RingVRF {
             depth: proving_key.depth,
             sk: Some(self.clone()),
             vrf_input: Some(vrf_input.as_point().clone()),
-            extra: Some(extra.challenge_scalar(b"extra-msg")),
+            extra: Some(extra_scalar::<E,_>(extra)),
             copath: Some(copath),
         };
         groth16::create_random_proof(instance, proving_key.srs, rng)
     } 
 
+    /// Create ring VRF signature like `ring_vrf_prove`, but abandon
+    /// proving once another thread sets `cancel`, like upon a timeout.
+    ///
+    /// We check `cancel` before and after circuit synthesis, and again
+    /// once bellman finishes, returning an `io::ErrorKind::Interrupted`
+    /// error whenever set.  We cannot interrupt bellman's FFTs and
+    /// multiexps once started, so cancellation only takes effect between
+    /// these phases, after which we discard any proof.
+    pub fn ring_vrf_prove_cancellable<T,R,P>(
+        &self,
+        vrf_input: VRFInput<E>,
+        extra: T,
+        copath: RingSecretCopath<E>,
+        proving_key: RingSRS<P>,
+        rng: &mut R,
+        cancel: &AtomicBool,
+    ) -> SynthesisResult<RingVRFProof<E>> 
+    where
+        T: SigningTranscript, 
+        P: groth16::ParameterSource<E>, 
+        R: RngCore+CryptoRng,
+    {
+        check_cancelled(cancel) ?;
+        check_ring_compatible(&vrf_input) ?;
+        check_depth(&copath, &proving_key) ?;
+        let circuit = crate::circuit::RingVRF {
+            depth: proving_key.depth,
+            sk: Some(self.clone()),
+            vrf_input: Some(vrf_input.as_point().clone()),
+            extra: Some(extra_scalar::<E,_>(extra)),
+            copath: Some(copath),
+        };
+        let proof = groth16::create_random_proof(Cancellable { circuit, cancel }, proving_key.srs, rng) ?;
+        check_cancelled(cancel) ?;
+        Ok(proof)
+    }
+
+    /// Create ring VRF signature like `ring_vrf_prove`, but using a type
+    /// erased randomness source, like an HSM or TPM backed RNG.
+    ///
+    /// We produce identical proofs to `ring_vrf_prove` given the same
+    /// randomness, while avoiding monomorphizing callers over their RNG.
+    pub fn ring_vrf_prove_dyn<T,P>(
+        &self,
+        vrf_input: VRFInput<E>,
+        extra: T,
+        copath: RingSecretCopath<E>,
+        proving_key: RingSRS<P>,
+        mut rng: &mut dyn crate::CryptoRngCore,
+    ) -> SynthesisResult<RingVRFProof<E>> 
+    where
+        T: SigningTranscript, 
+        P: groth16::ParameterSource<E>, 
+    {
+        self.ring_vrf_prove(vrf_input, extra, copath, proving_key, &mut rng)
+    }
+
+    /// Create ring VRF signature using specified randomness source,
+    /// along with our ring index encrypted to `auditor`.
+    ///
+    /// We require the CRS from `generate_crs_audit`.
+    #[cfg(feature = "audit_tag")]
+    pub fn ring_vrf_prove_with_audit<T,R,P>(
+        &self,
+        vrf_input: VRFInput<E>,
+        extra: T,
+        copath: RingSecretCopath<E>,
+        auditor: &crate::PublicKey<E>,
+        proving_key: RingSRS<P>,
+        rng: &mut R,
+    ) -> SynthesisResult<(RingVRFProof<E>, crate::audit::AuditTag<E>)>
+    where
+        T: SigningTranscript, 
+        P: groth16::ParameterSource<E>, 
+        R: RngCore+CryptoRng,
+    {
+        check_ring_compatible(&vrf_input) ?;
+        check_depth(&copath, &proving_key) ?;
+        let auditor = auditor.0.as_prime_order(E::params()).ok_or_else( || SynthesisError::IoError(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Auditor public key lies outside the prime order subgroup."
+        )) ) ?;
+        let r = <E::Fs as ::ff::Field>::random(rng);
+        let tag = crate::audit::AuditTag::new(copath.index(), &r, &auditor);
+        let instance = crate::circuit::RingVRFAudit {
+            depth: proving_key.depth,
+            sk: Some(self.clone()),
+            vrf_input: Some(vrf_input.as_point().clone()),
+            extra: Some(extra_scalar::<E,_>(extra)),
+            copath: Some(copath),
+            auditor: Some(auditor),
+            audit_randomness: Some(r),
+        };
+        let proof = groth16::create_random_proof(instance, proving_key.srs, rng) ?;
+        Ok((proof, tag))
+    }
+
+    /// Create ring VRF signature using specified randomness source,
+    /// which expires after epoch `expiry`.
+    ///
+    /// We commit `expiry` into the extra message transcript, so the
+    /// circuit's extra message input binds it, and altering `expiry`
+    /// invalidates the proof.  Verify with `RingRoot::ring_vrf_verify_with_expiry`.
+    pub fn ring_vrf_prove_with_expiry<T,R,P>(
+        &self,
+        vrf_input: VRFInput<E>,
+        mut extra: T,
+        expiry: u64,
+        copath: RingSecretCopath<E>,
+        proving_key: RingSRS<P>,
+        rng: &mut R,
+    ) -> SynthesisResult<RingVRFProof<E>> 
+    where
+        T: SigningTranscript, 
+        P: groth16::ParameterSource<E>, 
+        R: RngCore+CryptoRng,
+    {
+        extra.commit_bytes(b"expiry", &expiry.to_le_bytes());
+        self.ring_vrf_prove(vrf_input, extra, copath, proving_key, rng)
+    }
+
+    /// Create ring VRF signature using specified randomness source,
+    /// committing the anti-replay `nonce` into `extra`.
+    ///
+    /// Verify with `RingRoot::ring_vrf_verify_fresh`.
+    pub fn ring_vrf_prove_with_nonce<T,R,P>(
+        &self,
+        vrf_input: VRFInput<E>,
+        mut extra: T,
+        nonce: &[u8],
+        copath: RingSecretCopath<E>,
+        proving_key: RingSRS<P>,
+        rng: &mut R,
+    ) -> SynthesisResult<RingVRFProof<E>> 
+    where
+        T: SigningTranscript, 
+        P: groth16::ParameterSource<E>, 
+        R: RngCore+CryptoRng,
+    {
+        extra.commit_bytes(b"replay-nonce", nonce);
+        self.ring_vrf_prove(vrf_input, extra, copath, proving_key, rng)
+    }
+
+    /// Create ring VRF signature using specified randomness source,
+    /// with our `index` in the ring and an `oracle` supplying the
+    /// sibling at each level, like from `RingSecretCopath::from_oracle`.
+    ///
+    /// We still hold the whole copath during proving, but callers
+    /// control fetching and caching each sibling.
+    pub fn ring_vrf_prove_with_oracle<T,F,R,P>(
+        &self,
+        vrf_input: VRFInput<E>,
+        extra: T,
+        index: usize,
+        oracle: F,
+        proving_key: RingSRS<P>,
+        rng: &mut R,
+    ) -> SynthesisResult<RingVRFProof<E>> 
+    where
+        T: SigningTranscript, 
+        F: FnMut(u32) -> Option<E::Fr>,
+        P: groth16::ParameterSource<E>, 
+        R: RngCore+CryptoRng,
+    {
+        let copath = RingSecretCopath::from_oracle(proving_key.depth, index, oracle);
+        self.ring_vrf_prove(vrf_input, extra, copath, proving_key, rng)
+    }
+
+    /// Prove our key lies in the ring, along with a nullifier unique
+    /// to our key and `epoch`, using specified randomness source.
+    ///
+    /// We evaluate the ring VRF on `VRFInput::new_nullifier(epoch)`, so
+    /// the circuit constrains the nullifier exactly like any VRF output.
+    /// You must send the `VRFPreOut` along with the proof.
+    pub fn prove_membership_with_nullifier<R,P>(
+        &self,
+        copath: RingSecretCopath<E>,
+        epoch: &[u8],
+        proving_key: RingSRS<P>,
+        rng: &mut R,
+    ) -> SynthesisResult<(VRFPreOut<E>, RingVRFProof<E>)> 
+    where
+        P: groth16::ParameterSource<E>, 
+        R: RngCore+CryptoRng,
+    {
+        let input = VRFInput::new_nullifier(epoch);
+        let preout = input.to_preout(self);
+        let proof = self.ring_vrf_prove(input, no_extra(), copath, proving_key, rng) ?;
+        Ok((preout, proof))
+    }
+
+    /// Create ring VRF signature bound to the application `domain`,
+    /// using specified randomness source.
+    ///
+    /// We require a `proving_key` from `generate_crs_domain` for `domain`.
+    pub fn ring_vrf_prove_in_domain<T,R,P>(
+        &self,
+        vrf_input: VRFInput<E>,
+        extra: T,
+        copath: RingSecretCopath<E>,
+        domain: &[u8],
+        proving_key: RingSRS<P>,
+        rng: &mut R,
+    ) -> SynthesisResult<RingVRFProof<E>> 
+    where
+        T: SigningTranscript, 
+        P: groth16::ParameterSource<E>, 
+        R: RngCore+CryptoRng,
+    {
+        check_ring_compatible(&vrf_input) ?;
+        check_depth(&copath, &proving_key) ?;
+        let instance = crate::circuit::RingVRFDomain {
+            ring_vrf: crate::circuit::RingVRF {
+                depth: proving_key.depth,
+                sk: Some(self.clone()),
+                vrf_input: Some(vrf_input.as_point().clone()),
+                extra: Some(extra_scalar::<E,_>(extra)),
+                copath: Some(copath),
+            },
+            domain: crate::circuit::domain_scalar::<E>(domain),
+        };
+        groth16::create_random_proof(instance, proving_key.srs, rng)
+    }
+
+    /// Create ring VRF signature revealing only a commitment to our VRF
+    /// output with `blinding`, using specified randomness source.
+    ///
+    /// We return the commitment along with the proof.  Use a fresh
+    /// random `blinding` for each proof, or else proofs become linkable,
+    /// and reveal `blinding` only to parties who should learn the output.
+    /// We require a `proving_key` from `generate_crs_blinded`.
+    pub fn ring_vrf_prove_blinded<T,R,P>(
+        &self,
+        vrf_input: VRFInput<E>,
+        blinding: &crate::Scalar<E>,
+        extra: T,
+        copath: RingSecretCopath<E>,
+        proving_key: RingSRS<P>,
+        rng: &mut R,
+    ) -> SynthesisResult<(crate::vrf::VRFOutputCommitment<E>, RingVRFProof<E>)>
+    where
+        T: SigningTranscript, 
+        P: groth16::ParameterSource<E>, 
+        R: RngCore+CryptoRng,
+    {
+        check_ring_compatible(&vrf_input) ?;
+        check_depth(&copath, &proving_key) ?;
+        let commitment = crate::vrf::VRFOutputCommitment::new(&vrf_input.to_preout(self), blinding);
+        let instance = crate::circuit::RingVRFBlinded {
+            ring_vrf: crate::circuit::RingVRF {
+                depth: proving_key.depth,
+                sk: Some(self.clone()),
+                vrf_input: Some(vrf_input.as_point().clone()),
+                extra: Some(extra_scalar::<E,_>(extra)),
+                copath: Some(copath),
+            },
+            blinding: Some(blinding.clone()),
+        };
+        let proof = groth16::create_random_proof(instance, proving_key.srs, rng) ?;
+        Ok((commitment, proof))
+    }
+
+    /// Create ring VRF signature proving our key lies in both the old
+    /// and new rings, using specified randomness source.
+    ///
+    /// We require a `proving_key` from `generate_crs_transition`.
+    pub fn ring_vrf_prove_transition<T,R,P>(
+        &self,
+        vrf_input: VRFInput<E>,
+        extra: T,
+        copath_old: RingSecretCopath<E>,
+        copath_new: RingSecretCopath<E>,
+        proving_key: RingSRS<P>,
+        rng: &mut R,
+    ) -> SynthesisResult<RingVRFProof<E>> 
+    where
+        T: SigningTranscript, 
+        P: groth16::ParameterSource<E>, 
+        R: RngCore+CryptoRng,
+    {
+        check_ring_compatible(&vrf_input) ?;
+        check_depth(&copath_old, &proving_key) ?;
+        check_depth(&copath_new, &proving_key) ?;
+        let instance = crate::circuit::RingVRFTransition {
+            depth: proving_key.depth,
+            sk: Some(self.clone()),
+            vrf_input: Some(vrf_input.as_point().clone()),
+            extra: Some(extra_scalar::<E,_>(extra)),
+            copath_old: Some(copath_old),
+            copath_new: Some(copath_new),
+        };
+        groth16::create_random_proof(instance, proving_key.srs, rng)
+    }
+
 
 
     /// Run our Schnorr VRF on one single input, producing the output
@@ -86,6 +482,39 @@ impl<E: JubjubEngineWithParams> SecretKey<E> {
         Ok((inout, proof))
     }
 
+    /// Run our VRF on one single input and an extra message transcript
+    /// like `ring_vrf_sign_first`, but prove on a rayon thread while
+    /// computing the output on the caller's thread.
+    ///
+    /// We produce identical results to `to_inout` followed by
+    /// `ring_vrf_prove` given the same randomness.  Proving dominates,
+    /// so this helps pipelines more than single signatures.
+    #[cfg(feature = "parallel")]
+    pub fn sign_and_prove_parallel<T,R,P>(
+        &self,
+        input: VRFInput<E>,
+        extra: T,
+        copath: RingSecretCopath<E>,
+        proving_key: RingSRS<P>,
+        rng: &mut R,
+    ) -> SynthesisResult<(VRFInOut<E>, RingVRFProof<E>)>
+    where
+        T: SigningTranscript+Send,
+        P: groth16::ParameterSource<E>+Send,
+        R: RngCore+CryptoRng+Send,
+        SecretKey<E>: Sync,
+        VRFInput<E>: Send+Sync,
+        RingSecretCopath<E>: Send,
+        VRFInOut<E>: Send,
+        RingVRFProof<E>: Send,
+    {
+        let (proof, inout) = ::rayon::join(
+            || self.ring_vrf_prove(input.clone(), extra, copath, proving_key, rng),
+            || input.to_inout(self),
+        );
+        Ok((inout, proof ?))
+    }
+
     /// Run our Schnorr VRF on one single input, producing the output
     /// and correspodning Schnorr proof, but only if the result first
     /// passes some check, which itself returns either a `bool` or else
@@ -119,7 +548,7 @@ impl<E: JubjubEngineWithParams> SecretKey<E> {
     where T: SigningTranscript,
           P: groth16::ParameterSource<E>, 
     {
-        let VRFInOut { input, output } = inout;
+        let (input, output) = inout.split();
         let proof = self.ring_vrf_prove(input, extra, copath, proving_key, &mut rand_hack()) ?;
         Ok((output, proof))
     }
@@ -127,3 +556,258 @@ impl<E: JubjubEngineWithParams> SecretKey<E> {
     // TODO: VRFs methods
 }
 
+
+#[cfg(test)]
+mod tests {
+    use pairing::bls12_381::Bls12;
+
+    use super::*;
+    use crate::{testing, signing_context, CryptoRngCore, Scalar, VRFOutputCommitment};
+
+    #[test]
+    fn test_nonmalleable_rejected() {
+        let mut rng = ::rand_core::OsRng;
+        let fx = testing::RingFixture::shared();
+        let (sk, copath, _) = fx.signer();
+
+        let t = signing_context(b"Hello World!").bytes(b"meow");
+        let vrf_input = VRFInput::<Bls12>::new_nonmalleable(t, &sk.to_public());
+        assert!(vrf_input.is_nonmalleable());
+        match sk.ring_vrf_prove(vrf_input, no_extra(), copath, fx.srs(), &mut rng) {
+            Err(bellman::SynthesisError::IoError(e)) => assert!(e.to_string().contains("new_ring_malleable")),
+            _ => panic!("ring VRF prover accepted a non-malleable input"),
+        }
+    }
+
+    #[test]
+    fn test_nullifier() {
+        let mut rng = ::rand_core::OsRng;
+        let fx = testing::RingFixture::shared();
+        let srs = fx.srs();
+
+        let (sk, copath, auth_root) = fx.signer();
+
+        let (preout1, proof1) = sk.prove_membership_with_nullifier(copath.clone(), b"epoch 1", srs, &mut rng).unwrap();
+        let (preout2, proof2) = sk.prove_membership_with_nullifier(copath.clone(), b"epoch 1", srs, &mut rng).unwrap();
+        let (preout3, proof3) = sk.prove_membership_with_nullifier(copath, b"epoch 2", srs, &mut rng).unwrap();
+
+        let n1 = auth_root.verify_membership_with_nullifier(b"epoch 1", &preout1, proof1.clone(), &fx.pvk).unwrap().unwrap();
+        let n2 = auth_root.verify_membership_with_nullifier(b"epoch 1", &preout2, proof2, &fx.pvk).unwrap().unwrap();
+        let n3 = auth_root.verify_membership_with_nullifier(b"epoch 2", &preout3, proof3, &fx.pvk).unwrap().unwrap();
+        assert_eq!(n1, n2);
+        assert!(n1 != n3);
+
+        // Neither a forged nullifier nor a proof replayed in another epoch verifies.
+        assert_eq!(auth_root.verify_membership_with_nullifier(b"epoch 1", &preout3, proof1.clone(), &fx.pvk).unwrap(), None);
+        assert_eq!(auth_root.verify_membership_with_nullifier(b"epoch 2", &preout1, proof1, &fx.pvk).unwrap(), None);
+    }
+
+    #[test]
+    fn test_prove_dyn_rng() {
+        use rand_core::SeedableRng;
+
+        let fx = testing::RingFixture::shared();
+        let srs = fx.srs();
+
+        let (sk, copath, _) = fx.signer();
+        let input = || VRFInput::<Bls12>::new_malleable(signing_context(b"dyn").bytes(b"meow"));
+
+        let mut boxed: Box<dyn CryptoRngCore> = Box::new(::rand_chacha::ChaChaRng::from_seed([7u8; 32]));
+        let proof_dyn = sk.ring_vrf_prove_dyn(input(), no_extra(), copath.clone(), srs, boxed.as_mut()).unwrap();
+        let mut generic = ::rand_chacha::ChaChaRng::from_seed([7u8; 32]);
+        let proof = sk.ring_vrf_prove(input(), no_extra(), copath.clone(), srs, &mut generic).unwrap();
+
+        let (mut b0, mut b1) = (Vec::new(), Vec::new());
+        proof_dyn.write(&mut b0).unwrap();
+        proof.write(&mut b1).unwrap();
+        assert_eq!(b0, b1);
+
+        let auth_root = copath.to_root(&sk.to_public());
+        let vrf_inout = input().to_inout(&sk);
+        assert!(auth_root.ring_vrf_verify_unprepared(vrf_inout, no_extra(), proof_dyn, &srs.srs.vk).unwrap());
+    }
+
+    #[test]
+    fn test_proof_canonical_bytes() {
+        let mut rng = ::rand_core::OsRng;
+        let fx = testing::RingFixture::shared();
+        let srs = fx.srs();
+
+        let (sk, copath, _) = fx.signer();
+        let input = || VRFInput::<Bls12>::new_malleable(signing_context(b"canonical").bytes(b"meow"));
+
+        let proof = sk.ring_vrf_prove(input(), no_extra(), copath.clone(), srs, &mut rng).unwrap();
+        let bytes = proof.canonical_bytes();
+        let reread = RingVRFProof::<Bls12>::read(&bytes[..]).unwrap();
+        assert!(reread == proof);
+        assert_eq!(reread.canonical_bytes(), bytes);
+
+        // Groth16 proofs are randomized, so proving again yields another proof.
+        let other = sk.ring_vrf_prove(input(), no_extra(), copath, srs, &mut rng).unwrap();
+        assert!(other != proof);
+        assert_ne!(other.canonical_bytes(), bytes);
+
+        let s = proof.hex().to_string();
+        assert_eq!(s.len(), 2 * bytes.len());
+        assert!(RingVRFProof::<Bls12>::from_hex(&s).unwrap() == proof);
+        assert!(RingVRFProof::<Bls12>::from_hex(&s.to_uppercase()).unwrap() == proof);
+        assert!(RingVRFProof::<Bls12>::from_hex(&s[1..]).is_err());
+        assert!(RingVRFProof::<Bls12>::from_hex(&s[2..]).is_err());
+        assert!(RingVRFProof::<Bls12>::from_hex(&format!("{}00", s)).is_err());
+        assert!(RingVRFProof::<Bls12>::from_hex(&s.replacen(&s[..2], "zz", 1)).is_err());
+        assert!(RingVRFProof::<Bls12>::from_hex(&"ff".repeat(bytes.len())).is_err());
+    }
+
+    #[test]
+    fn test_expiry() {
+        let mut rng = ::rand_core::OsRng;
+        let fx = testing::RingFixture::shared();
+        let srs = fx.srs();
+
+        let (sk, copath, auth_root) = fx.signer();
+        let input = || VRFInput::<Bls12>::new_malleable(signing_context(b"expiry").bytes(b"meow"));
+
+        let proof = sk.ring_vrf_prove_with_expiry(input(), no_extra(), 100, copath, srs, &mut rng).unwrap();
+        let verify = |expiry, current_epoch| auth_root.ring_vrf_verify_with_expiry(
+            input().to_inout(&sk), no_extra(), expiry, current_epoch, proof.clone(), &fx.pvk
+        );
+        assert!(verify(100, 99).unwrap());
+        assert!(verify(100, 100).unwrap());
+        assert!(verify(100, 101).is_err());
+        // Extending the expiry invalidates the proof.
+        assert!(! verify(200, 101).unwrap());
+        // So does omitting the expiry.
+        assert!(! auth_root.ring_vrf_verify(input().to_inout(&sk), no_extra(), proof, &fx.pvk).unwrap());
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_sign_and_prove_parallel() {
+        use rand_core::SeedableRng;
+
+        let fx = testing::RingFixture::shared();
+        let srs = fx.srs();
+
+        let (sk, copath, _) = fx.signer();
+        let input = || VRFInput::<Bls12>::new_malleable(signing_context(b"parallel").bytes(b"meow"));
+
+        let mut rng0 = ::rand_chacha::ChaChaRng::from_seed([9u8; 32]);
+        let (inout, proof) = sk.sign_and_prove_parallel(input(), no_extra(), copath.clone(), srs, &mut rng0).unwrap();
+        let mut rng1 = ::rand_chacha::ChaChaRng::from_seed([9u8; 32]);
+        let expected = sk.ring_vrf_prove(input(), no_extra(), copath, srs, &mut rng1).unwrap();
+        assert!(proof == expected);
+        assert!(inout.output.as_point() == input().to_inout(&sk).output.as_point());
+    }
+
+    #[test]
+    fn test_prove_with_oracle() {
+        let mut rng = ::rand_core::OsRng;
+        let fx = testing::RingFixture::shared();
+        let (sks, ring) = testing::deterministic_ring::<Bls12>(3, fx.depth);
+
+        // We stand in for external storage with the builder's copath.
+        let (stored, _) = ring.copath(2);
+        let oracle = |level: u32| stored.0[level as usize].sibling.clone();
+        let input = || VRFInput::<Bls12>::new_malleable(signing_context(b"oracle").bytes(b"meow"));
+        let proof = sks[2].ring_vrf_prove_with_oracle(input(), no_extra(), 2, oracle, fx.srs(), &mut rng).unwrap();
+        assert!(ring.root().ring_vrf_verify(input().to_inout(&sks[2]), no_extra(), proof.clone(), &fx.pvk).unwrap());
+        assert!(! ring.root().ring_vrf_verify(input().to_inout(&sks[1]), no_extra(), proof, &fx.pvk).unwrap());
+    }
+
+    #[test]
+    fn test_domain_separation() {
+        let mut rng = ::rand_core::OsRng;
+        let depth = 2;
+        let srs_a = crate::generate_crs_domain::<Bls12>(depth, b"app A").expect("can't generate SRS");
+        let srs_b = crate::generate_crs_domain::<Bls12>(depth, b"app B").expect("can't generate SRS");
+        let fx = testing::RingFixture::with_params(srs_a, depth);
+        let pvk_b = groth16::prepare_verifying_key(&srs_b.vk);
+
+        let (sk, copath, auth_root) = fx.signer();
+        let input = VRFInput::<Bls12>::new_malleable(signing_context(b"domain").bytes(b"meow"));
+        let inout = input.to_inout(&sk);
+        let proof = sk.ring_vrf_prove_in_domain(input, no_extra(), copath, b"app A", fx.srs(), &mut rng).unwrap();
+
+        assert!(auth_root.ring_vrf_verify_in_domain(inout.clone(), no_extra(), b"app A", proof.clone(), &fx.pvk).unwrap());
+        assert!(! auth_root.ring_vrf_verify_in_domain(inout.clone(), no_extra(), b"app B", proof.clone(), &fx.pvk).unwrap());
+        assert!(! auth_root.ring_vrf_verify_in_domain(inout, no_extra(), b"app B", proof, &pvk_b).unwrap());
+    }
+
+    #[test]
+    fn test_transition_proof() {
+        let mut rng = ::rand_core::OsRng;
+        let depth = 2;
+        let srs = crate::generate_crs_transition::<Bls12>(depth).expect("can't generate SRS");
+        let fx = testing::RingFixture::with_params(srs, depth);
+
+        let (sk, copath_old, root_old) = fx.signer();
+        let copath_new = RingSecretCopath::random(depth, &mut rng);
+        let root_new = copath_new.to_root(&sk.to_public());
+        let input = || VRFInput::<Bls12>::new_malleable(signing_context(b"transition").bytes(b"meow"));
+        let proof = sk.ring_vrf_prove_transition(input(), no_extra(), copath_old.clone(), copath_new, fx.srs(), &mut rng).unwrap();
+        assert!(root_old.ring_vrf_verify_transition(&root_new, input().to_inout(&sk), no_extra(), proof, &fx.pvk).unwrap());
+
+        // Our key lies only in the old ring, not the ring `copath_other`
+        // builds around `other`, so no proof verifies against its root.
+        let other = SecretKey::<Bls12>::from_rng(&mut rng).to_public();
+        let copath_other = RingSecretCopath::random(depth, &mut rng);
+        let root_other = copath_other.to_root(&other);
+        let proof = sk.ring_vrf_prove_transition(input(), no_extra(), copath_old, copath_other, fx.srs(), &mut rng).unwrap();
+        assert!(! root_old.ring_vrf_verify_transition(&root_other, input().to_inout(&sk), no_extra(), proof, &fx.pvk).unwrap());
+    }
+
+    #[test]
+    fn test_blinded_outputs() {
+        use ff::Field;
+
+        let mut rng = ::rand_core::OsRng;
+        let depth = 2;
+        let srs = crate::generate_crs_blinded::<Bls12>(depth).expect("can't generate SRS");
+        let fx = testing::RingFixture::with_params(srs, depth);
+
+        let (sk, copath, auth_root) = fx.signer();
+        let input = || VRFInput::<Bls12>::new_malleable(signing_context(b"blinded").bytes(b"meow"));
+        let expected = input().to_preout(&sk);
+
+        let b1 = <Scalar<Bls12> as Field>::random(&mut rng);
+        let b2 = <Scalar<Bls12> as Field>::random(&mut rng);
+        let (c1, proof1) = sk.ring_vrf_prove_blinded(input(), &b1, no_extra(), copath.clone(), fx.srs(), &mut rng).unwrap();
+        let (c2, proof2) = sk.ring_vrf_prove_blinded(input(), &b2, no_extra(), copath, fx.srs(), &mut rng).unwrap();
+
+        // Both verify, yet their commitments differ.
+        assert!(auth_root.ring_vrf_verify_blinded(&input(), &c1, no_extra(), proof1.clone(), &fx.pvk).unwrap());
+        assert!(auth_root.ring_vrf_verify_blinded(&input(), &c2, no_extra(), proof2, &fx.pvk).unwrap());
+        assert!(c1.as_point() != c2.as_point());
+        assert!(c1.as_point() != expected.as_point());
+
+        // Blinding holders recover the true output.
+        assert!(c1.opens_to(&expected, &b1));
+        assert!(! c1.opens_to(&expected, &b2));
+        assert!(c1.open(&b1).as_point() == expected.as_point());
+        assert!(c2.open(&b2).as_point() == expected.as_point());
+
+        // Commitments to other outputs fail.
+        let forged = VRFOutputCommitment::new(&input().to_preout(&SecretKey::<Bls12>::from_rng(&mut rng)), &b1);
+        assert!(! auth_root.ring_vrf_verify_blinded(&input(), &forged, no_extra(), proof1, &fx.pvk).unwrap());
+    }
+
+    #[test]
+    fn test_prove_cancellable() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        let mut rng = ::rand_core::OsRng;
+        let fx = testing::RingFixture::shared();
+        let (sk, copath, auth_root) = fx.signer();
+        let input = || VRFInput::<Bls12>::new_malleable(signing_context(b"cancel").bytes(b"meow"));
+
+        let cancel = AtomicBool::new(false);
+        let proof = sk.ring_vrf_prove_cancellable(input(), no_extra(), copath.clone(), fx.srs(), &mut rng, &cancel).unwrap();
+        assert!(auth_root.ring_vrf_verify(input().to_inout(&sk), no_extra(), proof, &fx.pvk).unwrap());
+
+        cancel.store(true, Ordering::Release);
+        match sk.ring_vrf_prove_cancellable(input(), no_extra(), copath, fx.srs(), &mut rng, &cancel) {
+            Err(bellman::SynthesisError::IoError(e)) => assert_eq!(e.kind(), ::std::io::ErrorKind::Interrupted),
+            _ => panic!("Cancelled proving should fail"),
+        }
+    }
+}
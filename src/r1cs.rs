@@ -0,0 +1,256 @@
+// Copyright (c) 2026 The ring-vrf contributors
+//
+// Licensed under the MIT license, see LICENSE.
+
+//! ### Export the ring VRF circuit's R1CS for external tooling
+//!
+//! We write the circom `.r1cs` binary format, version 1, so auditors
+//! and alternative implementations can inspect our constraints with
+//! existing tools.  We never use this when proving or verifying.
+//!
+//! Wire 0 is the constant one, followed by our public inputs in the
+//! order of `public_input_schema`, and then all private wires.  We
+//! declare no public outputs and no private inputs, as bellman does
+//! not distinguish private inputs from other witnesses.
+//! All integers and field elements are little endian, with field
+//! elements in canonical, not Montgomery, form.
+
+use std::{any::TypeId, collections::HashMap, io, sync::Mutex};
+
+use ff::{Field, PrimeField};
+use bellman::{Circuit, ConstraintSystem, Index, LinearCombination, SynthesisError, Variable};
+
+use crate::JubjubEngineWithParams;
+
+
+type Factors = Vec<(u32, Vec<u8>)>;
+
+/// Constraint system recording every constraint, but no assignments.
+///
+/// We only count wires when `record` is unset, like when checking a
+/// CRS' depth, which avoids allocating every linear combination.
+struct R1csCollector {
+    num_inputs: usize,
+    num_aux: usize,
+    record: bool,
+    constraints: Vec<[Factors; 3]>,
+}
+
+impl R1csCollector {
+    fn wire(&self, v: &Variable) -> u32 {
+        match v.get_unchecked() {
+            Index::Input(i) => i as u32,
+            // We fix the wire numbering only after synthesis, so we
+            // store auxiliary wires by negated index for now.
+            Index::Aux(i) => !(i as u32),
+        }
+    }
+
+    fn factors<F: PrimeField>(&self, lc: LinearCombination<F>) -> Factors {
+        lc.as_ref().iter().map( |(v,c)| (self.wire(v), c.to_repr().as_ref().to_vec()) ).collect()
+    }
+}
+
+impl<F: PrimeField> ConstraintSystem<F> for R1csCollector {
+    type Root = Self;
+
+    fn alloc<V, A, AR>(&mut self, _annotation: A, _f: V) -> Result<Variable, SynthesisError>
+    where V: FnOnce() -> Result<F, SynthesisError>, A: FnOnce() -> AR, AR: Into<String>,
+    {
+        self.num_aux += 1;
+        Ok(Variable::new_unchecked(Index::Aux(self.num_aux - 1)))
+    }
+
+    fn alloc_input<V, A, AR>(&mut self, _annotation: A, _f: V) -> Result<Variable, SynthesisError>
+    where V: FnOnce() -> Result<F, SynthesisError>, A: FnOnce() -> AR, AR: Into<String>,
+    {
+        self.num_inputs += 1;
+        Ok(Variable::new_unchecked(Index::Input(self.num_inputs - 1)))
+    }
+
+    fn enforce<A, AR, LA, LB, LC>(&mut self, _annotation: A, a: LA, b: LB, c: LC)
+    where
+        A: FnOnce() -> AR, AR: Into<String>,
+        LA: FnOnce(LinearCombination<F>) -> LinearCombination<F>,
+        LB: FnOnce(LinearCombination<F>) -> LinearCombination<F>,
+        LC: FnOnce(LinearCombination<F>) -> LinearCombination<F>,
+    {
+        if ! self.record {  return;  }
+        let a = self.factors(a(LinearCombination::zero()));
+        let b = self.factors(b(LinearCombination::zero()));
+        let c = self.factors(c(LinearCombination::zero()));
+        self.constraints.push([a, b, c]);
+    }
+
+    fn push_namespace<NR, N>(&mut self, _name_fn: N) where NR: Into<String>, N: FnOnce() -> NR {}
+
+    fn pop_namespace(&mut self) {}
+
+    fn get_root(&mut self) -> &mut Self::Root {  self  }
+}
+
+/// Field modulus as little endian bytes, computed as `-1` plus one.
+fn modulus_bytes<F: PrimeField>() -> Vec<u8> {
+    let mut bytes = (-F::one()).to_repr().as_ref().to_vec();
+    for b in bytes.iter_mut() {
+        let (sum, carry) = b.overflowing_add(1);
+        *b = sum;
+        if ! carry {  break;  }
+    }
+    bytes
+}
+
+fn write_section<W: io::Write>(mut writer: W, section_type: u32, content: &[u8]) -> io::Result<()> {
+    writer.write_all(&section_type.to_le_bytes()) ?;
+    writer.write_all(&(content.len() as u64).to_le_bytes()) ?;
+    writer.write_all(content)
+}
+
+lazy_static! {
+    /// Auxiliary wire counts by engine and depth, since counting
+    /// synthesizes the whole circuit.
+    static ref NUM_AUX: Mutex<HashMap<(TypeId, u32), usize>> = Mutex::new(HashMap::new());
+}
+
+/// Number of auxiliary wires in the `RingVRF` circuit with the given
+/// Merkle tree `depth`, which fixes the length of the CRS' `l` query.
+///
+/// We synthesize the circuit only once per engine and depth.
+pub(crate) fn ring_vrf_num_aux<E: JubjubEngineWithParams>(depth: u32) -> Result<usize, SynthesisError> {
+    let key = (TypeId::of::<E>(), depth);
+    if let Some(num_aux) = NUM_AUX.lock().unwrap_or_else( |e| e.into_inner() ).get(&key) {
+        return Ok(*num_aux);
+    }
+    let circuit = crate::circuit::RingVRF::<E> {
+        depth,
+        sk: None,
+        vrf_input: None,
+        extra: None,
+        copath: None,
+    };
+    let mut cs = R1csCollector { num_inputs: 1, num_aux: 0, record: false, constraints: Vec::new() };
+    circuit.synthesize(&mut cs) ?;
+    NUM_AUX.lock().unwrap_or_else( |e| e.into_inner() ).insert(key, cs.num_aux);
+    Ok(cs.num_aux)
+}
+
+/// Write the R1CS of the `RingVRF` circuit with the given Merkle tree
+/// `depth` to `writer` in the circom `.r1cs` binary format.
+///
+/// We return the number of constraints written.
+pub fn export_r1cs<E, W>(depth: u32, mut writer: W) -> io::Result<usize>
+where E: JubjubEngineWithParams, W: io::Write,
+{
+    let circuit = crate::circuit::RingVRF::<E> {
+        depth,
+        sk: None,
+        vrf_input: None,
+        extra: None,
+        copath: None,
+    };
+    // The constant one occupies the first input, exactly like in bellman.
+    let mut cs = R1csCollector { num_inputs: 1, num_aux: 0, record: true, constraints: Vec::new() };
+    circuit.synthesize(&mut cs).map_err( |e| io::Error::new(io::ErrorKind::Other, e) ) ?;
+
+    let num_inputs = cs.num_inputs as u32;
+    let num_wires = num_inputs + cs.num_aux as u32;
+    let modulus = modulus_bytes::<E::Fr>();
+    let to_wire = |w: u32| if w < num_inputs {  w  } else {  num_inputs + !w  };
+
+    let mut header = Vec::new();
+    header.extend_from_slice(&(modulus.len() as u32).to_le_bytes());
+    header.extend_from_slice(&modulus);
+    header.extend_from_slice(&num_wires.to_le_bytes());
+    header.extend_from_slice(&0u32.to_le_bytes()); // public outputs
+    header.extend_from_slice(&(num_inputs - 1).to_le_bytes()); // public inputs
+    header.extend_from_slice(&0u32.to_le_bytes()); // private inputs
+    header.extend_from_slice(&(num_wires as u64).to_le_bytes()); // labels
+    header.extend_from_slice(&(cs.constraints.len() as u32).to_le_bytes());
+
+    let mut constraints = Vec::new();
+    for lcs in cs.constraints.iter() {
+        for lc in lcs.iter() {
+            constraints.extend_from_slice(&(lc.len() as u32).to_le_bytes());
+            for (w,c) in lc.iter() {
+                constraints.extend_from_slice(&to_wire(*w).to_le_bytes());
+                constraints.extend_from_slice(c);
+            }
+        }
+    }
+
+    let labels = (0..num_wires as u64).flat_map( |l| l.to_le_bytes().to_vec() ).collect::<Vec<u8>>();
+
+    writer.write_all(b"r1cs") ?;
+    writer.write_all(&1u32.to_le_bytes()) ?;
+    writer.write_all(&3u32.to_le_bytes()) ?;
+    write_section(&mut writer, 1, &header) ?;
+    write_section(&mut writer, 2, &constraints) ?;
+    write_section(&mut writer, 3, &labels) ?;
+    Ok(cs.constraints.len())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryInto;
+
+    use bellman::gadgets::test::TestConstraintSystem;
+    use pairing::bls12_381::{Bls12, Fr};
+
+    use super::*;
+    use crate::{RingSecretCopath, SecretKey, VRFInput};
+
+    fn u32_at(bytes: &[u8], i: usize) -> u32 {  u32::from_le_bytes(bytes[i..i+4].try_into().unwrap())  }
+
+    #[test]
+    fn export_matches_circuit() {
+        let depth = 2;
+        let mut bytes = Vec::new();
+        let count = export_r1cs::<Bls12,_>(depth, &mut bytes).unwrap();
+
+        let mut rng = ::rand_core::OsRng;
+        let vrf_input = VRFInput::<Bls12>::new_malleable(crate::signing_context(b"r1cs").bytes(b"meow"));
+        let instance = crate::circuit::RingVRF {
+            depth,
+            sk: Some(SecretKey::<Bls12>::from_rng(&mut rng)),
+            vrf_input: Some(vrf_input.as_point().clone()),
+            extra: Some(Fr::one()),
+            copath: Some(RingSecretCopath::random(depth, &mut rng)),
+        };
+        let mut cs = TestConstraintSystem::<Fr>::new();
+        instance.synthesize(&mut cs).unwrap();
+        assert_eq!(count, cs.num_constraints());
+
+        // Parse the file back, walking every constraint.
+        assert_eq!(&bytes[..4], b"r1cs");
+        assert_eq!(u32_at(&bytes, 4), 1);
+        assert_eq!(u32_at(&bytes, 8), 3);
+        assert_eq!(u32_at(&bytes, 12), 1);
+        let header = &bytes[24..];
+        let n8 = u32_at(header, 0) as usize;
+        assert_eq!(n8, 32);
+        assert_eq!(&header[4..36], &modulus_bytes::<Fr>()[..]);
+        let num_wires = u32_at(header, 36);
+        assert_eq!(u32_at(header, 44) as usize, crate::public_input_schema().iter().map( |f| f.width ).sum::<usize>());
+        assert_eq!(u32_at(header, 60) as usize, count);
+
+        let mut i = 24 + 64;
+        assert_eq!(u32_at(&bytes, i), 2);
+        let end = i + 12 + u64::from_le_bytes(bytes[i+4..i+12].try_into().unwrap()) as usize;
+        i += 12;
+        for _ in 0..3 * count {
+            let factors = u32_at(&bytes, i);
+            i += 4;
+            for _ in 0..factors {
+                assert!(u32_at(&bytes, i) < num_wires);
+                let mut repr = <Fr as PrimeField>::Repr::default();
+                repr.as_mut().copy_from_slice(&bytes[i+4..i+36]);
+                assert!(Fr::from_repr(repr).is_some());
+                i += 4 + n8;
+            }
+        }
+        assert_eq!(i, end);
+        assert_eq!(u32_at(&bytes, i), 3);
+        assert_eq!(bytes.len(), i + 12 + 8 * num_wires as usize);
+    }
+}
@@ -574,6 +574,94 @@ impl<E: JubjubEngineWithParams> SecretKey<E>  {
 }
 
 
+/// Proof that two VRF outputs share one secret key, without revealing
+/// either the secret key or the public key.
+///
+/// We prove equality of discrete logarithms for `h1^sk` and `h2^sk`
+/// with respect to the VRF inputs `h1` and `h2`, like `dleq_proove`
+/// but omitting the basepoint and public key.  We envision this for
+/// detecting double voting by anonymous ring VRF signers.
+#[derive(Debug, Clone)] // PartialEq, Eq // PartialOrd, Ord, Hash
+pub struct LinkProof<E: JubjubEngine> {
+    /// Challenge
+    c: Scalar<E>,
+    /// Schnorr proof
+    s: Scalar<E>,
+}
+
+impl<E: JubjubEngineWithParams> ReadWrite for LinkProof<E>  {
+    fn read<R: io::Read>(mut reader: R) -> io::Result<Self> {
+        let c = crate::read_scalar::<E, &mut R>(&mut reader) ?;
+        let s = crate::read_scalar::<E, &mut R>(&mut reader) ?;
+        Ok(LinkProof { c, s })
+    }
+    fn write<W: io::Write>(&self, mut writer: W) -> io::Result<()> {
+        crate::write_scalar::<E, &mut W>(&self.c, &mut writer) ?;
+        crate::write_scalar::<E, &mut W>(&self.s, &mut writer) ?;
+        Ok(())
+    }
+}
+
+#[allow(non_snake_case)]
+fn link_transcript<E,T>(t: &mut T, io1: &VRFInOut<E>, io2: &VRFInOut<E>, R1: &Point<E,Unknown>, R2: &Point<E,Unknown>)
+ -> Scalar<E>
+where E: JubjubEngineWithParams, T: SigningTranscript,
+{
+    t.proto_name(b"LinkProof");
+    t.commit_point(b"vrf:h1", io1.input.as_point());
+    t.commit_point(b"vrf:h2", io2.input.as_point());
+    t.commit_point(b"vrf:h1^r", R1);
+    t.commit_point(b"vrf:h2^r", R2);
+    t.commit_point(b"vrf:h1^sk", io1.output.as_point());
+    t.commit_point(b"vrf:h2^sk", io2.output.as_point());
+    t.challenge_scalar(b"prove")
+}
+
+impl<E: JubjubEngineWithParams> SecretKey<E>  {
+    /// Prove that the outputs in `io1` and `io2` were produced by this
+    /// same secret key, without revealing it.
+    ///
+    /// We assume both `VRFInOut`s were computed correctly by `to_inout`.
+    #[allow(non_snake_case)]
+    pub fn prove_same_signer<T>(&self, mut t: T, io1: &VRFInOut<E>, io2: &VRFInOut<E>) -> LinkProof<E>
+    where T: SigningTranscript,
+    {
+        let params = E::params();
+        let [r] : [Scalar<E>;1] = t.witness_scalars(b"linking\00",&[&self.nonce_seed], rand_hack());
+        let R1: Point<E,Unknown> = io1.input.as_point().mul(r.clone(), params).into();
+        let R2: Point<E,Unknown> = io2.input.as_point().mul(r.clone(), params).into();
+        let c = link_transcript(&mut t, io1, io2, &R1, &R2);
+        // let s = &r - &(&c * &self.key);
+        let mut s = r;
+        let mut tmp = self.key.clone();
+        tmp.mul_assign(&c);
+        s.sub_assign(&tmp);
+        LinkProof { c, s }
+    }
+}
+
+impl<E: JubjubEngineWithParams> LinkProof<E> {
+    /// Verify that the outputs in `io1` and `io2` share one secret key.
+    #[allow(non_snake_case)]
+    pub fn verify_same_signer<T>(&self, mut t: T, io1: &VRFInOut<E>, io2: &VRFInOut<E>) -> SignatureResult<()>
+    where T: SigningTranscript,
+    {
+        let params = E::params();
+        let LinkProof { c, s } = self.clone();
+        // We recompute h^r from the proof as in `dleq_verify`
+        let R1 = io1.output.as_point().mul(c.clone(),params)
+            .add(& io1.input.as_point().mul(s.clone(),params).into(), params);
+        let R2 = io2.output.as_point().mul(c.clone(),params)
+            .add(& io2.input.as_point().mul(s,params).into(), params);
+        if c == link_transcript(&mut t, io1, io2, &R1, &R2) {
+            Ok(())
+        } else {
+            Err( signature_error("VRF linking proof validation failed") )
+        }
+    }
+}
+
+
 impl<E,PD> VRFProof<E,VRFInOut<E>,Individual<E>,PD> 
 where E: JubjubEngineWithParams, PD: PedersenDeltaOrPublicKey<E>+Clone,
 {
@@ -873,6 +961,40 @@ mod tests {
         );
     }
 
+    #[test]
+    fn vrf_same_signer() {
+        let mut csprng = ::rand_core::OsRng;
+        let sk1 = SecretKey::<Bls12>::from_rng(&mut csprng);
+        let sk2 = SecretKey::<Bls12>::from_rng(&mut csprng);
+
+        let ctx = signing_context(b"vote");
+        let input1 = VRFInput::<Bls12>::new_malleable(ctx.bytes(b"ballot 1"));
+        let input2 = VRFInput::<Bls12>::new_malleable(ctx.bytes(b"ballot 2"));
+        let io11 = input1.to_inout(&sk1);
+        let io12 = input2.to_inout(&sk1);
+        let io22 = input2.to_inout(&sk2);
+
+        let proof = sk1.prove_same_signer(vrf::no_extra(), &io11, &io12);
+        assert!( proof.verify_same_signer(vrf::no_extra(), &io11, &io12).is_ok() );
+        assert!( proof.verify_same_signer(signing_context(b"other").bytes(b""), &io11, &io12).is_err() );
+        assert!( proof.verify_same_signer(vrf::no_extra(), &io11, &io22).is_err() );
+
+        let forged = sk1.prove_same_signer(vrf::no_extra(), &io11, &io22);
+        assert!( forged.verify_same_signer(vrf::no_extra(), &io11, &io22).is_err() );
+
+        // Proofs are randomized, and their serialization contains
+        // neither the public key nor anything fixed by the secret key.
+        let again = sk1.prove_same_signer(vrf::no_extra(), &io11, &io12);
+        let (mut b0, mut b1) = (Vec::new(), Vec::new());
+        proof.write(&mut b0).unwrap();
+        again.write(&mut b1).unwrap();
+        assert_eq!(b0.len(), 64);
+        assert_ne!(b0, b1);
+        let mut pk = Vec::new();
+        sk1.to_public().write(&mut pk).unwrap();
+        assert!( b0.windows(32).all(|w| w != &pk[..]) );
+    }
+
     /*
     #[test]
     fn vrf_malleable() {
@@ -0,0 +1,135 @@
+// Copyright (c) 2026 The ring-vrf contributors
+//
+// Licensed under the MIT license, see LICENSE.
+
+//! ### Deterministic fixtures for tests
+//!
+//! We build these only for our own tests, or under the `testing`
+//! feature for downstream tests.  Never use these keys outside tests,
+//! since anyone can recompute them.
+
+use std::sync::Arc;
+
+use bellman::groth16;
+use group::WnafGroup;
+use pairing::{MultiMillerLoop, bls12_381::Bls12};
+
+use crate::{
+    rand_hack, JubjubEngineWithParams, SecretKey, RingBuilder, RingSRS,
+    RingSecretCopath, RingRoot, SynthesisResult,
+};
+
+
+/// Derive `n` secret keys from seeds `0..n`, and a ring of the given
+/// `depth` containing their public keys in order.
+///
+/// We encode seed `i` as little endian in the first eight bytes of
+/// `SecretKey::from_seed`'s seed, so the `i`th key is stable across
+/// calls and `n`.  We panic if `n` exceeds the capacity of `depth`.
+pub fn deterministic_ring<E: JubjubEngineWithParams>(n: usize, depth: u32)
+ -> (Vec<SecretKey<E>>, RingBuilder<E>)
+{
+    let secrets = (0..n).map( |i| {
+        let mut seed = [0u8; 32];
+        seed[..8].copy_from_slice(&(i as u64).to_le_bytes());
+        SecretKey::from_seed(seed)
+    } ).collect::<Vec<_>>();
+    let mut ring = RingBuilder::new(depth);
+    for sk in secrets.iter() {
+        ring.push(sk.to_public()).expect("deterministic_ring exceeds ring capacity");
+    }
+    (secrets, ring)
+}
+
+/// Depth of the CRS behind `RingFixture::shared`.
+pub const SHARED_DEPTH: u32 = 2;
+
+lazy_static! {
+    static ref SHARED_BLS12: Arc<groth16::Parameters<Bls12>> = Arc::new(
+        crate::generate_crs::<Bls12>(SHARED_DEPTH).expect("can't generate SRS")
+    );
+}
+
+/// A ring VRF CRS, its prepared verifying key, and one signer whose
+/// random copath authenticates them to `auth_root`.
+///
+/// We generate a fresh signer per fixture, but `shared` fixtures reuse
+/// one CRS, so tests that compare proofs across setups should build
+/// fixtures with `new` instead.
+pub struct RingFixture<E: JubjubEngineWithParams + MultiMillerLoop> {
+    pub depth: u32,
+    pub params: Arc<groth16::Parameters<E>>,
+    pub pvk: groth16::PreparedVerifyingKey<E>,
+    pub sk: SecretKey<E>,
+    pub copath: RingSecretCopath<E>,
+    pub auth_root: RingRoot<E>,
+}
+
+impl<E: JubjubEngineWithParams + MultiMillerLoop> RingFixture<E> {
+    /// Generate a ring VRF CRS of the given `depth`, and a signer.
+    pub fn new(depth: u32) -> SynthesisResult<RingFixture<E>>
+    where
+        E::G1: WnafGroup,
+        E::G2: WnafGroup,
+    {
+        Ok(RingFixture::with_params(crate::generate_crs::<E>(depth) ?, depth))
+    }
+
+    /// Wrap some existing CRS of the given `depth`, like a domain
+    /// separated or blinded CRS, and generate a signer.
+    pub fn with_params(params: groth16::Parameters<E>, depth: u32) -> RingFixture<E> {
+        RingFixture::with_shared_params(Arc::new(params), depth)
+    }
+
+    /// Wrap some CRS of the given `depth` shared with other fixtures,
+    /// and generate a signer.
+    pub fn with_shared_params(params: Arc<groth16::Parameters<E>>, depth: u32) -> RingFixture<E> {
+        let mut rng = rand_hack();
+        let pvk = groth16::prepare_verifying_key(&params.vk);
+        let sk = SecretKey::from_rng(&mut rng);
+        let copath = RingSecretCopath::random(depth, &mut rng);
+        let auth_root = copath.to_root(&sk.to_public());
+        RingFixture { depth, params, pvk, sk, copath, auth_root }
+    }
+
+    /// Borrow the CRS for proving.
+    pub fn srs(&self) -> RingSRS<&groth16::Parameters<E>> {
+        RingSRS { srs: &*self.params, depth: self.depth }
+    }
+
+    /// Clone the signer's secret key, copath, and ring root.
+    pub fn signer(&self) -> (SecretKey<E>, RingSecretCopath<E>, RingRoot<E>) {
+        (self.sk.clone(), self.copath.clone(), self.auth_root.clone())
+    }
+}
+
+impl RingFixture<Bls12> {
+    /// Generate a signer for the process wide CRS of depth `SHARED_DEPTH`,
+    /// which we generate only once, upon first use.
+    pub fn shared() -> RingFixture<Bls12> {
+        RingFixture::with_shared_params(SHARED_BLS12.clone(), SHARED_DEPTH)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{signing_context, VRFInput, vrf::no_extra};
+
+    #[test]
+    fn deterministic_ring_proves_membership() {
+        let fx = RingFixture::shared();
+        let (secrets, ring) = deterministic_ring::<Bls12>(3, fx.depth);
+        let (again, _) = deterministic_ring::<Bls12>(2, fx.depth);
+        assert_eq!(ring.len(), 3);
+        assert!(secrets[1].to_public() == again[1].to_public());
+        assert!(secrets[0].to_public() != secrets[1].to_public());
+
+        let (copath, auth_root) = ring.copath(2);
+        let input = VRFInput::<Bls12>::new_malleable(signing_context(b"testing").bytes(b"meow"));
+        let (inout, proof) = secrets[2].ring_vrf_sign_first(input, no_extra(), copath, fx.srs()).unwrap();
+        assert!(auth_root == ring.root());
+        assert!(auth_root.ring_vrf_verify(inout, no_extra(), proof, &fx.pvk).unwrap());
+    }
+}
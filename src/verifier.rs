@@ -7,13 +7,85 @@
 
 //! ### Ring VRF zk SNARK verifier
 
-use bellman::groth16::{self, Proof}; // verify_proof, prepare_verifying_key, PreparedVerifyingKey, VerifyingKey
+use std::{collections::{BTreeMap, HashSet, VecDeque}, io, ops::RangeInclusive, sync::Mutex};
+
+use bellman::{SynthesisError, groth16::{self, Proof}}; // verify_proof, prepare_verifying_key, PreparedVerifyingKey, VerifyingKey
 
 use crate::{
     SynthesisResult, JubjubEngineWithParams, 
-    SigningTranscript, RingRoot, VRFInOut
+    SigningTranscript, RingRoot, VRFInOut, VRFInput, VRFPreOut, ReadWrite,
+    vrf::extra_scalar,
 };
 use pairing::MultiMillerLoop;
+use rand_core::{RngCore,CryptoRng};
+
+
+/// Hash a verifying key for binding into VRF inputs with
+/// `VRFInput::new_ring_malleable_bound`.
+pub fn verifying_key_hash<E: MultiMillerLoop>(verifying_key: &groth16::VerifyingKey<E>) -> [u8; 32] {
+    let mut bytes = Vec::new();
+    verifying_key.write(&mut bytes).expect("Writing to a Vec never fails.");
+    let mut t = ::merlin::Transcript::new(b"RingVRFVerifyingKey");
+    t.append_message(b"vk", &bytes);
+    let mut hash = [0u8; 32];
+    t.challenge_bytes(b"", &mut hash);
+    hash
+}
+
+
+/// Reconstruct the public inputs of the `RingVRF` circuit, in the order
+/// the circuit allocates them, exactly as `ring_vrf_verify` does.
+///
+/// We intend this for debugging, so developers may compare our inputs
+/// against those the prover intended.
+pub fn ring_vrf_debug_public_inputs<E,T>(vrf_inout: &VRFInOut<E>, extra: T, auth_root: &RingRoot<E>) -> Vec<E::Fr>
+where E: JubjubEngineWithParams, T: SigningTranscript,
+{
+    // Public inputs are elements of the main curve (BLS12-381) scalar field (that matches Jubjub base field, that's the thing)
+    let (x1, y1) = vrf_inout.input.as_point().to_xy();
+    let (x2, y2) = vrf_inout.output.as_point().to_xy();
+    // We employ the challenge_scalar method in extra_scalar since it hashes
+    // into a field, but we're hashing into the jubjub base field not the
+    // scalar field here, so maybe the method should be renamed.
+    let extra = extra_scalar::<E,_>(extra);
+    vec![ x1, y1, x2, y2, extra, auth_root.0.clone() ]
+}
+
+
+/// Version of the circuit whose CRS a verifying key came from, which
+/// determines how we assemble public inputs.
+///
+/// We keep assembling every older version's public inputs, so proofs
+/// made against an old CRS still verify during migrations, provided
+/// verifiers tag that CRS's `VerifierCache` with the old version.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CircuitVersion {
+    /// The `RingVRF` circuit from `generate_crs`, with public inputs
+    /// as given by `ring_vrf_debug_public_inputs`.
+    V1,
+    /// The `RingVRFDomain` circuit from `generate_crs_domain`, which
+    /// appends the given application domain to the V1 public inputs.
+    V2(Vec<u8>),
+}
+
+impl Default for CircuitVersion {
+    fn default() -> CircuitVersion { CircuitVersion::V1 }
+}
+
+impl CircuitVersion {
+    /// Reconstruct the public inputs of this version's circuit, in the
+    /// order the circuit allocates them.
+    pub fn public_inputs<E,T>(&self, vrf_inout: &VRFInOut<E>, extra: T, auth_root: &RingRoot<E>) -> Vec<E::Fr>
+    where E: JubjubEngineWithParams, T: SigningTranscript,
+    {
+        let mut public_input = ring_vrf_debug_public_inputs(vrf_inout, extra, auth_root);
+        match self {
+            CircuitVersion::V1 => {},
+            CircuitVersion::V2(domain) => public_input.push(crate::circuit::domain_scalar::<E>(domain)),
+        }
+        public_input
+    }
+}
 
 
 impl<E: JubjubEngineWithParams + MultiMillerLoop> RingRoot<E> {
@@ -51,7 +123,7 @@ impl<E: JubjubEngineWithParams + MultiMillerLoop> RingRoot<E> {
         // 2. VRF input and output points on Jubjub prepared togther
         vrf_inout: VRFInOut<E>,
         // 3. extra message signed along with the VRF
-        mut extra: T,
+        extra: T,
         // 
         zkproof: Proof<E>,
         // Prepared means that 1 pairing e(alpha, beta) has been precomputed.
@@ -63,16 +135,1329 @@ impl<E: JubjubEngineWithParams + MultiMillerLoop> RingRoot<E> {
         // TODO: lifetime?
         // TODO: Check params.auth_depth perhaps?
         // TODO: subgroup checks
-        // Public inputs are elements of the main curve (BLS12-381) scalar field (that matches Jubjub base field, that's the thing)
+        let public_input = ring_vrf_debug_public_inputs(&vrf_inout, extra, self);
+        // Verify the proof
+        Ok(groth16::verify_proof(verifying_key, &zkproof, &public_input[..]).is_ok())
+    }
+
+    /// Verify a proof like `ring_vrf_verify`, but also return the public
+    /// inputs we reconstructed, for comparison against the prover's when
+    /// debugging proofs that fail to verify.
+    pub fn ring_vrf_verify_debug<T>(
+        &self, // auth_root
+        vrf_inout: VRFInOut<E>,
+        extra: T,
+        zkproof: Proof<E>,
+        verifying_key: &groth16::PreparedVerifyingKey<E>,
+    ) -> SynthesisResult<(bool, Vec<E::Fr>)> 
+    where T: SigningTranscript, 
+    {
+        let public_input = ring_vrf_debug_public_inputs(&vrf_inout, extra, self);
+        let valid = groth16::verify_proof(verifying_key, &zkproof, &public_input[..]).is_ok();
+        Ok((valid, public_input))
+    }
+
+    /// Verify a proof from `SecretKey::ring_vrf_prove_with_expiry`,
+    /// provided `current_epoch` does not exceed `expiry`.
+    ///
+    /// We return an `io::ErrorKind::TimedOut` error for expired proofs,
+    /// which callers may distinguish from `Ok(false)` for invalid proofs.
+    pub fn ring_vrf_verify_with_expiry<T>(
+        &self, // auth_root
+        vrf_inout: VRFInOut<E>,
+        mut extra: T,
+        expiry: u64,
+        current_epoch: u64,
+        zkproof: Proof<E>,
+        verifying_key: &groth16::PreparedVerifyingKey<E>,
+    ) -> SynthesisResult<bool> 
+    where T: SigningTranscript, 
+    {
+        if current_epoch > expiry {
+            return Err(SynthesisError::IoError(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "Ring VRF proof expired."
+            )));
+        }
+        extra.commit_bytes(b"expiry", &expiry.to_le_bytes());
+        self.ring_vrf_verify(vrf_inout, extra, zkproof, verifying_key)
+    }
+
+    /// Verify a proof from `SecretKey::ring_vrf_prove_with_nonce`, which
+    /// commits `nonce` into `extra`, and record `nonce` in `seen`.
+    ///
+    /// We return an `io::ErrorKind::AlreadyExists` error if `seen`
+    /// already contains `nonce`, without verifying.  We insert `nonce`
+    /// only once the proof verifies, so invalid proofs cannot burn
+    /// nonces belonging to honest signers.
+    pub fn ring_vrf_verify_fresh<T>(
+        &self, // auth_root
+        vrf_inout: VRFInOut<E>,
+        mut extra: T,
+        nonce: &[u8],
+        seen: &mut HashSet<Vec<u8>>,
+        zkproof: Proof<E>,
+        verifying_key: &groth16::PreparedVerifyingKey<E>,
+    ) -> SynthesisResult<bool> 
+    where T: SigningTranscript, 
+    {
+        if seen.contains(nonce) {
+            return Err(SynthesisError::IoError(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                "Ring VRF nonce replayed."
+            )));
+        }
+        extra.commit_bytes(b"replay-nonce", nonce);
+        let valid = self.ring_vrf_verify(vrf_inout, extra, zkproof, verifying_key) ?;
+        if valid {  seen.insert(nonce.to_vec());  }
+        Ok(valid)
+    }
+
+    /// Verify a ring VRF lottery ticket, distinguishing invalid proofs
+    /// from valid proofs whose output fails `exceeds_threshold`.
+    pub fn verify_lottery<T>(
+        &self, // auth_root
+        vrf_inout: VRFInOut<E>,
+        extra: T,
+        zkproof: Proof<E>,
+        verifying_key: &groth16::PreparedVerifyingKey<E>,
+        context: &[u8],
+        threshold: &[u8; 32],
+    ) -> LotteryResult<E> 
+    where T: SigningTranscript, 
+    {
+        match self.ring_vrf_verify(vrf_inout.clone(), extra, zkproof, verifying_key) {
+            Err(e) => LotteryResult::Invalid(e),
+            Ok(false) => LotteryResult::Invalid(invalid_proof_error()),
+            Ok(true) if vrf_inout.exceeds_threshold(context, threshold) => LotteryResult::LostLottery,
+            Ok(true) => LotteryResult::Won(vrf_inout),
+        }
+    }
+
+    /// Verify a proof from `SecretKey::prove_membership_with_nullifier`,
+    /// returning the nullifier if valid.
+    ///
+    /// Any two valid proofs for the same `epoch` by the same key return
+    /// the same nullifier, so callers detect double actions by storing
+    /// the nullifiers they have seen.
+    pub fn verify_membership_with_nullifier(
+        &self, // auth_root
+        epoch: &[u8],
+        preout: &VRFPreOut<E>,
+        zkproof: Proof<E>,
+        verifying_key: &groth16::PreparedVerifyingKey<E>,
+    ) -> SynthesisResult<Option<[u8; 32]>> 
+    {
+        let inout = VRFInOut { input: VRFInput::new_nullifier(epoch), output: preout.clone() };
+        let nullifier = inout.nullifier();
+        if self.ring_vrf_verify(inout, crate::vrf::no_extra(), zkproof, verifying_key) ? {
+            Ok(Some(nullifier))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Verify a proof that the signer's key lies in both `self`, the
+    /// old ring, and `new_root`, the new ring, using the prepared
+    /// verifying key from `generate_crs_transition`.
+    pub fn ring_vrf_verify_transition<T>(
+        &self, // old auth_root
+        new_root: &RingRoot<E>,
+        vrf_inout: VRFInOut<E>,
+        extra: T,
+        zkproof: Proof<E>,
+        verifying_key: &groth16::PreparedVerifyingKey<E>,
+    ) -> SynthesisResult<bool> 
+    where T: SigningTranscript, 
+    {
         let (x1, y1) = vrf_inout.input.as_point().to_xy();
         let (x2, y2) = vrf_inout.output.as_point().to_xy();
-        // We employ the challenge_scalar method since it hashes into a field,
-        // but we're hashing into the jubjub base field not the scalar field
-        // here, so maybe the method should be renamed.
-        let extra = extra.challenge_scalar(b"extra-msg");
+        let extra = extra_scalar::<E,_>(extra);
+        let public_input: [E::Fr; 7] = [ x1, y1, x2, y2, extra, self.0.clone(), new_root.0.clone() ];
+        Ok(groth16::verify_proof(verifying_key, &zkproof, &public_input[..]).is_ok())
+    }
+
+    /// Verify a proof from `SecretKey::ring_vrf_prove_in_domain` for
+    /// the application `domain`, using the prepared verifying key from
+    /// `generate_crs_domain`.
+    pub fn ring_vrf_verify_in_domain<T>(
+        &self, // auth_root
+        vrf_inout: VRFInOut<E>,
+        extra: T,
+        domain: &[u8],
+        zkproof: Proof<E>,
+        verifying_key: &groth16::PreparedVerifyingKey<E>,
+    ) -> SynthesisResult<bool> 
+    where T: SigningTranscript, 
+    {
+        let public_input = CircuitVersion::V2(domain.to_vec()).public_inputs(&vrf_inout, extra, self);
+        Ok(groth16::verify_proof(verifying_key, &zkproof, &public_input[..]).is_ok())
+    }
+
+    /// Verify a proof from `SecretKey::ring_vrf_prove_blinded`, that
+    /// `commitment` commits to some ring member's VRF output on
+    /// `vrf_input`, using the prepared verifying key from `generate_crs_blinded`.
+    ///
+    /// Parties holding the blinding then recover the VRF output with
+    /// `VRFOutputCommitment::open`.
+    pub fn ring_vrf_verify_blinded<T>(
+        &self, // auth_root
+        vrf_input: &VRFInput<E>,
+        commitment: &crate::vrf::VRFOutputCommitment<E>,
+        extra: T,
+        zkproof: Proof<E>,
+        verifying_key: &groth16::PreparedVerifyingKey<E>,
+    ) -> SynthesisResult<bool> 
+    where T: SigningTranscript, 
+    {
+        let (x1, y1) = vrf_input.as_point().to_xy();
+        let (x2, y2) = commitment.as_point().to_xy();
+        let extra = extra_scalar::<E,_>(extra);
         let public_input: [E::Fr; 6] = [ x1, y1, x2, y2, extra, self.0.clone() ];
-        // Verify the proof
         Ok(groth16::verify_proof(verifying_key, &zkproof, &public_input[..]).is_ok())
     }
+
+    /// Verify a proof from `SecretKey::ring_vrf_prove_with_audit`, which
+    /// shows `tag` encrypts the signer's ring index to `auditor`, using
+    /// the prepared verifying key from `generate_crs_audit`.
+    #[cfg(feature = "audit_tag")]
+    pub fn ring_vrf_verify_with_audit<T>(
+        &self, // auth_root
+        vrf_inout: VRFInOut<E>,
+        extra: T,
+        zkproof: Proof<E>,
+        tag: &crate::audit::AuditTag<E>,
+        auditor: &crate::PublicKey<E>,
+        verifying_key: &groth16::PreparedVerifyingKey<E>,
+    ) -> SynthesisResult<bool> 
+    where T: SigningTranscript, 
+    {
+        let (x1, y1) = vrf_inout.input.as_point().to_xy();
+        let (x2, y2) = vrf_inout.output.as_point().to_xy();
+        let extra = extra_scalar::<E,_>(extra);
+        let (rx, ry) = tag.ephemeral.to_xy();
+        let (ax, ay) = auditor.0.to_xy();
+        let (cx, cy) = tag.ciphertext.to_xy();
+        let public_input: [E::Fr; 12] = [
+            x1, y1, x2, y2, extra, self.0.clone(),
+            rx, ry, ax, ay, cx, cy,
+        ];
+        Ok(groth16::verify_proof(verifying_key, &zkproof, &public_input[..]).is_ok())
+    }
+}
+
+
+/// Number of proofs `ring_vrf_verify_stream` checks together.
+const STREAM_BATCH: usize = 16;
+
+/// Check many Groth16 proofs against their public inputs at once,
+/// returning true only if every proof verifies.
+///
+/// We check one random linear combination of the proofs' pairing
+/// equations, so `n` proofs cost `n + 3` Miller loops and one final
+/// exponentiation, instead of `3 n` Miller loops and `n` final
+/// exponentiations.  We cannot say which proof failed, so callers
+/// should check proofs individually after we return false.
+pub fn verify_proofs_batch<'b,E,R,I>(verifying_key: &groth16::VerifyingKey<E>, proofs: I, rng: &mut R) -> bool
+where
+    E: MultiMillerLoop,
+    R: RngCore+CryptoRng,
+    I: IntoIterator<Item = (&'b Proof<E>, &'b [E::Fr])>,
+{
+    use ff::Field;
+    use group::{Curve, Group};
+    use pairing::MillerLoopResult;
+
+    // We check e(A, B) = e(alpha, beta) e(IC, gamma) e(C, delta) for each
+    // proof, after raising each proof's equation to a random power r.
+    let mut ab = Vec::new();
+    let mut r_sum = E::Fr::zero();
+    let mut r_ic = E::G1::identity();
+    let mut r_c = E::G1::identity();
+    for (proof, public_input) in proofs {
+        if public_input.len() + 1 != verifying_key.ic.len() {
+            return false;
+        }
+        let r = E::Fr::random(&mut *rng);
+        let mut ic = E::G1::from(verifying_key.ic[0]);
+        for (x, base) in public_input.iter().zip(verifying_key.ic[1..].iter()) {
+            ic += E::G1::from(*base) * *x;
+        }
+        r_ic += ic * r;
+        r_c += E::G1::from(proof.c) * r;
+        r_sum += r;
+        ab.push(( (E::G1::from(proof.a) * r).to_affine(), E::G2Prepared::from(proof.b) ));
+    }
+    if ab.is_empty() {
+        return true;
+    }
+
+    let neg_alpha = (-(E::G1::from(verifying_key.alpha_g1) * r_sum)).to_affine();
+    let neg_ic = (-r_ic).to_affine();
+    let neg_c = (-r_c).to_affine();
+    let beta = E::G2Prepared::from(verifying_key.beta_g2);
+    let gamma = E::G2Prepared::from(verifying_key.gamma_g2);
+    let delta = E::G2Prepared::from(verifying_key.delta_g2);
+    let mut terms = ab.iter().map( |(a,b)| (a,b) ).collect::<Vec<_>>();
+    terms.push((&neg_alpha, &beta));
+    terms.push((&neg_ic, &gamma));
+    terms.push((&neg_c, &delta));
+    bool::from(E::multi_miller_loop(&terms).final_exponentiation().is_identity())
+}
+
+/// Verify a stream of ring VRF signatures against one ring root, yielding
+/// each `VRFInOut` whose proof verifies, or else an error.
+///
+/// We verify lazily as items arrive, like from a network socket, so we
+/// hold at most `STREAM_BATCH` proofs at a time, and callers may stop
+/// early.  We check each group of `STREAM_BATCH` proofs together using
+/// `verify_proofs_batch`, and check proofs individually only when their
+/// group fails, so honest streams save most pairings.  We return an
+/// `io::ErrorKind::InvalidData` error for invalid proofs.
+pub fn ring_vrf_verify_stream<'a,E,T,I>(
+    items: I,
+    auth_root: &'a RingRoot<E>,
+    verifying_key: &'a groth16::VerifyingKey<E>,
+) -> impl Iterator<Item = SynthesisResult<VRFInOut<E>>> + 'a
+where
+    E: JubjubEngineWithParams + MultiMillerLoop,
+    T: SigningTranscript,
+    I: IntoIterator<Item = (VRFInOut<E>, T, Proof<E>)>,
+    I::IntoIter: 'a,
+{
+    let pvk = groth16::prepare_verifying_key(verifying_key);
+    let mut items = items.into_iter();
+    let mut results = VecDeque::with_capacity(STREAM_BATCH);
+    ::std::iter::from_fn( move || {
+        if results.is_empty() {
+            let batch = items.by_ref().take(STREAM_BATCH).map( |(vrf_inout, extra, zkproof)| {
+                let public_input = ring_vrf_debug_public_inputs(&vrf_inout, extra, auth_root);
+                (vrf_inout, zkproof, public_input)
+            } ).collect::<Vec<_>>();
+            let all_valid = verify_proofs_batch(
+                verifying_key,
+                batch.iter().map( |(_, zkproof, public_input)| (zkproof, &public_input[..]) ),
+                &mut crate::rand_hack(),
+            );
+            results.extend( batch.into_iter().map( |(vrf_inout, zkproof, public_input)| {
+                if all_valid || groth16::verify_proof(&pvk, &zkproof, &public_input[..]).is_ok() {
+                    Ok(vrf_inout)
+                } else {
+                    Err(invalid_proof_error())
+                }
+            } ) );
+        }
+        results.pop_front()
+    } )
+}
+
+pub(crate) fn invalid_proof_error() -> SynthesisError {
+    SynthesisError::IoError(io::Error::new(
+        io::ErrorKind::InvalidData,
+        "Ring VRF proof failed to verify."
+    ))
+}
+
+/// Verify a ring VRF signature against the ring of `members`, in the
+/// order given, returning the `VRFInOut` if the proof verifies.
+///
+/// We compute the ring root exactly like `RingRoot::from_publickeys`,
+/// so callers holding only public keys avoid root mismatches.  We
+/// return an `io::ErrorKind::InvalidData` error for invalid proofs,
+/// like `ring_vrf_verify_stream`.
+pub fn ring_vrf_verify_members<E,T>(
+    vrf_inout: VRFInOut<E>,
+    extra: T,
+    members: &[crate::PublicKey<E>],
+    depth: u32,
+    zkproof: Proof<E>,
+    verifying_key: &groth16::PreparedVerifyingKey<E>,
+) -> SynthesisResult<VRFInOut<E>>
+where
+    E: JubjubEngineWithParams + MultiMillerLoop,
+    T: SigningTranscript,
+{
+    if members.len() < 2 || crate::ring_capacity(depth).map_or(false, |c| members.len() as u64 > c) {
+        return Err(SynthesisError::IoError(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Ring members do not fit a ring of this depth."
+        )));
+    }
+    let auth_root = RingRoot::from_publickeys(members, depth as usize);
+    if auth_root.ring_vrf_verify(vrf_inout.clone(), extra, zkproof, verifying_key) ? {
+        Ok(vrf_inout)
+    } else {
+        Err(invalid_proof_error())
+    }
+}
+
+
+/// Verify a ring VRF signature given the serialized VRF pre-output,
+/// returning the VRF output bytes `make_bytes(context)` if valid.
+///
+/// We let callers who store VRF outputs as bytes avoid handling
+/// `VRFPreOut`.  We return the `io::Error` from `VRFPreOut::read` for
+/// malformed `output_bytes`, and otherwise errors like `ring_vrf_verify_stream`.
+pub fn ring_vrf_verify_bytes<E,T>(
+    vrf_input: VRFInput<E>,
+    output_bytes: &[u8; 32],
+    extra: T,
+    auth_root: &RingRoot<E>,
+    zkproof: Proof<E>,
+    verifying_key: &groth16::PreparedVerifyingKey<E>,
+    context: &[u8],
+) -> SynthesisResult<[u8; 32]>
+where
+    E: JubjubEngineWithParams + MultiMillerLoop,
+    T: SigningTranscript,
+{
+    let output = VRFPreOut::read(&output_bytes[..]) ?;
+    let vrf_inout = VRFInOut { input: vrf_input, output };
+    if auth_root.ring_vrf_verify(vrf_inout.clone(), extra, zkproof, verifying_key) ? {
+        Ok(vrf_inout.make_bytes(context))
+    } else {
+        Err(invalid_proof_error())
+    }
+}
+
+
+/// Verify a ring VRF signature against a VRF output we computed
+/// ourselves, like from a deterministic input and a known Diffie-Hellman
+/// shared value, so the prover need never transmit the output.
+///
+/// We return the `VRFInOut` if `expected_output` is some ring member's
+/// VRF output on `vrf_input`, and otherwise errors like `ring_vrf_verify_stream`.
+pub fn ring_vrf_verify_expected<E,T>(
+    vrf_input: VRFInput<E>,
+    expected_output: &VRFPreOut<E>,
+    extra: T,
+    auth_root: &RingRoot<E>,
+    zkproof: Proof<E>,
+    verifying_key: &groth16::PreparedVerifyingKey<E>,
+) -> SynthesisResult<VRFInOut<E>>
+where
+    E: JubjubEngineWithParams + MultiMillerLoop,
+    T: SigningTranscript,
+{
+    let vrf_inout = VRFInOut { input: vrf_input, output: expected_output.clone() };
+    if auth_root.ring_vrf_verify(vrf_inout.clone(), extra, zkproof, verifying_key) ? {
+        Ok(vrf_inout)
+    } else {
+        Err(invalid_proof_error())
+    }
+}
+
+
+/// Outcome of `RingRoot::verify_lottery`.
+#[derive(Debug)]
+pub enum LotteryResult<E: JubjubEngineWithParams> {
+    /// The proof failed to verify, so callers should penalize the peer.
+    Invalid(SynthesisError),
+    /// The proof verifies, but the output exceeds the threshold.
+    LostLottery,
+    /// The proof verifies and the output lies below the threshold.
+    Won(VRFInOut<E>),
+}
+
+
+/// Number of VRF outputs `VerifierCache` remembers as lying in the
+/// prime order subgroup.
+pub const SUBGROUP_CACHE_CAPACITY: usize = 4096;
+
+/// Memoized subgroup checks of VRF outputs, keyed by their compressed
+/// encoding, so that verifying gossip duplicates skips the scalar
+/// multiplication by the subgroup order.
+///
+/// We remember only outputs that pass, and forget everything once we
+/// reach our capacity.
+pub struct SubgroupCache<E: JubjubEngineWithParams> {
+    capacity: usize,
+    checked: Mutex<HashSet<[u8; 32]>>,
+    _marker: ::core::marker::PhantomData<E>,
+}
+
+impl<E: JubjubEngineWithParams> SubgroupCache<E> {
+    /// Remember up to `capacity` outputs.
+    pub fn new(capacity: usize) -> SubgroupCache<E> {
+        SubgroupCache { capacity, checked: Mutex::new(HashSet::new()), _marker: ::core::marker::PhantomData }
+    }
+
+    fn key(preout: &VRFPreOut<E>) -> [u8; 32] {
+        let mut key = [0u8; 32];
+        preout.write(&mut key[..]).expect("VRFPreOut encodes as 32 bytes.");
+        key
+    }
+
+    /// Returns true if we previously found `preout` in the prime order subgroup.
+    pub fn contains(&self, preout: &VRFPreOut<E>) -> bool {
+        self.checked.lock().unwrap_or_else( |e| e.into_inner() ).contains(&Self::key(preout))
+    }
+
+    /// Number of outputs we currently remember.
+    pub fn len(&self) -> usize {
+        self.checked.lock().unwrap_or_else( |e| e.into_inner() ).len()
+    }
+
+    /// Returns true if `preout` lies in the prime order subgroup,
+    /// consulting and updating our cache.
+    pub fn check(&self, preout: &VRFPreOut<E>) -> bool {
+        let key = Self::key(preout);
+        if self.checked.lock().unwrap_or_else( |e| e.into_inner() ).contains(&key) {
+            return true;
+        }
+        // We hold no lock during the scalar multiplication.
+        if preout.as_point().as_prime_order(E::params()).is_none() {
+            return false;
+        }
+        let mut checked = self.checked.lock().unwrap_or_else( |e| e.into_inner() );
+        if checked.len() >= self.capacity {  checked.clear();  }
+        checked.insert(key);
+        true
+    }
+}
+
+/// Reject VRF outputs outside the prime order subgroup.
+fn check_subgroup<E: JubjubEngineWithParams>(cache: &SubgroupCache<E>, preout: &VRFPreOut<E>) -> SynthesisResult<()> {
+    if cache.check(preout) {  return Ok(());  }
+    Err(SynthesisError::IoError(io::Error::new(
+        io::ErrorKind::InvalidData,
+        "VRF output lies outside the prime order subgroup."
+    )))
+}
+
+/// Stage at which `VerifierCache::verify_report` rejected a signature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VerifyStage {
+    /// The ring root lies outside the verifier's allowlist.
+    UnknownRoot,
+    /// The VRF output bytes encode no Jubjub point.
+    PublicInputDecode,
+    /// The VRF output lies outside the prime order subgroup.
+    SubgroupCheck,
+    /// The verifying key expects a different number of public inputs,
+    /// like from another circuit version.
+    ///
+    /// The depth changes no public input, so verifying keys for other
+    /// depths fail at `PairingCheck` instead.  Check the depth when
+    /// loading the CRS, see `crs_check_depth`.
+    VkMismatch,
+    /// The Groth16 pairing check failed, so the proof is invalid.
+    PairingCheck,
+}
+
+/// Why `VerifierCache::verify_report` rejected a signature.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifyReport {
+    /// Stage at which verification failed.
+    pub stage: VerifyStage,
+    /// Human readable description of the failure.
+    pub reason: &'static str,
+}
+
+impl VerifyReport {
+    fn new(stage: VerifyStage, reason: &'static str) -> VerifyReport {
+        VerifyReport { stage, reason }
+    }
+}
+
+impl ::core::fmt::Display for VerifyReport {
+    fn fmt(&self, f: &mut ::core::fmt::Formatter) -> ::core::fmt::Result {
+        write!(f, "{:?}: {}", self.stage, self.reason)
+    }
+}
+
+impl ::std::error::Error for VerifyReport {}
+
+/// Prepared verifying key along with an optional allowlist of ring roots.
+///
+/// Validators should only accept proofs against rings they recognize,
+/// so we reject unknown roots before doing any pairing work.
+pub struct VerifierCache<E: JubjubEngineWithParams + MultiMillerLoop> {
+    verifying_key: groth16::PreparedVerifyingKey<E>,
+    /// Number of public inputs our verifying key expects.
+    num_inputs: usize,
+    allowed_roots: Option<HashSet<RingRoot<E>>>,
+    version: CircuitVersion,
+    /// Trusted setup epoch of our verifying key's CRS.
+    crs_epoch: u32,
+    subgroup: SubgroupCache<E>,
+    #[cfg(feature = "metrics")]
+    metrics: crate::metrics::VerifierMetrics,
+}
+
+impl<E: JubjubEngineWithParams + MultiMillerLoop> VerifierCache<E> {
+    /// Prepare `verifying_key`, initially accepting any ring root.
+    pub fn new(verifying_key: &groth16::VerifyingKey<E>) -> VerifierCache<E> {
+        VerifierCache {
+            verifying_key: groth16::prepare_verifying_key::<E>(verifying_key),
+            num_inputs: verifying_key.ic.len().saturating_sub(1),
+            allowed_roots: None,
+            version: CircuitVersion::V1,
+            crs_epoch: 0,
+            subgroup: SubgroupCache::new(SUBGROUP_CACHE_CAPACITY),
+            #[cfg(feature = "metrics")]
+            metrics: Default::default(),
+        }
+    }
+
+    /// Counters updated by our verification methods.
+    #[cfg(feature = "metrics")]
+    pub fn metrics(&self) -> &crate::metrics::VerifierMetrics {
+        &self.metrics
+    }
+
+    /// Our memoized subgroup checks of VRF outputs.
+    pub fn subgroup_cache(&self) -> &SubgroupCache<E> {
+        &self.subgroup
+    }
+
+    /// Assemble public inputs for `version`, which must be the version
+    /// of the circuit whose CRS gave our verifying key.
+    pub fn with_circuit_version(self, version: CircuitVersion) -> VerifierCache<E> {
+        VerifierCache { version, ..self }
+    }
+
+    /// Version of the circuit whose proofs we verify.
+    pub fn circuit_version(&self) -> &CircuitVersion {
+        &self.version
+    }
+
+    /// Tag our verifying key with the trusted setup `epoch` of its CRS,
+    /// like from `read_crs_with_epoch`, initially zero.
+    pub fn with_crs_epoch(self, crs_epoch: u32) -> VerifierCache<E> {
+        VerifierCache { crs_epoch, ..self }
+    }
+
+    /// Trusted setup epoch of our verifying key's CRS.
+    pub fn crs_epoch(&self) -> u32 {
+        self.crs_epoch
+    }
+
+    /// Verify `zkproof` against our verifying key, assembling public
+    /// inputs for our circuit version.
+    fn verify_proof<T>(&self, auth_root: &RingRoot<E>, vrf_inout: &VRFInOut<E>, extra: T, zkproof: &Proof<E>) -> bool
+    where T: SigningTranscript,
+    {
+        let public_input = self.version.public_inputs(vrf_inout, extra, auth_root);
+        groth16::verify_proof(&self.verifying_key, zkproof, &public_input[..]).is_ok()
+    }
+
+    /// Accept only proofs against the given ring roots.
+    pub fn with_allowed_roots(self, roots: HashSet<RingRoot<E>>) -> VerifierCache<E> {
+        VerifierCache { allowed_roots: Some(roots), ..self }
+    }
+
+    /// Returns true if we accept proofs against `auth_root`.
+    pub fn is_allowed(&self, auth_root: &RingRoot<E>) -> bool {
+        self.allowed_roots.as_ref().map_or(true, |roots| roots.contains(auth_root))
+    }
+
+    /// Verify a proof like `RingRoot::ring_vrf_verify`, but first check
+    /// `auth_root` lies in our allowlist.
+    ///
+    /// We return an `io::ErrorKind::PermissionDenied` error for unknown
+    /// roots, which callers may distinguish from `Ok(false)` for invalid proofs.
+    pub fn ring_vrf_verify<T>(
+        &self,
+        auth_root: &RingRoot<E>,
+        vrf_inout: VRFInOut<E>,
+        extra: T,
+        zkproof: Proof<E>,
+    ) -> SynthesisResult<bool> 
+    where T: SigningTranscript, 
+    {
+        if ! self.is_allowed(auth_root) {
+            #[cfg(feature = "metrics")]
+            self.metrics.record_rejected(crate::metrics::RejectReason::UnknownRoot);
+            return Err(SynthesisError::IoError(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "Ring root is not in the verifier's allowlist."
+            )));
+        }
+        // We run the pairing check even for outputs outside the subgroup,
+        // so remote observers cannot distinguish malformed outputs from
+        // invalid proofs by our timing.
+        let in_subgroup = check_subgroup(&self.subgroup, &vrf_inout.output);
+
+        #[cfg(feature = "metrics")]
+        let start = ::std::time::Instant::now();
+        let valid = self.verify_proof(auth_root, &vrf_inout, extra, &zkproof);
+        let r = in_subgroup.map( |()| valid );
+        #[cfg(feature = "metrics")] {
+            use crate::metrics::RejectReason;
+            self.metrics.record_latency(start.elapsed());
+            match r {
+                Ok(true) => self.metrics.record_verified(),
+                Ok(false) => self.metrics.record_rejected(RejectReason::InvalidProof),
+                Err(_) => self.metrics.record_rejected(RejectReason::Error),
+            }
+        }
+        r
+    }
+
+    /// Verify many proofs like `ring_vrf_verify`, returning each result
+    /// in order, and recording the batch size in our metrics.
+    ///
+    /// We verify every proof individually, so batching saves no pairings.
+    pub fn ring_vrf_verify_batch<T,I>(&self, items: I) -> Vec<SynthesisResult<bool>>
+    where
+        T: SigningTranscript,
+        I: IntoIterator<Item = (RingRoot<E>, VRFInOut<E>, T, Proof<E>)>,
+    {
+        let results = items.into_iter().map( |(auth_root, vrf_inout, extra, zkproof)|
+            self.ring_vrf_verify(&auth_root, vrf_inout, extra, zkproof)
+        ).collect::<Vec<_>>();
+        #[cfg(feature = "metrics")]
+        self.metrics.record_batch(results.len());
+        results
+    }
+
+    /// Verify a proof against each allowed root in `roots` in turn,
+    /// like a window of recent rings during epoch transitions, returning
+    /// the `VRFInOut` and the index of the first root for which it verifies.
+    ///
+    /// We skip roots outside our allowlist, and return an
+    /// `io::ErrorKind::InvalidData` error if no root verifies.
+    pub fn verify_within_roots<T>(
+        &self,
+        vrf_inout: VRFInOut<E>,
+        extra: T,
+        zkproof: Proof<E>,
+        roots: &[RingRoot<E>],
+    ) -> SynthesisResult<(VRFInOut<E>, usize)> 
+    where T: SigningTranscript+Clone, 
+    {
+        // We check the subgroup before pairing, but like `ring_vrf_verify`
+        // we report failures only after pairing, so timing never reveals
+        // outputs outside the subgroup.
+        let in_subgroup = check_subgroup(&self.subgroup, &vrf_inout.output);
+        for (i,auth_root) in roots.iter().enumerate() {
+            if ! self.is_allowed(auth_root) {  continue;  }
+            if self.verify_proof(auth_root, &vrf_inout, extra.clone(), &zkproof) {
+                in_subgroup ?;
+                #[cfg(feature = "metrics")]
+                self.metrics.record_verified();
+                return Ok((vrf_inout, i));
+            }
+        }
+        in_subgroup ?;
+        #[cfg(feature = "metrics")]
+        self.metrics.record_rejected(crate::metrics::RejectReason::InvalidProof);
+        Err(invalid_proof_error())
+    }
+
+    /// Verify a `SignedVrfBundle` on `vrf_input`, but first check the
+    /// bundle claims `expected_root`, like from our configuration, so
+    /// bundles for stale or foreign rings fail before any pairing.
+    ///
+    /// We return an `io::ErrorKind::InvalidInput` error for root
+    /// mismatches, and otherwise errors like `ring_vrf_verify_stream`.
+    pub fn verify_strict<T>(
+        &self,
+        expected_root: &RingRoot<E>,
+        vrf_input: VRFInput<E>,
+        bundle: &crate::SignedVrfBundle<E>,
+        extra: T,
+    ) -> SynthesisResult<VRFInOut<E>>
+    where T: SigningTranscript,
+    {
+        if bundle.auth_root != *expected_root {
+            return Err(SynthesisError::IoError(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Ring root differs from the expected ring root."
+            )));
+        }
+        let vrf_inout = VRFInOut { input: vrf_input, output: bundle.preout.clone() };
+        if self.ring_vrf_verify(&bundle.auth_root, vrf_inout.clone(), extra, bundle.proof.clone()) ? {
+            Ok(vrf_inout)
+        } else {
+            Err(invalid_proof_error())
+        }
+    }
+
+    /// Verify a signature given the serialized VRF pre-output, like
+    /// `ring_vrf_verify_bytes`, but report the stage at which
+    /// verification failed, for debugging and metrics.
+    ///
+    /// We stop at the first failing stage, so unlike `ring_vrf_verify`
+    /// our timing reveals the stage, which reports reveal anyways.
+    pub fn verify_report<T>(
+        &self,
+        auth_root: &RingRoot<E>,
+        vrf_input: VRFInput<E>,
+        output_bytes: &[u8; 32],
+        extra: T,
+        zkproof: Proof<E>,
+    ) -> Result<VRFInOut<E>, VerifyReport>
+    where T: SigningTranscript,
+    {
+        if ! self.is_allowed(auth_root) {
+            return Err(VerifyReport::new(VerifyStage::UnknownRoot, "Ring root is not in the verifier's allowlist."));
+        }
+        let output = VRFPreOut::read(&output_bytes[..]).map_err( |_|
+            VerifyReport::new(VerifyStage::PublicInputDecode, "VRF output bytes encode no Jubjub point.")
+        ) ?;
+        if ! self.subgroup.check(&output) {
+            return Err(VerifyReport::new(VerifyStage::SubgroupCheck, "VRF output lies outside the prime order subgroup."));
+        }
+        let vrf_inout = VRFInOut { input: vrf_input, output };
+        let public_input = self.version.public_inputs(&vrf_inout, extra, auth_root);
+        if public_input.len() != self.num_inputs {
+            return Err(VerifyReport::new(VerifyStage::VkMismatch, "Verifying key expects a different number of public inputs."));
+        }
+        if groth16::verify_proof(&self.verifying_key, &zkproof, &public_input[..]).is_err() {
+            return Err(VerifyReport::new(VerifyStage::PairingCheck, "Ring VRF proof failed to verify."));
+        }
+        Ok(vrf_inout)
+    }
 }
 
+
+/// Verifiers for several trusted setup epochs, of which we accept only
+/// those within a range, so governance may retire a setup.
+///
+/// Proofs never name their epoch, so callers learn the epoch a proof
+/// claims alongside it, and we reject retired epochs before any
+/// pairing work.  A proof made under one epoch's CRS never verifies
+/// against another epoch's verifying key anyways.
+pub struct EpochVerifiers<E: JubjubEngineWithParams + MultiMillerLoop> {
+    verifiers: BTreeMap<u32, VerifierCache<E>>,
+    accepted: RangeInclusive<u32>,
+}
+
+impl<E: JubjubEngineWithParams + MultiMillerLoop> EpochVerifiers<E> {
+    /// Start with no verifiers, accepting epochs in `accepted`.
+    pub fn new(accepted: RangeInclusive<u32>) -> EpochVerifiers<E> {
+        EpochVerifiers { verifiers: BTreeMap::new(), accepted }
+    }
+
+    /// Add `verifier` under its `VerifierCache::crs_epoch`, replacing any
+    /// previous verifier for that epoch.
+    pub fn insert(&mut self, verifier: VerifierCache<E>) {
+        self.verifiers.insert(verifier.crs_epoch(), verifier);
+    }
+
+    /// Accept only epochs in `accepted`, like after rotating setups.
+    pub fn set_accepted(&mut self, accepted: RangeInclusive<u32>) {
+        self.accepted = accepted;
+    }
+
+    /// Epochs we currently accept.
+    pub fn accepted(&self) -> &RangeInclusive<u32> {
+        &self.accepted
+    }
+
+    /// Select the verifier for `crs_epoch`.
+    ///
+    /// We return an `io::ErrorKind::PermissionDenied` error for epochs
+    /// outside our accepted range, and an `io::ErrorKind::NotFound`
+    /// error for accepted epochs lacking a verifier.
+    pub fn select(&self, crs_epoch: u32) -> SynthesisResult<&VerifierCache<E>> {
+        if ! self.accepted.contains(&crs_epoch) {
+            return Err(SynthesisError::IoError(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "CRS epoch lies outside the accepted range."
+            )));
+        }
+        self.verifiers.get(&crs_epoch).ok_or_else( || SynthesisError::IoError(io::Error::new(
+            io::ErrorKind::NotFound,
+            "No verifying key for this CRS epoch."
+        )) )
+    }
+
+    /// Verify a proof made under the CRS of `crs_epoch`, like
+    /// `VerifierCache::ring_vrf_verify`, once `select` succeeds.
+    pub fn ring_vrf_verify<T>(
+        &self,
+        crs_epoch: u32,
+        auth_root: &RingRoot<E>,
+        vrf_inout: VRFInOut<E>,
+        extra: T,
+        zkproof: Proof<E>,
+    ) -> SynthesisResult<bool> 
+    where T: SigningTranscript, 
+    {
+        self.select(crs_epoch) ?.ring_vrf_verify(auth_root, vrf_inout, extra, zkproof)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use ff::{Field, PrimeField};
+    use pairing::bls12_381::{Bls12, Fr};
+
+    use super::*;
+    use crate::{
+        testing, signing_context, vrf::no_extra, SecretKey, RingSecretCopath,
+        RingSRS, SignedVrfBundle, write_crs_with_epoch, read_crs_with_epoch,
+    };
+
+    #[test]
+    fn subgroup_cache() {
+        let sk = crate::SecretKey::<Bls12>::from_rng(::rand_core::OsRng);
+        let input = VRFInput::<Bls12>::new_malleable(crate::signing_context(b"subgroup").bytes(b"meow"));
+        let preout = input.to_preout(&sk);
+        let cache = SubgroupCache::<Bls12>::new(2);
+        assert!(! cache.contains(&preout));
+        assert!(cache.check(&preout));
+        assert!(cache.contains(&preout));
+        assert!(cache.check(&preout));
+        assert_eq!(cache.len(), 1);
+
+        // (0, -1) has order two.
+        let torsion = VRFPreOut::<Bls12>::read((-Fr::one()).to_repr().as_ref()).unwrap();
+        let tainted = VRFPreOut(preout.as_point().add(torsion.as_point(), Bls12::params()));
+        assert!(! cache.check(&tainted));
+        assert!(! cache.check(&tainted));
+        assert!(! cache.contains(&tainted));
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn test_root_allowlist() {
+        let mut rng = ::rand_core::OsRng;
+        let fx = testing::RingFixture::shared();
+        let depth = fx.depth;
+        let cache = VerifierCache::new(&fx.params.vk);
+        let srs = fx.srs();
+
+        let (sk, copath, auth_root) = fx.signer();
+        let other_root = RingSecretCopath::random(depth, &mut rng).to_root(&sk.to_public());
+
+        let t = signing_context(b"allowlist").bytes(b"meow");
+        let (vrf_inout, proof) = sk.ring_vrf_sign_simple(VRFInput::new_malleable(t), copath, srs).unwrap();
+        assert!(cache.ring_vrf_verify(&auth_root, vrf_inout.clone(), no_extra(), proof.clone()).unwrap());
+
+        let allowed = ::std::iter::once(auth_root.clone()).collect();
+        let cache = cache.with_allowed_roots(allowed);
+        assert!(cache.is_allowed(&auth_root) && !cache.is_allowed(&other_root));
+        assert!(cache.ring_vrf_verify(&auth_root, vrf_inout.clone(), no_extra(), proof.clone()).unwrap());
+        // Unknown roots fail with an error, not merely `Ok(false)` from the pairing check.
+        match cache.ring_vrf_verify(&other_root, vrf_inout, no_extra(), proof) {
+            Err(bellman::SynthesisError::IoError(e)) => assert_eq!(e.kind(), ::std::io::ErrorKind::PermissionDenied),
+            _ => panic!("verifier accepted a ring root outside its allowlist"),
+        }
+    }
+
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn test_verifier_metrics() {
+        let mut rng = ::rand_core::OsRng;
+        let fx = testing::RingFixture::shared();
+        let depth = fx.depth;
+        let cache = VerifierCache::new(&fx.params.vk);
+        let srs = fx.srs();
+
+        let (sk, copath, auth_root) = fx.signer();
+        let other_root = RingSecretCopath::random(depth, &mut rng).to_root(&sk.to_public());
+        let t = signing_context(b"metrics").bytes(b"meow");
+        let (vrf_inout, proof) = sk.ring_vrf_sign_simple(VRFInput::new_malleable(t), copath, srs).unwrap();
+
+        let before = cache.metrics().snapshot();
+        for _ in 0..3 {
+            assert!(cache.ring_vrf_verify(&auth_root, vrf_inout.clone(), no_extra(), proof.clone()).unwrap());
+        }
+        assert!(!cache.ring_vrf_verify(&other_root, vrf_inout.clone(), no_extra(), proof.clone()).unwrap());
+        let cache = cache.with_allowed_roots(::std::iter::once(auth_root.clone()).collect());
+        assert!(cache.ring_vrf_verify(&other_root, vrf_inout.clone(), no_extra(), proof.clone()).is_err());
+
+        let after = cache.metrics().snapshot();
+        assert_eq!(after.verified - before.verified, 3);
+        assert_eq!(after.invalid_proof - before.invalid_proof, 1);
+        assert_eq!(after.unknown_root - before.unknown_root, 1);
+        assert_eq!(after.error, before.error);
+        assert_eq!(after.rejected() - before.rejected(), 2);
+        assert!(after.average_latency > ::std::time::Duration::from_nanos(0));
+        assert_eq!(after.batches, before.batches);
+
+        let batch = vec![
+            (auth_root.clone(), vrf_inout.clone(), no_extra(), proof.clone()),
+            (other_root.clone(), vrf_inout.clone(), no_extra(), proof.clone()),
+            (auth_root, vrf_inout, no_extra(), proof),
+        ];
+        let results = cache.ring_vrf_verify_batch(batch);
+        assert!(*results[0].as_ref().unwrap());
+        assert!(results[1].is_err());
+        assert!(*results[2].as_ref().unwrap());
+        let batched = cache.metrics().snapshot();
+        assert_eq!(batched.batches - after.batches, 1);
+        assert_eq!(batched.batched_proofs - after.batched_proofs, 3);
+        assert_eq!(batched.average_batch_size(), 3);
+        assert_eq!(batched.verified - after.verified, 2);
+        assert_eq!(batched.unknown_root - after.unknown_root, 1);
+    }
+
+    #[test]
+    fn test_verify_stream() {
+        let fx = testing::RingFixture::shared();
+        let srs = fx.srs();
+
+        let (sk, copath, auth_root) = fx.signer();
+
+        let items = (0..5u8).map( |i| {
+            let input = VRFInput::<Bls12>::new_malleable(signing_context(b"stream").bytes(&[i]));
+            let (inout, proof) = sk.ring_vrf_sign_first(input, no_extra(), copath.clone(), srs).unwrap();
+            // Claim another input for odd items, invalidating their proofs.
+            let inout = if i % 2 == 1 {
+                VRFInput::<Bls12>::new_malleable(signing_context(b"stream").bytes(b"other")).to_inout(&sk)
+            } else { inout };
+            (inout, no_extra(), proof)
+        } );
+        let results = ring_vrf_verify_stream(items, &auth_root, &fx.params.vk).collect::<Vec<_>>();
+        assert_eq!(results.len(), 5);
+        for (i,r) in results.iter().enumerate() {
+            assert_eq!(r.is_ok(), i % 2 == 0);
+        }
+    }
+
+    #[test]
+    fn test_verify_proofs_batch() {
+        let mut rng = ::rand_core::OsRng;
+        let fx = testing::RingFixture::shared();
+        let (sk, copath, auth_root) = fx.signer();
+
+        // More than one stream batch, so the stream must refill.
+        let signed = (0..(STREAM_BATCH as u8 + 2)).map( |i| {
+            let input = VRFInput::<Bls12>::new_malleable(signing_context(b"batch").bytes(&[i]));
+            let (inout, proof) = sk.ring_vrf_sign_first(input, no_extra(), copath.clone(), fx.srs()).unwrap();
+            let public_input = ring_vrf_debug_public_inputs(&inout, no_extra(), &auth_root);
+            (inout, proof, public_input)
+        } ).collect::<Vec<_>>();
+        let mut batch = |signed: &[(VRFInOut<Bls12>, Proof<Bls12>, Vec<Fr>)]| verify_proofs_batch(
+            &fx.params.vk, signed.iter().map( |(_, proof, input)| (proof, &input[..]) ), &mut rng
+        );
+        assert!(batch(&signed));
+        assert!(batch(&[]));
+
+        // One wrong public input fails the whole batch, but the stream
+        // still rejects only that item.
+        let mut tampered = signed.clone();
+        tampered[3].2[4] = Fr::one();
+        assert!(! batch(&tampered));
+        let mut items = signed.iter().cloned().map( |(inout, proof, _)| (inout, no_extra(), proof) ).collect::<Vec<_>>();
+        items[3].0 = VRFInput::<Bls12>::new_malleable(signing_context(b"batch").bytes(b"other")).to_inout(&sk);
+        let results = ring_vrf_verify_stream(items, &auth_root, &fx.params.vk).collect::<Vec<_>>();
+        assert_eq!(results.len(), signed.len());
+        for (i,r) in results.iter().enumerate() {
+            assert_eq!(r.is_ok(), i != 3);
+        }
+    }
+
+    #[test]
+    fn test_verify_members() {
+        let mut rng = ::rand_core::OsRng;
+        let fx = testing::RingFixture::shared();
+        let (depth, srs) = (fx.depth, fx.srs());
+
+        let sks = (0..3).map( |_| SecretKey::<Bls12>::from_rng(&mut rng) ).collect::<Vec<_>>();
+        let members = sks.iter().map( |sk| sk.to_public() ).collect::<Vec<_>>();
+        let (copath, auth_root) = RingSecretCopath::from_publickeys(&members, 1, depth as usize);
+        let input = || VRFInput::<Bls12>::new_malleable(signing_context(b"members").bytes(b"meow"));
+
+        let (inout, proof) = sks[1].ring_vrf_sign_first(input(), no_extra(), copath, srs).unwrap();
+        assert!(auth_root.ring_vrf_verify(inout.clone(), no_extra(), proof.clone(), &fx.pvk).unwrap());
+        let verified = ring_vrf_verify_members(inout.clone(), no_extra(), &members, depth, proof.clone(), &fx.pvk).unwrap();
+        assert!(verified.output.as_point() == inout.output.as_point());
+
+        let mut reordered = members.clone();
+        reordered.swap(0, 2);
+        assert!(ring_vrf_verify_members(inout.clone(), no_extra(), &reordered, depth, proof.clone(), &fx.pvk).is_err());
+        assert!(ring_vrf_verify_members(inout, no_extra(), &members[..1], depth, proof, &fx.pvk).is_err());
+    }
+
+    /// Fixture for rejection tests, with a proof and a copy whose output
+    /// we move outside the prime order subgroup.
+    fn tainted_proof(fx: &testing::RingFixture<Bls12>) -> (VRFInOut<Bls12>, VRFInOut<Bls12>, Proof<Bls12>) {
+        let (sk, copath, _) = fx.signer();
+        let input = VRFInput::<Bls12>::new_malleable(signing_context(b"timing").bytes(b"meow"));
+        let (inout, proof) = sk.ring_vrf_sign_first(input, no_extra(), copath, fx.srs()).unwrap();
+
+        let torsion = VRFPreOut::<Bls12>::read((-Fr::one()).to_repr().as_ref()).unwrap();
+        let mut tainted = inout.clone();
+        tainted.output = VRFPreOut(tainted.output.as_point().add(torsion.as_point(), Bls12::params()));
+        (inout, tainted, proof)
+    }
+
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn test_rejection_reaches_pairing() {
+        let fx = testing::RingFixture::shared();
+        let (inout, tainted, proof) = tainted_proof(&fx);
+        let cache = VerifierCache::new(&fx.params.vk);
+
+        // Outputs outside the subgroup still pay for the pairing check.
+        assert!(cache.ring_vrf_verify(&fx.auth_root, inout, no_extra(), proof.clone()).unwrap());
+        assert_eq!(cache.metrics().snapshot().pairing_checks, 1);
+        assert!(cache.ring_vrf_verify(&fx.auth_root, tainted, no_extra(), proof).is_err());
+        let snapshot = cache.metrics().snapshot();
+        assert_eq!(snapshot.pairing_checks, 2);
+        assert_eq!(snapshot.error, 1);
+    }
+
+    /// Benchmark comparing valid and rejected verification times, which
+    /// wall clock noise makes too flaky for CI, so run with `--ignored`.
+    #[test]
+    #[ignore]
+    fn test_rejection_timing_uniform() {
+        use std::time::{Duration, Instant};
+
+        let fx = testing::RingFixture::shared();
+        let (inout, tainted, proof) = tainted_proof(&fx);
+
+        // We use a fresh cache for every verification, since the subgroup
+        // cache would otherwise remember only the valid output.
+        let time = |inout: &VRFInOut<Bls12>| (0..5).map( |_| {
+            let cache = VerifierCache::new(&fx.params.vk);
+            let start = Instant::now();
+            let _ = cache.ring_vrf_verify(&fx.auth_root, inout.clone(), no_extra(), proof.clone());
+            start.elapsed()
+        } ).min().unwrap_or(Duration::from_secs(0));
+        let (valid, invalid) = (time(&inout), time(&tainted));
+        // Both paths run the pairing check, which dominates, so we allow generous slack.
+        assert!(invalid * 2 > valid && valid * 2 > invalid, "valid {:?} invalid {:?}", valid, invalid);
+    }
+
+    #[test]
+    fn test_verify_expected() {
+        let mut rng = ::rand_core::OsRng;
+        let fx = testing::RingFixture::shared();
+        let srs = fx.srs();
+
+        let (sk, copath, auth_root) = fx.signer();
+        let input = || VRFInput::<Bls12>::new_malleable(signing_context(b"expected").bytes(b"meow"));
+        let (_, proof) = sk.ring_vrf_sign_first(input(), no_extra(), copath, srs).unwrap();
+
+        // We stand in for a verifier who derives the output from shared
+        // data, so the output never crosses any channel.
+        let expected = input().to_preout(&sk);
+        let inout = ring_vrf_verify_expected(input(), &expected, no_extra(), &auth_root, proof.clone(), &fx.pvk).unwrap();
+        assert!(inout.output.as_point() == expected.as_point());
+
+        let other = input().to_preout(&SecretKey::<Bls12>::from_rng(&mut rng));
+        assert!(ring_vrf_verify_expected(input(), &other, no_extra(), &auth_root, proof, &fx.pvk).is_err());
+    }
+
+    #[test]
+    fn test_circuit_versions() {
+        let mut rng = ::rand_core::OsRng;
+        let fx = testing::RingFixture::shared();
+        let depth = fx.depth;
+        let srs_v2 = crate::generate_crs_domain::<Bls12>(depth, b"app").expect("can't generate SRS");
+        let v1 = VerifierCache::new(&fx.params.vk);
+        let v2 = VerifierCache::new(&srs_v2.vk).with_circuit_version(CircuitVersion::V2(b"app".to_vec()));
+        assert_eq!(v1.circuit_version(), &CircuitVersion::V1);
+
+        let (sk, copath, auth_root) = fx.signer();
+        let input = || VRFInput::<Bls12>::new_malleable(signing_context(b"versions").bytes(b"meow"));
+        let inout = input().to_inout(&sk);
+        let proof_v1 = sk.ring_vrf_prove(input(), no_extra(), copath.clone(), fx.srs(), &mut rng).unwrap();
+        let proof_v2 = sk.ring_vrf_prove_in_domain(input(), no_extra(), copath, b"app", RingSRS { srs: &srs_v2, depth }, &mut rng).unwrap();
+
+        assert!(v1.ring_vrf_verify(&auth_root, inout.clone(), no_extra(), proof_v1.clone()).unwrap());
+        assert!(v2.ring_vrf_verify(&auth_root, inout.clone(), no_extra(), proof_v2.clone()).unwrap());
+        assert!(! v2.ring_vrf_verify(&auth_root, inout.clone(), no_extra(), proof_v1.clone()).unwrap());
+        assert!(! v1.ring_vrf_verify(&auth_root, inout.clone(), no_extra(), proof_v2).unwrap());
+
+        // A v1 verifying key mistagged as v2 assembles too many inputs.
+        let mistagged = VerifierCache::new(&fx.params.vk).with_circuit_version(CircuitVersion::V2(b"app".to_vec()));
+        assert!(! mistagged.ring_vrf_verify(&auth_root, inout, no_extra(), proof_v1).unwrap());
+    }
+
+    #[test]
+    fn test_verify_fresh() {
+        let mut rng = ::rand_core::OsRng;
+        let fx = testing::RingFixture::shared();
+        let (sk, copath, auth_root) = fx.signer();
+        let input = || VRFInput::<Bls12>::new_malleable(signing_context(b"fresh").bytes(b"meow"));
+        let inout = input().to_inout(&sk);
+        let proof = sk.ring_vrf_prove_with_nonce(input(), no_extra(), b"nonce 1", copath, fx.srs(), &mut rng).unwrap();
+
+        let mut seen = HashSet::new();
+        // The nonce is bound into the proof.
+        assert!(! auth_root.ring_vrf_verify_fresh(inout.clone(), no_extra(), b"nonce 2", &mut seen, proof.clone(), &fx.pvk).unwrap());
+        assert!(seen.is_empty());
+
+        assert!(auth_root.ring_vrf_verify_fresh(inout.clone(), no_extra(), b"nonce 1", &mut seen, proof.clone(), &fx.pvk).unwrap());
+        assert!(seen.contains(&b"nonce 1"[..]));
+        match auth_root.ring_vrf_verify_fresh(inout, no_extra(), b"nonce 1", &mut seen, proof, &fx.pvk) {
+            Err(bellman::SynthesisError::IoError(e)) => assert_eq!(e.kind(), ::std::io::ErrorKind::AlreadyExists),
+            _ => panic!("Replayed nonce should fail"),
+        }
+    }
+
+    #[test]
+    fn test_crs_epochs() {
+        let fx = testing::RingFixture::shared();
+        let new_srs = crate::generate_crs::<Bls12>(fx.depth).expect("can't generate SRS");
+
+        // Epochs travel in the CRS header.
+        let mut bytes = Vec::new();
+        write_crs_with_epoch(&new_srs, 2, &mut bytes).unwrap();
+        let (epoch, read) = read_crs_with_epoch::<Bls12,_>(&bytes[..], false).unwrap();
+        assert_eq!(epoch, 2);
+
+        let mut verifiers = EpochVerifiers::new(1..=2);
+        verifiers.insert(VerifierCache::new(&fx.params.vk).with_crs_epoch(1));
+        verifiers.insert(VerifierCache::new(&read.vk).with_crs_epoch(epoch));
+
+        let (sk, copath, auth_root) = fx.signer();
+        let input = || VRFInput::<Bls12>::new_malleable(signing_context(b"epochs").bytes(b"meow"));
+        let (inout, old_proof) = sk.ring_vrf_sign_first(input(), no_extra(), copath.clone(), fx.srs()).unwrap();
+        let (_, new_proof) = sk.ring_vrf_sign_first(input(), no_extra(), copath, RingSRS { srs: &new_srs, depth: fx.depth }).unwrap();
+
+        assert!(verifiers.ring_vrf_verify(1, &auth_root, inout.clone(), no_extra(), old_proof.clone()).unwrap());
+        assert!(verifiers.ring_vrf_verify(2, &auth_root, inout.clone(), no_extra(), new_proof.clone()).unwrap());
+        assert!(! verifiers.ring_vrf_verify(2, &auth_root, inout.clone(), no_extra(), old_proof.clone()).unwrap());
+
+        // Retire epoch 1.
+        verifiers.set_accepted(2..=2);
+        match verifiers.ring_vrf_verify(1, &auth_root, inout.clone(), no_extra(), old_proof) {
+            Err(bellman::SynthesisError::IoError(e)) => assert_eq!(e.kind(), ::std::io::ErrorKind::PermissionDenied),
+            _ => panic!("Retired epochs should fail"),
+        }
+        assert!(verifiers.ring_vrf_verify(2, &auth_root, inout, no_extra(), new_proof).unwrap());
+        assert!(verifiers.select(3).is_err());
+    }
+
+    #[test]
+    fn test_verify_lottery() {
+        let fx = testing::RingFixture::shared();
+        let srs = fx.srs();
+
+        let (sk, copath, auth_root) = fx.signer();
+        let input = VRFInput::<Bls12>::new_malleable(signing_context(b"lottery").bytes(b"slot"));
+        let (inout, proof) = sk.ring_vrf_sign_first(input, no_extra(), copath, srs).unwrap();
+
+        let lottery = |inout: &VRFInOut<Bls12>, threshold| auth_root.verify_lottery(
+            inout.clone(), no_extra(), proof.clone(), &fx.pvk, b"lottery", threshold
+        );
+        match lottery(&inout, &[0xff; 32]) {
+            LotteryResult::Won(won) => assert!(won.output.as_point() == inout.output.as_point()),
+            r => panic!("expected to win, got {:?}", r),
+        }
+        assert!(matches!(lottery(&inout, &[0; 32]), LotteryResult::LostLottery));
+        let other = VRFInput::<Bls12>::new_malleable(signing_context(b"lottery").bytes(b"other")).to_inout(&sk);
+        assert!(matches!(lottery(&other, &[0xff; 32]), LotteryResult::Invalid(_)));
+    }
+
+    #[test]
+    fn test_verify_bytes() {
+        let fx = testing::RingFixture::shared();
+        let srs = fx.srs();
+
+        let (sk, copath, auth_root) = fx.signer();
+        let input = || VRFInput::<Bls12>::new_malleable(signing_context(b"bytes").bytes(b"meow"));
+        let (inout, proof) = sk.ring_vrf_sign_first(input(), no_extra(), copath, srs).unwrap();
+
+        let mut bytes = [0u8; 32];
+        inout.output.write(&mut bytes[..]).unwrap();
+        let output = ring_vrf_verify_bytes(input(), &bytes, no_extra(), &auth_root, proof.clone(), &fx.pvk, b"ctx").unwrap();
+        assert!(auth_root.ring_vrf_verify(inout.clone(), no_extra(), proof.clone(), &fx.pvk).unwrap());
+        assert_eq!(output, inout.make_bytes::<[u8; 32]>(b"ctx"));
+
+        // Find some bytes which encode no point.
+        let mut malformed = [0xffu8; 32];
+        while VRFPreOut::<Bls12>::read(&malformed[..]).is_ok() {  malformed[0] = malformed[0].wrapping_sub(1);  }
+        assert!(ring_vrf_verify_bytes(input(), &malformed, no_extra(), &auth_root, proof, &fx.pvk, b"ctx").is_err());
+    }
+
+    #[test]
+    fn test_no_extra_distinct() {
+        let fx = testing::RingFixture::shared();
+        let srs = fx.srs();
+
+        let (sk, copath, auth_root) = fx.signer();
+        let input = VRFInput::<Bls12>::new_malleable(signing_context(b"extra").bytes(b"meow"));
+        let (inout, proof) = sk.ring_vrf_sign_first(input, no_extra(), copath, srs).unwrap();
+
+        assert!(auth_root.ring_vrf_verify(inout.clone(), no_extra(), proof.clone(), &fx.pvk).unwrap());
+        assert!(! auth_root.ring_vrf_verify(inout, signing_context(b"").bytes(b""), proof, &fx.pvk).unwrap());
+    }
+
+    #[test]
+    fn test_verify_within_roots() {
+        let fx = testing::RingFixture::shared();
+        let depth = fx.depth;
+        let cache = VerifierCache::new(&fx.params.vk);
+        let srs = fx.srs();
+
+        let (sk, copath, _) = fx.signer();
+        let input = VRFInput::<Bls12>::new_malleable(signing_context(b"window").bytes(b"meow"));
+        let (inout, proof) = sk.ring_vrf_sign_first(input, no_extra(), copath.clone(), srs).unwrap();
+
+        let other = || RingSecretCopath::<Bls12>::random(depth, &mut ::rand_core::OsRng).to_root(&sk.to_public());
+        let roots = [other(), copath.to_root(&sk.to_public()), other()];
+        let (_, i) = cache.verify_within_roots(inout.clone(), no_extra(), proof.clone(), &roots).unwrap();
+        assert_eq!(i, 1);
+        assert!(cache.verify_within_roots(inout.clone(), no_extra(), proof.clone(), &[roots[0].clone(), roots[2].clone()]).is_err());
+        assert!(cache.subgroup_cache().contains(&inout.output));
+
+        // Outputs with a torsion component fail before any pairing.
+        let torsion = VRFPreOut::<Bls12>::read((-Fr::one()).to_repr().as_ref()).unwrap();
+        let mut tainted = inout;
+        tainted.output = VRFPreOut(tainted.output.as_point().add(torsion.as_point(), Bls12::params()));
+        assert!(cache.verify_within_roots(tainted.clone(), no_extra(), proof, &roots).is_err());
+        assert!(! cache.subgroup_cache().contains(&tainted.output));
+    }
+
+    #[test]
+    fn test_verify_report() {
+        let fx = testing::RingFixture::shared();
+        let cache = VerifierCache::new(&fx.params.vk);
+
+        let (sk, copath, auth_root) = fx.signer();
+        let input = || VRFInput::<Bls12>::new_malleable(signing_context(b"report").bytes(b"meow"));
+        let (inout, proof) = sk.ring_vrf_sign_first(input(), no_extra(), copath, fx.srs()).unwrap();
+        let mut output = [0u8; 32];
+        inout.output.write(&mut output[..]).unwrap();
+        let stage = |cache: &VerifierCache<Bls12>, output: &[u8; 32], extra| {
+            cache.verify_report(&auth_root, input(), output, extra, proof.clone()).unwrap_err().stage
+        };
+
+        let verified = cache.verify_report(&auth_root, input(), &output, no_extra(), proof.clone()).unwrap();
+        assert!(verified.output.as_point() == inout.output.as_point());
+
+        let allowlisted = VerifierCache::new(&fx.params.vk).with_allowed_roots(HashSet::new());
+        assert_eq!(stage(&allowlisted, &output, no_extra()), VerifyStage::UnknownRoot);
+
+        // The y coordinate 2 has no x coordinate on Jubjub.
+        let mut undecodable = [0u8; 32];
+        undecodable[0] = 2;
+        assert_eq!(stage(&cache, &undecodable, no_extra()), VerifyStage::PublicInputDecode);
+
+        let torsion = VRFPreOut::<Bls12>::read((-Fr::one()).to_repr().as_ref()).unwrap();
+        let mut tainted = [0u8; 32];
+        VRFPreOut(inout.output.as_point().add(torsion.as_point(), Bls12::params())).write(&mut tainted[..]).unwrap();
+        assert_eq!(stage(&cache, &tainted, no_extra()), VerifyStage::SubgroupCheck);
+
+        let mistagged = VerifierCache::new(&fx.params.vk).with_circuit_version(CircuitVersion::V2(b"app".to_vec()));
+        assert_eq!(stage(&mistagged, &output, no_extra()), VerifyStage::VkMismatch);
+
+        assert_eq!(stage(&cache, &output, signing_context(b"other").bytes(b"")), VerifyStage::PairingCheck);
+    }
+
+    #[test]
+    fn test_verify_strict() {
+        let mut rng = ::rand_core::OsRng;
+        let fx = testing::RingFixture::shared();
+        let depth = fx.depth;
+        let cache = VerifierCache::new(&fx.params.vk);
+
+        let (sk, copath, auth_root) = fx.signer();
+        let input = || VRFInput::<Bls12>::new_malleable(signing_context(b"strict").bytes(b"meow"));
+        let (inout, proof) = sk.ring_vrf_sign_first(input(), no_extra(), copath, fx.srs()).unwrap();
+        let bundle = SignedVrfBundle { preout: inout.output.clone(), auth_root: auth_root.clone(), proof };
+
+        let verified = cache.verify_strict(&auth_root, input(), &bundle, no_extra()).unwrap();
+        assert!(verified.output.as_point() == inout.output.as_point());
+
+        let kind = |r: SynthesisResult<VRFInOut<Bls12>>| match r {
+            Err(bellman::SynthesisError::IoError(e)) => e.kind(),
+            _ => panic!("expected an io error"),
+        };
+        let stale = RingSecretCopath::<Bls12>::random(depth, &mut rng).to_root(&sk.to_public());
+        assert_eq!(kind(cache.verify_strict(&stale, input(), &bundle, no_extra())), std::io::ErrorKind::InvalidInput);
+        assert_eq!(kind(cache.verify_strict(&auth_root, input(), &bundle, signing_context(b"other").bytes(b""))), std::io::ErrorKind::InvalidData);
+    }
+}
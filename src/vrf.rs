@@ -14,22 +14,44 @@
 //! certificates instead of HDKD when using VRFs.
 
 use std::io;
+use core::fmt;
 
 use rand_core::{RngCore,CryptoRng,SeedableRng}; // OsRng
 
 use merlin::Transcript;
 
+use subtle::{Choice,ConstantTimeEq,ConstantTimeGreater};
+
 use ff::PrimeField;
 use zcash_primitives::jubjub::{JubjubEngine, edwards::Point, PrimeOrder, Unknown};
 
-use crate::{JubjubEngineWithParams, ReadWrite, SigningTranscript, Scalar};  // use super::*;
+use crate::{JubjubEngineWithParams, ReadWrite, SigningTranscript, SignatureResult, signature_error, clear_cofactor};  // use super::*;
 
 
 /// VRF input, always created locally from a `SigningTranscript`.
 ///
 /// All creation methods require the developer acknoledge their VRF output malleability.
+///
+/// We clear the cofactor once when creating the input, and only ever
+/// store the resulting prime order point, so proving or evaluating the
+/// same input repeatedly never redoes the cofactor multiplication.
 #[derive(Debug, Clone)] // PartialEq, Eq
-pub struct VRFInput<E: JubjubEngine>(Point<E, PrimeOrder>);
+pub struct VRFInput<E: JubjubEngine>(Point<E, PrimeOrder>, Malleability);
+
+/// Records how a `VRFInput` was derived, so that ring VRF proving can
+/// reject non-malleable inputs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Malleability {
+    Malleable,
+    NonMalleable,
+}
+
+/// Fork `t` for one item of `VRFInput::derive_many`.
+fn fork_item<T: SigningTranscript+Clone>(t: &T, item: &[u8]) -> T {
+    let mut t = t.clone();
+    t.commit_bytes(b"vrf-item", item);
+    t
+}
 
 impl<E: JubjubEngineWithParams> VRFInput<E> {
     pub(crate) fn as_point(&self) -> &Point<E, PrimeOrder> { &self.0 }
@@ -38,7 +60,7 @@ impl<E: JubjubEngineWithParams> VRFInput<E> {
     #[inline(always)]
     fn from_rng<R: RngCore+CryptoRng>(mut rng: R) -> Self {
         let params = E::params();
-        VRFInput( Point::rand(&mut rng, params).mul_by_cofactor(params) )
+        VRFInput( clear_cofactor(&Point::rand(&mut rng, params)), Malleability::Malleable )
     }
 
     /// Acknoledge VRF transcript malleablity
@@ -53,6 +75,49 @@ impl<E: JubjubEngineWithParams> VRFInput<E> {
         VRFInput::from_rng(rng)
     }
 
+    /// Derive one malleable VRF input per item, each from a fork of
+    /// `base_transcript` with the item committed.
+    pub fn derive_many<T,I>(base_transcript: T, per_item: I) -> Vec<VRFInput<E>>
+    where T: SigningTranscript+Clone, I: IntoIterator, I::Item: AsRef<[u8]>,
+    {
+        per_item.into_iter()
+        .map( |item| VRFInput::new_malleable(fork_item(&base_transcript, item.as_ref())) )
+        .collect()
+    }
+
+    /// Derive one malleable VRF input per item like `derive_many`,
+    /// but run hash-to-curve across the rayon thread pool.
+    ///
+    /// We fork the transcripts sequentially because forking costs little,
+    /// so outputs are identical to `derive_many`.
+    #[cfg(feature = "parallel")]
+    pub fn derive_many_parallel<T,I>(base_transcript: T, per_item: I) -> Vec<VRFInput<E>>
+    where
+        T: SigningTranscript+Clone+Send,
+        I: IntoIterator, I::Item: AsRef<[u8]>,
+        VRFInput<E>: Send,
+    {
+        use rayon::prelude::*;
+        per_item.into_iter()
+        .map( |item| fork_item(&base_transcript, item.as_ref()) )
+        .collect::<Vec<T>>()
+        .into_par_iter()
+        .map( VRFInput::new_malleable )
+        .collect()
+    }
+
+    /// Acknoledge VRF transcript malleablity, but hash to the curve
+    /// using the RFC 9380 construction in `crate::h2c`, which other
+    /// implementations can reproduce from the specification.
+    #[cfg(feature = "h2c")]
+    pub fn new_malleable_h2c<T>(mut t: T) -> VRFInput<E> 
+    where T: SigningTranscript
+    {
+        let mut msg = [0u8; 64];
+        t.challenge_bytes(b"vrf-input-h2c", &mut msg);
+        VRFInput( crate::h2c::hash_to_curve::<E>(&msg, crate::h2c::VRF_INPUT_DST), Malleability::Malleable )
+    }
+
     /// Non-malleable VRF transcript.
     ///
     /// Incompatable with ring VRF however.  We avoid malleability within the
@@ -61,12 +126,30 @@ impl<E: JubjubEngineWithParams> VRFInput<E> {
      -> VRFInput<E>
     where T: SigningTranscript
     {
-        let params = E::params();
-        t.commit_point(b"vrf-nm-pk", &publickey.0.mul_by_cofactor(params));
-        VRFInput::new_malleable(t)
+        t.commit_point(b"vrf-nm-pk", &clear_cofactor(&publickey.0));
+        let VRFInput(p, _) = VRFInput::new_malleable(t);
+        VRFInput(p, Malleability::NonMalleable)
+    }
+
+    /// Returns true if this input was derived by `new_nonmalleable`,
+    /// and hence cannot be used with ring VRF proving.
+    pub fn is_nonmalleable(&self) -> bool {
+        self.1 == Malleability::NonMalleable
+    }
+
+    /// VRF input for nullifiers, which depends only upon the `epoch`.
+    ///
+    /// A ring VRF output on this input is deterministic per secret key
+    /// and epoch, but reveals nothing else about the key, see
+    /// `SecretKey::prove_membership_with_nullifier`.
+    pub fn new_nullifier(epoch: &[u8]) -> VRFInput<E> {
+        VRFInput::new_malleable(crate::signing_context(b"ring-vrf-nullifier").bytes(epoch))
     }
 
     /// Semi-malleable VRF transcript
+    ///
+    /// We commit the ring root as its canonical 32 byte little endian
+    /// encoding, identical to `RingRoot::write`, which consensus relies upon.
     pub fn new_ring_malleable<T>(mut t: T, auth_root: &crate::merkle::RingRoot<E>)
      -> VRFInput<E>
     where T: SigningTranscript
@@ -75,8 +158,51 @@ impl<E: JubjubEngineWithParams> VRFInput<E> {
         VRFInput::new_malleable(t)
     }
 
+    /// Semi-malleable VRF transcript bound to one CRS.
+    ///
+    /// We commit the hash of the verifying key, like from
+    /// `verifying_key_hash`, alongside the ring root, so outputs
+    /// cannot be replayed as if proven against another CRS.
+    pub fn new_ring_malleable_bound<T>(mut t: T, auth_root: &crate::merkle::RingRoot<E>, vk_hash: &[u8; 32])
+     -> VRFInput<E>
+    where T: SigningTranscript
+    {
+        t.commit_bytes(b"vrf-vk", vk_hash);
+        VRFInput::new_ring_malleable(t, auth_root)
+    }
+
+    /// Semi-malleable VRF transcript bound to the ring's member count.
+    ///
+    /// We commit `member_count` alongside the ring root, so a proof
+    /// verifies only if the verifier believes the same anonymity set
+    /// size as the prover.  We cannot count populated leaves in-circuit,
+    /// so verifiers should take `member_count` from their own copy of
+    /// the ring, like `RingBuilder::len`, never from the prover.
+    pub fn new_ring_malleable_sized<T>(mut t: T, auth_root: &crate::merkle::RingRoot<E>, member_count: u64)
+     -> VRFInput<E>
+    where T: SigningTranscript
+    {
+        t.commit_bytes(b"vrf-ring-size", &member_count.to_le_bytes());
+        VRFInput::new_ring_malleable(t, auth_root)
+    }
+
     /// Into VRF output.
+    ///
+    /// We multiply by the secret key using uniform operations, because
+    /// adversaries frequently choose VRF inputs, like by choosing the
+    /// messages signed, and then observe our signing time, so a
+    /// variable time scalar multiplication could leak the secret key
+    /// over many evaluations.
     pub fn to_preout(&self, sk: &crate::SecretKey<E>) -> VRFPreOut<E> {
+        VRFPreOut( crate::misc::mul_consttime(&self.0, &sk.key) )
+    }
+
+    /// Into VRF output, using variable time scalar multiplication.
+    ///
+    /// We produce the same output as `to_preout` faster, but our timing
+    /// leaks information about the secret key, so only use this when
+    /// no adversary influences the input or observes our timing.
+    pub fn to_preout_vartime(&self, sk: &crate::SecretKey<E>) -> VRFPreOut<E> {
         let p: Point<E, Unknown> = self.0.clone().into();
         VRFPreOut( p.mul(sk.key.clone(), E::params()) )
     }
@@ -86,15 +212,123 @@ impl<E: JubjubEngineWithParams> VRFInput<E> {
         let output = self.to_preout(sk);
         VRFInOut { input: self.clone(), output }
     }
+
+    /// Blind this input by multiplying by `factor`, for oblivious
+    /// evaluation, see `crate::oblivious`.
+    pub fn blind(&self, factor: &crate::Scalar<E>) -> VRFInput<E> {
+        VRFInput( self.0.mul(factor.clone(), E::params()), self.1 )
+    }
 }
 
 
+/// VRF transcript not yet finalized into a `VRFInput`.
+///
+/// We cannot convert a malleable `VRFInput` into a ring malleable one,
+/// because both hash the transcript to the curve, so instead callers
+/// who have not yet decided hold the transcript here, and finalize a
+/// clone for each variant they require.
+#[derive(Clone)]
+pub struct VRFInputSpec<T: SigningTranscript>(T);
+
+impl<T: SigningTranscript> VRFInputSpec<T> {
+    /// Hold `t` until finalization.
+    pub fn new(t: T) -> VRFInputSpec<T> {
+        VRFInputSpec(t)
+    }
+
+    /// Finalize like `VRFInput::new_malleable`.
+    pub fn finalize_malleable<E: JubjubEngineWithParams>(self) -> VRFInput<E> {
+        VRFInput::new_malleable(self.0)
+    }
+
+    /// Finalize like `VRFInput::new_ring_malleable`.
+    pub fn finalize_ring_malleable<E: JubjubEngineWithParams>(self, auth_root: &crate::merkle::RingRoot<E>) -> VRFInput<E> {
+        VRFInput::new_ring_malleable(self.0, auth_root)
+    }
+}
+
+/// Builder for malleable VRF inputs which refuses to finalize unless
+/// the caller bound a round or nonce, so inputs never repeat across
+/// protocol rounds by accident.
+///
+/// We commit fields and the nonce in the order given, so the result
+/// equals `VRFInput::new_malleable` on a transcript to which you made
+/// the same `commit_bytes` calls, with the nonce committed as
+/// `commit_bytes(b"vrf-nonce", &nonce.to_le_bytes())`.
+pub struct VRFInputBuilder<T: SigningTranscript> {
+    t: T,
+    nonce_bound: bool,
+}
+
+impl<T: SigningTranscript> VRFInputBuilder<T> {
+    /// Start building from `t`, which should already hold the context.
+    pub fn new(t: T) -> VRFInputBuilder<T> {
+        VRFInputBuilder { t, nonce_bound: false }
+    }
+
+    /// Commit an application field.
+    pub fn field(mut self, label: &'static [u8], bytes: &[u8]) -> VRFInputBuilder<T> {
+        self.t.commit_bytes(label, bytes);
+        self
+    }
+
+    /// Commit the round or nonce, which `finalize` requires.
+    pub fn nonce(mut self, nonce: u64) -> VRFInputBuilder<T> {
+        self.t.commit_bytes(b"vrf-nonce", &nonce.to_le_bytes());
+        self.nonce_bound = true;
+        self
+    }
+
+    /// Finalize like `VRFInput::new_malleable`, or return an error
+    /// if we never committed a nonce.
+    pub fn finalize<E: JubjubEngineWithParams>(self) -> SignatureResult<VRFInput<E>> {
+        if ! self.nonce_bound {
+            return Err(signature_error("VRFInputBuilder requires a nonce before finalizing"));
+        }
+        Ok(VRFInput::new_malleable(self.t))
+    }
+}
+
 /// VRF output, possibly unverified.
 #[derive(Debug, Clone)] // Default, PartialEq, Eq, PartialOrd, Ord, Hash
-pub struct VRFPreOut<E: JubjubEngine>(Point<E, Unknown>);
+pub struct VRFPreOut<E: JubjubEngine>(pub(crate) Point<E, Unknown>);
 
 impl<E: JubjubEngineWithParams> VRFPreOut<E> {
-    pub(crate) fn as_point(&self) -> &Point<E, Unknown> { &self.0 }
+    /// JubJub point of this VRF output, for combining with other group
+    /// elements, like in accumulators.
+    ///
+    /// We never check this point lies in the prime order subgroup, so
+    /// multiply by the cofactor if you require that.  You must verify
+    /// the VRF before trusting this point.
+    pub fn as_point(&self) -> &Point<E, Unknown> { &self.0 }
+
+    /// Consume this VRF output, returning its JubJub point, like `as_point`.
+    pub fn into_point(self) -> Point<E, Unknown> { self.0 }
+
+    /// Affine Edwards x-coordinate of this VRF output, in the JubJub base
+    /// field, exactly as the `RingVRF` circuit inputizes it, for feeding
+    /// into other JubJub circuits.
+    ///
+    /// We caution that the x-coordinate determines the point only up to
+    /// sign of y, so both `(x, y)` and `(x, -y)` map to one field element.
+    /// Any circuit consuming only `x` must not rely upon distinguishing
+    /// these, or else must also take the y-coordinate from `as_point`.
+    pub fn x_coordinate(&self) -> E::Fr {
+        self.0.to_xy().0
+    }
+
+    /// Blind this output by multiplying by `factor`, for oblivious
+    /// evaluation, see `crate::oblivious`.
+    pub fn blind(&self, factor: &crate::Scalar<E>) -> VRFPreOut<E> {
+        VRFPreOut( self.0.mul(factor.clone(), E::params()) )
+    }
+
+    /// Remove the blinding `factor` from an output evaluated on an
+    /// input blinded by `factor`, or `None` if `factor` is zero.
+    pub fn unblind(&self, factor: &crate::Scalar<E>) -> Option<VRFPreOut<E>> {
+        let inverse = Option::from(::ff::Field::invert(factor)) ?;
+        Some(self.blind(&inverse))
+    }
 
     /// Acknoledge VRF transcript malleablity
     ///
@@ -125,8 +359,62 @@ impl<E: JubjubEngineWithParams> VRFPreOut<E> {
         let input = VRFInput::new_ring_malleable(t,auth_root);
         VRFInOut { input, output: self.clone() }
     }
+
+    /// Semi-malleable VRF transcript bound to one CRS, see `VRFInput::new_ring_malleable_bound`.
+    pub fn attach_input_ring_malleable_bound<T>(&self, t: T, auth_root: &crate::merkle::RingRoot<E>, vk_hash: &[u8; 32])
+     -> VRFInOut<E>
+    where T: SigningTranscript
+    {
+        let input = VRFInput::new_ring_malleable_bound(t,auth_root,vk_hash);
+        VRFInOut { input, output: self.clone() }
+    }
+
+    /// Semi-malleable VRF transcript bound to the ring's member count, see `VRFInput::new_ring_malleable_sized`.
+    pub fn attach_input_ring_malleable_sized<T>(&self, t: T, auth_root: &crate::merkle::RingRoot<E>, member_count: u64)
+     -> VRFInOut<E>
+    where T: SigningTranscript
+    {
+        let input = VRFInput::new_ring_malleable_sized(t,auth_root,member_count);
+        VRFInOut { input, output: self.clone() }
+    }
+}
+
+
+/// Byte order for interpreting VRF output bytes as an integer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteOrder {
+    /// Least significant byte first, as produced by `make_bytes`.
+    LittleEndian,
+    /// Most significant byte first, as produced by `make_bytes_be`.
+    BigEndian,
 }
 
+/// Role for which a validator derives VRF output bytes, so one VRF
+/// evaluation serves several roles with independent outputs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Role {
+    /// Block production lotteries
+    BlockProduction,
+    /// Parachain validator assignment
+    ParachainAssignment,
+    /// Dispute resolution
+    Disputes,
+    /// Application defined role, never colliding with the roles above
+    Custom(u8),
+}
+
+impl Role {
+    /// Unique two byte tag, whose first byte separates our predefined
+    /// roles from `Role::Custom`.
+    fn tag(&self) -> [u8; 2] {
+        match self {
+            Role::BlockProduction => [0, 0],
+            Role::ParachainAssignment => [0, 1],
+            Role::Disputes => [0, 2],
+            Role::Custom(t) => [1, *t],
+        }
+    }
+}
 
 /// VRF input and output paired together, possibly unverified.
 ///
@@ -145,6 +433,43 @@ impl<E: JubjubEngine> From<VRFInOut<E>> for VRFInput<E> {
 }
 
 impl<E: JubjubEngineWithParams> VRFInOut<E> {
+    /// Split into owned VRF input and output.
+    pub fn split(self) -> (VRFInput<E>, VRFPreOut<E>) {
+        let VRFInOut { input, output } = self;
+        (input, output)
+    }
+
+    /// Borrow VRF input and output.
+    pub fn parts(&self) -> (&VRFInput<E>, &VRFPreOut<E>) {
+        (&self.input, &self.output)
+    }
+
+    /// Check our VRF input equals one the verifier derived independently.
+    ///
+    /// Verifying a proof only shows the output matches the input the
+    /// prover used, so if you derived `expected` from your own transcript
+    /// then call this before trusting `make_bytes`.
+    pub fn assert_input_matches(&self, expected: &VRFInput<E>) -> SignatureResult<()> {
+        if self.input.as_point() == expected.as_point() {
+            Ok(())
+        } else {
+            Err(signature_error("VRF input differs from the independently derived input"))
+        }
+    }
+
+    /// Check our VRF output equals the output `sk` produces on our input.
+    ///
+    /// Signers trust outputs from `to_inout`, but high assurance signers
+    /// may recheck before proving, to catch memory corruption or a buggy
+    /// engine, at the cost of one more scalar multiplication.
+    pub fn assert_consistent_with(&self, sk: &crate::SecretKey<E>) -> SignatureResult<()> {
+        if self.input.to_preout(sk).as_point() == self.output.as_point() {
+            Ok(())
+        } else {
+            Err(signature_error("VRF output differs from the output of the secret key"))
+        }
+    }
+
     /// Write VRF output
     pub fn write_output<W: io::Write>(&self, writer: W) -> io::Result<()> {
         self.output.write(writer)
@@ -160,9 +485,8 @@ impl<E: JubjubEngineWithParams> VRFInOut<E> {
     /// We use this construction both for the VRF usage methods
     /// `VRFInOut::make_*` as well as for signer side batching.
     pub fn commit<T: SigningTranscript>(&self, t: &mut T) {
-        let params = E::params();
-        t.commit_point(b"vrf-in", &self.input.as_point()); // .mul_by_cofactor(&params);
-        t.commit_point(b"vrf-out", &self.output.as_point().mul_by_cofactor(&params));
+        t.commit_point(b"vrf-in", &self.input.as_point()); // clear_cofactor
+        t.commit_point(b"vrf-out", &clear_cofactor(self.output.as_point()));
     }
 
     /// Raw bytes output from the VRF.
@@ -175,6 +499,14 @@ impl<E: JubjubEngineWithParams> VRFInOut<E> {
     /// construction from Theorem 2 on page 32 in appendex C of
     /// ["Ouroboros Praos: An adaptively-secure, semi-synchronous proof-of-stake blockchain"](https://eprint.iacr.org/2017/573.pdf)
     /// by Bernardo David, Peter Gazi, Aggelos Kiayias, and Alexander Russell.
+    ///
+    /// We return merlin's challenge bytes in the order merlin produces
+    /// them, which integrators should treat as a little endian integer.
+    /// These bytes never depend upon the target's endianness or word size,
+    /// because merlin, point serialization, and `PrimeField::Repr` all
+    /// work with byte arrays of fixed order.
+    /// Use `make_bytes_be` or `make_bytes_with_order` if your consensus
+    /// rules compare big endian integers instead.
     pub fn make_bytes<B: Default + AsMut<[u8]>>(&self, context: &[u8]) -> B {
         let mut t = ::merlin::Transcript::new(b"VRFResult");
         t.append_message(b"",context);
@@ -184,6 +516,110 @@ impl<E: JubjubEngineWithParams> VRFInOut<E> {
         seed
     }
 
+    /// Raw bytes output from the VRF, derived with BLAKE3 in key
+    /// derivation mode, which differ entirely from `make_bytes`, so
+    /// all parties must agree upon which they use.
+    ///
+    /// We length prefix `context` and hash both points exactly like
+    /// `commit`, so BLAKE3 provides the same domain separation as merlin.
+    ///
+    /// If you are not the signer then you must verify the VRF before calling this method.
+    #[cfg(feature = "fast_transcript")]
+    pub fn make_bytes_blake3<B: Default + AsMut<[u8]>>(&self, context: &[u8]) -> B {
+        let mut point = [0u8; 32];
+        let mut h = ::blake3::Hasher::new_derive_key("ring-vrf 2020 VRFResult");
+        h.update(&(context.len() as u64).to_le_bytes());
+        h.update(context);
+        self.input.as_point().write(&mut point[..]).expect("Jubjub points encode as 32 bytes.");
+        h.update(&point);
+        clear_cofactor(self.output.as_point()).write(&mut point[..]).expect("Jubjub points encode as 32 bytes.");
+        h.update(&point);
+        let mut seed = B::default();
+        h.finalize_xof().fill(seed.as_mut());
+        seed
+    }
+
+    /// Raw bytes output from the VRF, encoding the same integer as
+    /// `make_bytes` but in big endian byte order.
+    ///
+    /// If you are not the signer then you must verify the VRF before calling this method.
+    pub fn make_bytes_be<B: Default + AsMut<[u8]>>(&self, context: &[u8]) -> B {
+        self.make_bytes_with_order(context, ByteOrder::BigEndian)
+    }
+
+    /// Raw bytes output from the VRF, encoding the integer given by
+    /// `make_bytes` in the requested byte order.
+    ///
+    /// If you are not the signer then you must verify the VRF before calling this method.
+    pub fn make_bytes_with_order<B: Default + AsMut<[u8]>>(&self, context: &[u8], order: ByteOrder) -> B {
+        let mut bytes = self.make_bytes::<B>(context);
+        if order == ByteOrder::BigEndian {
+            bytes.as_mut().reverse();
+        }
+        bytes
+    }
+
+    /// Raw bytes output from the VRF for one `role`.
+    ///
+    /// We commit the role and `context` as separate transcript
+    /// messages, so different roles yield independent outputs even
+    /// with identical `context`, and never collide with `make_bytes`.
+    ///
+    /// If you are not the signer then you must verify the VRF before calling this method.
+    pub fn make_bytes_for_role<B: Default + AsMut<[u8]>>(&self, role: Role, context: &[u8]) -> B {
+        let mut t = ::merlin::Transcript::new(b"VRFRoleResult");
+        t.append_message(b"role", &role.tag());
+        t.append_message(b"context", context);
+        self.commit(&mut t);
+        let mut seed = B::default();
+        t.challenge_bytes(b"", seed.as_mut());
+        seed
+    }
+
+    /// Compare the raw `make_bytes` output for `context` against
+    /// `threshold` in constant time, reading both as little endian
+    /// integers, like `make_bytes` documents.
+    ///
+    /// We return true if the output exceeds the threshold, so lottery
+    /// participants win whenever this returns false.  We suggest
+    /// `threshold_from_probability` for computing thresholds.
+    ///
+    /// If you are not the signer then you must verify the VRF before calling this method.
+    pub fn exceeds_threshold(&self, context: &[u8], threshold: &[u8; 32]) -> bool {
+        let bytes = self.make_bytes::<[u8; 32]>(context);
+        let mut gt = Choice::from(0u8);
+        let mut eq = Choice::from(1u8);
+        for (a,b) in bytes.iter().zip(threshold.iter()).rev() {
+            gt |= eq & a.ct_gt(b);
+            eq &= a.ct_eq(b);
+        }
+        gt.into()
+    }
+
+    /// Nullifier bytes for a VRF input from `VRFInput::new_nullifier`.
+    ///
+    /// If you are not the signer then you must verify the VRF before calling this method.
+    pub fn nullifier(&self) -> [u8; 32] {
+        self.make_bytes::<[u8; 32]>(b"nullifier")
+    }
+
+    /// Digest identifying this input and output pair, like for logging
+    /// and deduplication, which binds no application context.
+    ///
+    /// We hash both points' canonical encodings, not clearing the
+    /// cofactor like `make_bytes` does, so pairs differing by torsion
+    /// receive different digests.  Digests determine `make_bytes`
+    /// no more than the pair does, but protocols keeping VRF outputs
+    /// secret should treat digests as secret too.
+    pub fn digest(&self) -> [u8; 32] {
+        let mut t = ::merlin::Transcript::new(b"VRFInOutDigest");
+        t.commit_point(b"vrf-in", self.input.as_point());
+        t.commit_point(b"vrf-out", self.output.as_point());
+        let mut digest = [0u8; 32];
+        t.challenge_bytes(b"", &mut digest);
+        digest
+    }
+
     /// VRF output converted into any `SeedableRng`.
     ///
     /// If you are not the signer then you must verify the VRF before calling this method.
@@ -204,7 +640,7 @@ impl<E: JubjubEngineWithParams> VRFInOut<E> {
     /// construction from Theorem 2 on page 32 in appendex C of
     /// ["Ouroboros Praos: An adaptively-secure, semi-synchronous proof-of-stake blockchain"](https://eprint.iacr.org/2017/573.pdf)
     /// by Bernardo David, Peter Gazi, Aggelos Kiayias, and Alexander Russell.
-    #[cfg(feature = "rand_chacha")]
+    #[cfg(feature = "chacharng")]
     pub fn make_chacharng(&self, context: &[u8]) -> ::rand_chacha::ChaChaRng {
         self.make_rng::<::rand_chacha::ChaChaRng>(context)
     }
@@ -219,28 +655,34 @@ impl<E: JubjubEngineWithParams> VRFInOut<E> {
     /// separation.
     #[inline(always)]
     pub fn make_merlin_rng(&self, context: &[u8]) -> ::merlin::TranscriptRng {
-        // Very insecure hack except for our commit_witness_bytes below
-        struct ZeroFakeRng;
-        impl RngCore for ZeroFakeRng {
-            fn next_u32(&mut self) -> u32 {  panic!()  }
-            fn next_u64(&mut self) -> u64 {  panic!()  }
-            fn fill_bytes(&mut self, dest: &mut [u8]) {
-                for i in dest.iter_mut() {  *i = 0;  }
-            }
-            fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), ::rand_core::Error> {
-                self.fill_bytes(dest);
-                Ok(())
-            }
-        }
-        impl CryptoRng for ZeroFakeRng {}
-
         let mut t = ::merlin::Transcript::new(b"VRFResult");
         t.append_message(b"",context);
         self.commit(&mut t);
+        // Very insecure hack except for our commit_witness_bytes below
         t.build_rng().finalize(&mut ZeroFakeRng)
     }
 }
 
+/// Fake RNG producing only zeros, which `make_merlin_rng` supplies to
+/// merlin, whose output depends only upon the transcript.
+///
+/// We never panic here, in case merlin changes which methods it calls.
+struct ZeroFakeRng;
+
+impl RngCore for ZeroFakeRng {
+    fn next_u32(&mut self) -> u32 {  0  }
+    fn next_u64(&mut self) -> u64 {  0  }
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for i in dest.iter_mut() {  *i = 0;  }
+    }
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), ::rand_core::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+impl CryptoRng for ZeroFakeRng {}
+
 impl<E: JubjubEngineWithParams> ReadWrite for VRFPreOut<E>  {
     fn read<R: io::Read>(reader: R) -> io::Result<Self> {
         let p = Point::read(reader,E::params()) ?;
@@ -258,6 +700,127 @@ impl<E: JubjubEngineWithParams> ReadWrite for VRFPreOut<E>  {
     }
 }
 
+/// Add VRF outputs as JubJub points, like for threshold beacons that
+/// combine outputs by different keys on the same input.
+///
+/// We caution that sums only mean anything under protocol specific
+/// assumptions:  Outputs by keys `x_i` on one input `H` sum to the output
+/// `(sum x_i) H` of the summed key, but sums across different inputs
+/// have no meaning as VRF outputs, and nobody can prove them.  Verify
+/// each output first, since only verified outputs lie in the prime order
+/// subgroup, which sums then preserve.
+impl<'a, E: JubjubEngineWithParams> ::core::ops::Add for &'a VRFPreOut<E> {
+    type Output = VRFPreOut<E>;
+
+    fn add(self, other: &'a VRFPreOut<E>) -> VRFPreOut<E> {
+        VRFPreOut( self.0.add(&other.0, E::params()) )
+    }
+}
+
+impl<E: JubjubEngineWithParams> VRFPreOut<E> {
+    /// Sum VRF outputs, subject to the cautions on our `Add` impl,
+    /// returning the identity for no outputs.
+    pub fn sum<B,I>(outputs: I) -> VRFPreOut<E>
+    where B: ::core::borrow::Borrow<VRFPreOut<E>>, I: IntoIterator<Item=B>
+    {
+        VRFPreOut( outputs.into_iter().fold(Point::zero(), |acc, o| acc.add(&o.borrow().0, E::params())) )
+    }
+}
+
+/// Pedersen commitment `output + b B` to a VRF output, for a blinding
+/// `b` and the blinding generator `B`, which `ring_vrf_prove_blinded`
+/// reveals instead of the VRF output.
+#[derive(Debug, Clone)]
+pub struct VRFOutputCommitment<E: JubjubEngine>(pub(crate) Point<E, Unknown>);
+
+impl<E: JubjubEngineWithParams> VRFOutputCommitment<E> {
+    /// Commit to `preout` with `blinding`.
+    pub fn new(preout: &VRFPreOut<E>, blinding: &crate::Scalar<E>) -> VRFOutputCommitment<E> {
+        let b: Point<E, Unknown> = crate::scalar_times_blinding_generator(blinding).into();
+        VRFOutputCommitment( preout.as_point().add(&b, E::params()) )
+    }
+
+    /// JubJub point of this commitment.
+    pub fn as_point(&self) -> &Point<E, Unknown> { &self.0 }
+
+    /// Returns true if this commits to `preout` with `blinding`.
+    pub fn opens_to(&self, preout: &VRFPreOut<E>, blinding: &crate::Scalar<E>) -> bool {
+        VRFOutputCommitment::new(preout, blinding).0 == self.0
+    }
+
+    /// Recover the committed VRF output given `blinding`, which parties
+    /// holding the blinding must still check against the VRF input if
+    /// the proof has not been verified.
+    pub fn open(&self, blinding: &crate::Scalar<E>) -> VRFPreOut<E> {
+        let b: Point<E, Unknown> = crate::scalar_times_blinding_generator(blinding).into();
+        VRFPreOut( self.0.add(&b.negate(), E::params()) )
+    }
+}
+
+impl<E: JubjubEngineWithParams> ReadWrite for VRFOutputCommitment<E>  {
+    fn read<R: io::Read>(reader: R) -> io::Result<Self> {
+        Ok(VRFOutputCommitment( Point::read(reader,E::params()) ? ))
+    }
+
+    fn write<W: io::Write>(&self, writer: W) -> io::Result<()> {
+        self.0.write(writer)
+    }
+}
+
+impl<E: JubjubEngineWithParams> fmt::Display for VRFPreOut<E> {
+    /// Lowercase hex of the canonical serialization.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut bytes = Vec::with_capacity(32);
+        self.write(&mut bytes).map_err( |_| fmt::Error ) ?;
+        crate::misc::fmt_hex(&bytes, f)
+    }
+}
+
+impl<E: JubjubEngineWithParams> VRFPreOut<E> {
+    /// Parse the hex produced by our `Display` implementation.
+    pub fn from_hex(s: &str) -> io::Result<Self> {
+        crate::misc::read_hex(s, |r| VRFPreOut::read(r))
+    }
+}
+
+/// Version byte `VRFPreOut::write_versioned` prefixes to the compressed
+/// point encoding of `VRFPreOut::write`.
+#[cfg(feature = "versioned_output")]
+pub const VRF_OUTPUT_VERSION: u8 = 0;
+
+#[cfg(feature = "versioned_output")]
+impl<E: JubjubEngineWithParams> VRFPreOut<E> {
+    /// Serialize as the `VRF_OUTPUT_VERSION` byte followed by `write`'s
+    /// encoding, so future encodings remain distinguishable.
+    ///
+    /// We leave `write` itself unversioned, since protocols embed its
+    /// 32 bytes in fixed size fields.
+    pub fn write_versioned<W: io::Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_all(&[VRF_OUTPUT_VERSION]) ?;
+        self.write(writer)
+    }
+
+    /// Deserialize `write_versioned`'s format, rejecting unknown versions.
+    pub fn read_versioned<R: io::Read>(reader: R) -> io::Result<Self> {
+        VRFPreOut::read_versioned_with(reader, |_version, _reader|
+            Err( signature_error("VRFPreOut has unknown version") )
+        )
+    }
+
+    /// Deserialize `write_versioned`'s format, decoding version zero
+    /// ourselves, and dispatching other versions to `decode`.
+    pub fn read_versioned_with<R,F>(mut reader: R, decode: F) -> io::Result<Self>
+    where R: io::Read, F: FnOnce(u8, R) -> io::Result<Self>
+    {
+        let mut version = [0u8; 1];
+        reader.read_exact(&mut version) ?;
+        match version[0] {
+            VRF_OUTPUT_VERSION => VRFPreOut::read(reader),
+            v => decode(v, reader),
+        }
+    }
+}
+
 
 /// Merge VRF input and output pairs from the same signer,
 /// probably using variable time arithmetic
@@ -287,45 +850,236 @@ impl<E: JubjubEngineWithParams> ReadWrite for VRFPreOut<E>  {
 /// hashed to the curve.  TODO: Cite Wagner.
 /// We also note no such requirement when the values being hashed are
 /// BLS public keys as in https://crypto.stanford.edu/~dabo/pubs/papers/BLSmultisig.html
+///
+/// Panics if `ps` is empty, for which see `try_vrfs_merge`.
 pub fn vrfs_merge<E,B>(ps: &[B]) -> VRFInOut<E>
 where
     E: JubjubEngineWithParams,
     B: ::core::borrow::Borrow<VRFInOut<E>>,
 {
-    let engine_params = E::params();
+    vrfs_merge_with_context(ps, b"")
+}
+
+/// Merge VRF input and output pairs like `vrfs_merge`, but bind the
+/// delinearization weights to `context`, like an epoch, so merging the
+/// same pairs in different contexts yields different results.
+///
+/// We commit nothing for an empty `context`, so `vrfs_merge` agrees
+/// with merges from before we supported contexts.  Panics if `ps` is
+/// empty, like `vrfs_merge`.
+pub fn vrfs_merge_with_context<E,B>(ps: &[B], context: &[u8]) -> VRFInOut<E>
+where
+    E: JubjubEngineWithParams,
+    B: ::core::borrow::Borrow<VRFInOut<E>>,
+{
+    assert!( ps.len() > 0);
+    let zs = merge_weights(ps, context);
+    merge_with_weights(ps, &zs)
+}
+
+/// Merge VRF input and output pairs like `vrfs_merge`, but return an
+/// error instead of panicking if `ps` is empty.
+pub fn try_vrfs_merge<E,B>(ps: &[B]) -> SignatureResult<VRFInOut<E>>
+where
+    E: JubjubEngineWithParams,
+    B: ::core::borrow::Borrow<VRFInOut<E>>,
+{
+    if ps.is_empty() {
+        return Err(signature_error("Cannot merge an empty list of VRF input and output pairs"));
+    }
+    Ok(vrfs_merge(ps))
+}
 
+/// Merge VRF input and output pairs like `vrfs_merge`, but also return
+/// the 128 bit delinearization weight of each pair.
+///
+/// Verifiers may check these weights independently, and we have
+/// `vrfs_merge(ps) = ∑ weights[i] ps[i]` for both inputs and outputs.
+pub fn vrfs_merge_weights<E,B>(ps: &[B]) -> (Vec<crate::Scalar<E>>, VRFInOut<E>)
+where
+    E: JubjubEngineWithParams,
+    B: ::core::borrow::Borrow<VRFInOut<E>>,
+{
     assert!( ps.len() > 0);
+    let zs = merge_weights(ps, b"");
+    let weights = zs.iter().map( |z| crate::misc::scalar_from_u128::<E>(*z) ).collect();
+    (weights, merge_with_weights(ps, &zs))
+}
+
+/// Derive the 128 bit little endian delinearization weights for `vrfs_merge`.
+fn merge_weights<E,B>(ps: &[B], context: &[u8]) -> Vec<[u8; 16]>
+where
+    E: JubjubEngineWithParams,
+    B: ::core::borrow::Borrow<VRFInOut<E>>,
+{
     let mut t = ::merlin::Transcript::new(b"MergeVRFs");
+    if ! context.is_empty() {
+        t.append_message(b"context", context);
+    }
     for p in ps {  p.borrow().commit(&mut t);  }
 
-    // We'd do accumulation here, but rust lacks polymorphic closures.
-    let psz = || ps.iter().map( |p| { 
+    ps.iter().map( |p| { 
         let mut t0 = t.clone();
-        let p = p.borrow();
-        p.commit(&mut t0);
+        p.borrow().commit(&mut t0);
 
         // Sample a 128bit scalar
         let mut s = [0u8; 16];
         t0.challenge_bytes(b"", &mut s);
-        let z: Scalar<E> = crate::misc::scalar_from_u128::<E>(s);
-        (p,z)
-    } );
-
-    let input = VRFInput( psz().fold(Point::zero(), |acc,(p,z)| { 
-        acc.add(&p.input.as_point().mul(z, engine_params), engine_params)
-    } ) );
-    let output = VRFPreOut( psz().fold(Point::zero(), |acc,(p,z)| { 
-        acc.add(&p.output.as_point().mul(z, engine_params), engine_params)
-    } ) );
+        s
+    } ).collect()
+}
+
+fn merge_with_weights<E,B>(ps: &[B], zs: &[[u8; 16]]) -> VRFInOut<E>
+where
+    E: JubjubEngineWithParams,
+    B: ::core::borrow::Borrow<VRFInOut<E>>,
+{
+    // We share the doublings among all points, which makes merging
+    // far cheaper than multiplying each point separately.
+    let malleability = if ps.iter().any( |p| p.borrow().input.is_nonmalleable() ) {
+        Malleability::NonMalleable
+    } else {
+        Malleability::Malleable
+    };
+    let input = VRFInput( crate::misc::multiply_u128s(
+        ps.iter().map( |p| p.borrow().input.as_point() ), zs
+    ), malleability );
+    let output = VRFPreOut( crate::misc::multiply_u128s(
+        ps.iter().map( |p| p.borrow().output.as_point() ), zs
+    ) );
     VRFInOut { input, output }
 }
 
 
+impl<E: JubjubEngineWithParams> crate::SecretKey<E> {
+    /// Evaluate our VRF on each input, returning the individual
+    /// `VRFInOut`s along with their `vrfs_merge`.
+    ///
+    /// We produce no proofs here, so only use the merged `VRFInOut`
+    /// once you prove it, like with `ring_vrf_sign_checked`.
+    /// Panics if `inputs` is empty, like `vrfs_merge`, for which see
+    /// `try_vrf_evaluate_batch`.
+    pub fn vrf_evaluate_batch<I>(&self, inputs: I) -> (Vec<VRFInOut<E>>, VRFInOut<E>)
+    where I: IntoIterator<Item=VRFInput<E>>
+    {
+        let inouts = inputs.into_iter().map( |input| input.to_inout(self) ).collect::<Vec<_>>();
+        let merged = vrfs_merge(&inouts);
+        (inouts, merged)
+    }
+
+    /// Evaluate our VRF on each input like `vrf_evaluate_batch`, but
+    /// return an error instead of panicking if `inputs` is empty.
+    pub fn try_vrf_evaluate_batch<I>(&self, inputs: I) -> SignatureResult<(Vec<VRFInOut<E>>, VRFInOut<E>)>
+    where I: IntoIterator<Item=VRFInput<E>>
+    {
+        let inouts = inputs.into_iter().map( |input| input.to_inout(self) ).collect::<Vec<_>>();
+        let merged = try_vrfs_merge(&inouts) ?;
+        Ok((inouts, merged))
+    }
+
+    /// Compute our VRF outputs on many inputs, identical to calling
+    /// `VRFInput::to_preout` on each.
+    ///
+    /// We share only the decomposition of our secret key into 4 bit
+    /// windows, which costs little.  Each input still builds its own
+    /// table of multiples and performs its full constant time scalar
+    /// multiplication, so this runs about as fast as calling
+    /// `VRFInput::to_preout` in a loop.
+    pub fn to_preouts(&self, inputs: &[VRFInput<E>]) -> Vec<VRFPreOut<E>> {
+        let mut nibbles = crate::misc::scalar_nibbles::<E>(&self.key);
+        let preouts = inputs.iter()
+            .map( |input| VRFPreOut( crate::misc::mul_consttime_nibbles(&input.0, &nibbles) ) )
+            .collect();
+        ::zeroize::Zeroize::zeroize(&mut nibbles);
+        preouts
+    }
+
+    /// Evaluate our VRF lazily on each input, like when scanning slots
+    /// for one we win, so consumers may stop early via `find` or
+    /// `take_while` without evaluating every candidate.
+    pub fn vrf_scan<'a,I>(&'a self, inputs: I) -> impl Iterator<Item=VRFInOut<E>> + 'a
+    where I: IntoIterator<Item=VRFInput<E>>, I::IntoIter: 'a
+    {
+        inputs.into_iter().map( move |input| input.to_inout(self) )
+    }
+}
+
+
+/// Deterministic beacon value combining several signers' VRF outputs.
+///
+/// You must verify every `VRFInOut` against its `PublicKey` before
+/// calling this method.  We commit the pairs in a canonical order,
+/// sorted by their serialization, so any committee member obtains
+/// the same beacon regardless of the order in which they received
+/// the pairs.
+pub fn aggregate_beacon<E>(outputs: &[(crate::PublicKey<E>, VRFInOut<E>)], context: &[u8]) -> [u8; 32]
+where E: JubjubEngineWithParams,
+{
+    let mut sorted = outputs.iter().map( |(pk,io)| {
+        let mut key = Vec::with_capacity(96);
+        clear_cofactor(&pk.0).write(&mut key).expect("Writing to a Vec never fails.");
+        io.input.as_point().write(&mut key).expect("Writing to a Vec never fails.");
+        clear_cofactor(io.output.as_point()).write(&mut key).expect("Writing to a Vec never fails.");
+        (key,pk,io)
+    } ).collect::<Vec<_>>();
+    sorted.sort_by( |a,b| a.0.cmp(&b.0) );
+
+    let mut t = ::merlin::Transcript::new(b"VRFBeacon");
+    t.append_message(b"",context);
+    for (_,pk,io) in sorted {
+        t.commit_point(b"vrf-pk", &clear_cofactor(&pk.0));
+        io.commit(&mut t);
+    }
+    let mut beacon = [0u8; 32];
+    t.challenge_bytes(b"", &mut beacon);
+    beacon
+}
+
+
+/// Threshold for `VRFInOut::exceeds_threshold` under which VRF outputs
+/// win with probability `p`, as a little endian integer.
+///
+/// We retain 64 bits of precision, so outputs do not exceed the
+/// threshold with probability `floor(p 2^64) / 2^64`, except that
+/// `p >= 1.0` always wins and `p <= 0.0` wins only for the zero output.
+pub fn threshold_from_probability(p: f64) -> [u8; 32] {
+    if p >= 1.0 {  return [0xffu8; 32];  }
+    let mut threshold = [0u8; 32];
+    // Casts saturate, so NaN and negative p become zero.
+    let top = (p * 18446744073709551616.0) as u64;
+    if top == 0 {  return threshold;  }
+    // threshold = top 2^192 - 1
+    threshold[24..].copy_from_slice(&(top - 1).to_le_bytes());
+    for b in threshold[..24].iter_mut() {  *b = 0xff;  }
+    threshold
+}
+
+
 /// Almost all VRF methods support signing an extra message
 /// alongside the VRF, so `no_extra` provides a convenient
 /// default transcript when no extra message is desired.
+///
+/// We commit a distinct domain tag here, so signing no extra message
+/// always differs from signing an explicitly empty extra message,
+/// like `Transcript::new(b"VRF")` or `signing_context(b"").bytes(b"")`.
+///
+/// This breaks the wire format:  Earlier versions returned only
+/// `Transcript::new(b"VRF")`, so proofs made with `no_extra` by earlier
+/// versions never verify with ours, nor ours with theirs.  Verifiers of
+/// such old proofs should pass `Transcript::new(b"VRF")` explicitly.
 pub fn no_extra() -> Transcript {
-    Transcript::new(b"VRF")
+    let mut t = Transcript::new(b"VRF");
+    t.append_message(b"no-extra", b"");
+    t
+}
+
+/// Public input for the extra message transcript, in the JubJub base
+/// field aka the BLS12-381 scalar field.
+///
+/// Both provers and verifiers derive the circuit's extra message input
+/// here, so they always agree upon the same transcript label.
+pub fn extra_scalar<E: JubjubEngine, T: SigningTranscript>(mut extra: T) -> E::Fr {
+    extra.challenge_scalar(b"extra-msg")
 }
 
 /// We take closures like `F: FnMut(&VRFInOut<E>) -> impl VRFExtraMessage`
@@ -350,11 +1104,761 @@ impl VRFExtraMessage for Transcript {
     fn extra(self) -> Option<Transcript> { Some(self) }
 }
 
+impl VRFExtraMessage for PreHashedExtra {
+    type T = Transcript;
+    fn extra(self) -> Option<Transcript> { Some(self.transcript()) }
+}
+
 pub fn no_check_no_extra<E: JubjubEngineWithParams>(_: &VRFInOut<E>) -> bool { true }
 
+/// Digest of a large extra message, which callers compute once and
+/// then pass to both the prover and verifier.
+///
+/// We commit only the 32 byte digest into the extra transcript, so
+/// both sides agree whenever they agree upon the digest, and neither
+/// rehashes the payload when proving or verifying repeatedly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PreHashedExtra([u8; 32]);
+
+impl PreHashedExtra {
+    /// Hash the extra message `payload`.
+    pub fn new(payload: &[u8]) -> PreHashedExtra {
+        let mut t = Transcript::new(b"PreHashedExtra");
+        t.append_message(b"payload", payload);
+        let mut digest = [0u8; 32];
+        t.challenge_bytes(b"digest", &mut digest);
+        PreHashedExtra(digest)
+    }
+
+    /// Use a digest previously computed by `PreHashedExtra::new`,
+    /// perhaps by another party.
+    pub fn from_digest(digest: [u8; 32]) -> PreHashedExtra {
+        PreHashedExtra(digest)
+    }
+
+    /// The digest, for sending alongside the proof.
+    pub fn digest(&self) -> &[u8; 32] { &self.0 }
+
+    /// Extra message transcript for proving or verifying.
+    pub fn transcript(&self) -> Transcript {
+        let mut t = no_extra();
+        t.commit_bytes(b"extra-digest", &self.0);
+        t
+    }
+}
+
 
 
 #[cfg(test)]
 mod tests {
-}
+    use rand_core::RngCore;
+    use pairing::bls12_381::Bls12;
+
+    use super::*;
+    use crate::{testing, signing_context, RingRoot, RingSecretCopath, SecretKey};
+
+    #[test]
+    fn read_arbitrary_bytes() {
+        let mut rng = ::rand_core::OsRng;
+        let mut bytes = [0u8; 36];
+        for _ in 0..256 {
+            rng.fill_bytes(&mut bytes);
+            let _ = VRFPreOut::<Bls12>::read(&bytes[..]);
+            let _ = RingRoot::<Bls12>::read(&bytes[..]);
+            let _ = RingSecretCopath::<Bls12>::read(&bytes[..]);
+        }
+        assert!(VRFPreOut::<Bls12>::read(&bytes[..31]).is_err());
+        // A huge length prefix must fail upon the missing data, not abort upon allocation.
+        assert!(RingSecretCopath::<Bls12>::read(&[0xffu8; 4][..]).is_err());
+    }
+
+    #[test]
+    fn ring_root_encoding_pinned() {
+        use ff::Field;
+        let root = RingRoot::<Bls12>(-<Bls12 as ::pairing::Engine>::Fr::one());
+        let mut bytes = Vec::new();
+        root.write(&mut bytes).unwrap();
+        assert_eq!(bytes, crate::misc::decode_hex("00000000fffffffffe5bfeff02a4bd5305d8a10908d83933487d9d2953a7ed73").unwrap());
+        assert_eq!(root.0.to_repr().as_ref(), &bytes[..]);
+
+        let ctx = crate::signing_context(b"root");
+        let input = VRFInput::<Bls12>::new_ring_malleable(ctx.bytes(b"meow"), &root);
+        let mut t = ctx.bytes(b"meow");
+        t.commit_bytes(b"vrf-nm-ar", &bytes);
+        assert!(input.as_point() == VRFInput::<Bls12>::new_malleable(t).as_point());
+        assert!(input.as_point() == VRFInput::<Bls12>::new_ring_malleable(ctx.bytes(b"meow"), &root).as_point());
+    }
+
+    #[test]
+    fn evaluate_batch_matches_merge() {
+        let sk = crate::SecretKey::<Bls12>::from_rng(::rand_core::OsRng);
+        let input = |i: u8| VRFInput::<Bls12>::new_malleable(crate::signing_context(b"batch").bytes(&[i]));
+        let (inouts, merged) = sk.vrf_evaluate_batch((0..4).map(input));
+        assert_eq!(inouts.len(), 4);
+        for (i,io) in inouts.iter().enumerate() {
+            assert!(io.output.as_point() == input(i as u8).to_inout(&sk).output.as_point());
+        }
+        let expected = vrfs_merge(&(0..4).map( |i| input(i).to_inout(&sk) ).collect::<Vec<_>>());
+        assert!(merged.input.as_point() == expected.input.as_point());
+        assert!(merged.output.as_point() == expected.output.as_point());
+    }
+
+    #[test]
+    fn empty_merges_are_errors() {
+        let sk = crate::SecretKey::<Bls12>::from_rng(::rand_core::OsRng);
+        assert!(try_vrfs_merge::<Bls12,VRFInOut<Bls12>>(&[]).is_err());
+        assert!(sk.try_vrf_evaluate_batch(Vec::new()).is_err());
+
+        let input = VRFInput::<Bls12>::new_malleable(crate::signing_context(b"merge").bytes(b"meow"));
+        let (inouts, merged) = sk.try_vrf_evaluate_batch(vec![input]).unwrap();
+        assert!(merged.output.as_point() == vrfs_merge(&inouts).output.as_point());
+    }
+
+    #[test]
+    fn batched_preouts_match() {
+        let sk = crate::SecretKey::<Bls12>::from_rng(::rand_core::OsRng);
+        let inputs = (0..4u8).map( |i| VRFInput::<Bls12>::new_malleable(crate::signing_context(b"preouts").bytes(&[i])) ).collect::<Vec<_>>();
+        let preouts = sk.to_preouts(&inputs);
+        assert_eq!(preouts.len(), 4);
+        for (input,preout) in inputs.iter().zip(preouts.iter()) {
+            assert!(preout.as_point() == input.to_preout(&sk).as_point());
+            assert!(preout.as_point() == input.to_preout_vartime(&sk).as_point());
+        }
+        assert!(sk.to_preouts(&[]).is_empty());
+    }
+
+    #[test]
+    fn scan_is_lazy() {
+        let sk = crate::SecretKey::<Bls12>::from_rng(::rand_core::OsRng);
+        let input = |i: u8| VRFInput::<Bls12>::new_malleable(crate::signing_context(b"scan").bytes(&[i]));
+        let (eager, _) = sk.vrf_evaluate_batch((0..4).map(input));
+        let lazy = sk.vrf_scan((0..4).map(input)).collect::<Vec<_>>();
+        for (a,b) in eager.iter().zip(lazy.iter()) {
+            assert!(a.output.as_point() == b.output.as_point());
+        }
+
+        let evaluated = ::core::cell::Cell::new(0);
+        let inputs = (0..u8::MAX).map( |i| {  evaluated.set(evaluated.get() + 1);  input(i)  } );
+        let target = eager[2].output.as_point().clone();
+        let found = sk.vrf_scan(inputs).position( |io| *io.output.as_point() == target );
+        assert_eq!(found, Some(2));
+        assert_eq!(evaluated.get(), 3);
+    }
+
+    #[test]
+    fn inout_consistent_with_secret() {
+        let mut rng = ::rand_core::OsRng;
+        let sk = crate::SecretKey::<Bls12>::from_rng(&mut rng);
+        let input = VRFInput::<Bls12>::new_malleable(crate::signing_context(b"consistent").bytes(b"meow"));
+        let mut io = input.to_inout(&sk);
+        assert!(io.assert_consistent_with(&sk).is_ok());
+        assert!(io.assert_consistent_with(&crate::SecretKey::<Bls12>::from_rng(&mut rng)).is_err());
+        io.output = io.output.blind(&crate::Scalar::<Bls12>::from(2u64));
+        assert!(io.assert_consistent_with(&sk).is_err());
+    }
+
+    #[test]
+    fn role_outputs_independent() {
+        let sk = crate::SecretKey::<Bls12>::from_rng(::rand_core::OsRng);
+        let io = VRFInput::<Bls12>::new_malleable(crate::signing_context(b"roles").bytes(b"meow")).to_inout(&sk);
+        let roles = [Role::BlockProduction, Role::ParachainAssignment, Role::Disputes, Role::Custom(0), Role::Custom(2)];
+        let outputs = roles.iter().map( |r| io.make_bytes_for_role::<[u8; 32]>(*r, b"ctx") ).collect::<Vec<_>>();
+        for i in 0..outputs.len() {
+            for j in 0..i {  assert_ne!(outputs[i], outputs[j]);  }
+            assert_ne!(outputs[i], io.make_bytes::<[u8; 32]>(b"ctx"));
+        }
+        assert_eq!(outputs[0], io.make_bytes_for_role::<[u8; 32]>(Role::BlockProduction, b"ctx"));
+    }
 
+    #[test]
+    fn preout_point_accessors() {
+        let sk = crate::SecretKey::<Bls12>::from_rng(::rand_core::OsRng);
+        let out = VRFInput::<Bls12>::new_malleable(crate::signing_context(b"point").bytes(b"meow")).to_preout(&sk);
+        let mut bytes = Vec::new();
+        out.write(&mut bytes).unwrap();
+        let parsed = Point::<Bls12, Unknown>::read(&bytes[..], Bls12::params()).unwrap();
+        let out = VRFPreOut::<Bls12>::read(&bytes[..]).unwrap();
+        assert!(*out.as_point() == parsed);
+        assert!(out.into_point() == parsed);
+    }
+
+    #[test]
+    fn output_x_coordinates() {
+        let sk = crate::SecretKey::<Bls12>::from_rng(::rand_core::OsRng);
+        let io = VRFInput::<Bls12>::new_malleable(crate::signing_context(b"x").bytes(b"meow")).to_inout(&sk);
+        let root = RingRoot::<Bls12>(extra_scalar::<Bls12,_>(no_extra()));
+        let inputs = crate::ring_vrf_debug_public_inputs(&io, no_extra(), &root);
+        assert_eq!(io.output.x_coordinate(), inputs[2]);
+
+        let mut bytes = Vec::new();
+        io.output.write(&mut bytes).unwrap();
+        let parsed = VRFPreOut::<Bls12>::read(&bytes[..]).unwrap();
+        assert_eq!(parsed.x_coordinate(), inputs[2]);
+    }
+
+    #[test]
+    fn output_sums() {
+        let mut rng = ::rand_core::OsRng;
+        let params = Bls12::params();
+        let sks = (0..3).map( |_| crate::SecretKey::<Bls12>::from_rng(&mut rng) ).collect::<Vec<_>>();
+        let input = VRFInput::<Bls12>::new_malleable(crate::signing_context(b"sums").bytes(b"meow"));
+        let outputs = sks.iter().map( |sk| input.to_preout(sk) ).collect::<Vec<_>>();
+
+        let pair = &outputs[0] + &outputs[1];
+        assert!(*pair.as_point() == outputs[0].as_point().add(outputs[1].as_point(), params));
+
+        // Summing outputs on one input evaluates the summed key.
+        let total = VRFPreOut::sum(&outputs);
+        let mut key = sks[0].key.clone();
+        for sk in sks[1..].iter() {  ::core::ops::AddAssign::add_assign(&mut key, &sk.key);  }
+        let expected: Point<Bls12, Unknown> = input.as_point().mul(key, params).into();
+        assert!(*total.as_point() == expected);
+        assert!(total.as_point().as_prime_order(params).is_some());
+        assert!(*VRFPreOut::<Bls12>::sum(Vec::<VRFPreOut<Bls12>>::new()).as_point() == Point::zero());
+    }
+
+    #[test]
+    fn extra_scalar_pinned() {
+        // We generated these vectors by printing, for each transcript `t`,
+        //
+        //     let mut c = t.clone();
+        //     let mut seed = [0u8; 32];
+        //     c.challenge_bytes(b"extra-msg", &mut seed);
+        //     println!("{:?} {:?}", seed, extra_scalar::<Bls12,_>(t).to_repr());
+        //
+        // and encoding both in hex.  We pin the merlin challenge separately,
+        // so failures distinguish transcript changes from changes in how
+        // the field samples from ChaCha seeded by the challenge.
+        let vectors = [
+            (no_extra(),
+             "1f0eda6a0303dfbae3d528029218352a45dc4e1dab8a6c80d873bf192e2a803a",
+             "678f785cf5d5776a6e7ef8de6be26f52f892b7bc6ea3b22dd4377017822d7d44"),
+            (crate::signing_context(b"extra").bytes(b"meow"),
+             "b8321096f3179fe7dd4ad9ae11fc87f5ca1e01646f865928cdd071d2e39136e2",
+             "b24e399a73ff7aec1409a1e7d6861eb9dad9360283d0d444ab6a27f8e6909c29"),
+        ];
+        for (t, seed, scalar) in vectors.iter() {
+            let mut c = t.clone();
+            let mut bytes = [0u8; 32];
+            c.challenge_bytes(b"extra-msg", &mut bytes);
+            assert_eq!(&bytes[..], &crate::misc::decode_hex(seed).unwrap()[..]);
+            let extra = extra_scalar::<Bls12,_>(t.clone());
+            assert_eq!(extra.to_repr().as_ref(), &crate::misc::decode_hex(scalar).unwrap()[..]);
+        }
+    }
+
+    #[test]
+    fn extra_scalar_matches_prover_and_verifier() {
+        use bellman::{Circuit, gadgets::test::TestConstraintSystem};
+
+        let mut rng = ::rand_core::OsRng;
+        let sk = crate::SecretKey::<Bls12>::from_rng(&mut rng);
+        let copath = RingSecretCopath::<Bls12>::random(2, &mut rng);
+        let root = copath.to_root(&sk.to_public());
+        let input = VRFInput::<Bls12>::new_malleable(crate::signing_context(b"extra").bytes(b"meow"));
+        let extra = || crate::signing_context(b"extra").bytes(b"msg");
+
+        // We build the circuit exactly like `SecretKey::ring_vrf_prove`.
+        let circuit = crate::circuit::RingVRF {
+            depth: 2,
+            sk: Some(sk.clone()),
+            vrf_input: Some(input.as_point().clone()),
+            extra: Some(extra_scalar::<Bls12,_>(extra())),
+            copath: Some(copath),
+        };
+        let mut cs = TestConstraintSystem::new();
+        circuit.synthesize(&mut cs).unwrap();
+        assert!(cs.is_satisfied());
+
+        let inputs = crate::ring_vrf_debug_public_inputs(&input.to_inout(&sk), extra(), &root);
+        let paths = [
+            "VRF_BASE input/x/input variable", "VRF_BASE input/y/input variable",
+            "vrf/x/input variable", "vrf/y/input variable",
+            "extra/input variable", "anchor/input variable",
+        ];
+        for (i,path) in paths.iter().enumerate() {
+            assert_eq!(cs.get_input(i + 1, path), inputs[i], "{}", path);
+        }
+        let other = crate::ring_vrf_debug_public_inputs(&input.to_inout(&sk), crate::signing_context(b"extra").bytes(b"other"), &root);
+        assert_ne!(cs.get_input(5, "extra/input variable"), other[4]);
+    }
+
+    #[test]
+    fn no_extra_differs_from_empty_extra() {
+        let none = extra_scalar::<Bls12,_>(no_extra());
+        assert_eq!(none, extra_scalar::<Bls12,_>(no_extra()));
+        assert_ne!(none, extra_scalar::<Bls12,_>(Transcript::new(b"VRF")));
+        assert_ne!(none, extra_scalar::<Bls12,_>(crate::signing_context(b"").bytes(b"")));
+    }
+
+    #[test]
+    fn no_extra_known_answer() {
+        // Any change here breaks the wire format of every proof signed
+        // with `no_extra`, like the "no-extra" label did, so we pin the
+        // transcript's challenge, and the one before that label.
+        let challenge = |mut t: Transcript| {
+            let mut bytes = [0u8; 32];
+            t.challenge_bytes(b"extra-msg", &mut bytes);
+            bytes
+        };
+        assert_eq!(
+            &challenge(no_extra())[..],
+            &crate::misc::decode_hex("1f0eda6a0303dfbae3d528029218352a45dc4e1dab8a6c80d873bf192e2a803a").unwrap()[..]
+        );
+        assert_eq!(
+            &challenge(Transcript::new(b"VRF"))[..],
+            &crate::misc::decode_hex("61d4ef1efbc2a674cd9b613e01d57ed5334f58933f40d1938769cbc527ed97ae").unwrap()[..]
+        );
+    }
+
+    #[test]
+    fn merge_weights_reconstruct_merge() {
+        let params = Bls12::params();
+        let sk = crate::SecretKey::<Bls12>::from_rng(::rand_core::OsRng);
+        let ps = (0..3u8).map( |i|
+            VRFInput::<Bls12>::new_malleable(crate::signing_context(b"weights").bytes(&[i])).to_inout(&sk)
+        ).collect::<Vec<_>>();
+        let (weights, merged) = vrfs_merge_weights(&ps);
+        let direct = vrfs_merge(&ps);
+        assert!(merged.input.as_point() == direct.input.as_point());
+        assert!(merged.output.as_point() == direct.output.as_point());
+
+        let mut input = Point::<Bls12,PrimeOrder>::zero();
+        let mut output = Point::<Bls12,Unknown>::zero();
+        for (w,p) in weights.iter().zip(ps.iter()) {
+            input = input.add(&p.input.as_point().mul(w.clone(), params), params);
+            output = output.add(&p.output.as_point().mul(w.clone(), params), params);
+        }
+        assert!(input == *direct.input.as_point());
+        assert!(output == *direct.output.as_point());
+    }
+
+    #[test]
+    fn merge_contexts_differ() {
+        let sk = crate::SecretKey::<Bls12>::from_rng(::rand_core::OsRng);
+        let ps = (0..3u8).map( |i|
+            VRFInput::<Bls12>::new_malleable(crate::signing_context(b"contexts").bytes(&[i])).to_inout(&sk)
+        ).collect::<Vec<_>>();
+        let plain = vrfs_merge(&ps);
+        let empty = vrfs_merge_with_context(&ps, b"");
+        let epoch1 = vrfs_merge_with_context(&ps, b"epoch 1");
+        let epoch2 = vrfs_merge_with_context(&ps, b"epoch 2");
+        assert!(plain.output.as_point() == empty.output.as_point());
+        assert!(epoch1.output.as_point() == vrfs_merge_with_context(&ps, b"epoch 1").output.as_point());
+        assert!(epoch1.input.as_point() != epoch2.input.as_point());
+        assert!(epoch1.output.as_point() != epoch2.output.as_point());
+        assert!(epoch1.output.as_point() != plain.output.as_point());
+    }
+
+    #[test]
+    fn output_backends() {
+        let sk = crate::SecretKey::<Bls12>::from_rng(::rand_core::OsRng);
+        let io = VRFInput::<Bls12>::new_malleable(crate::signing_context(b"backends").bytes(b"meow")).to_inout(&sk);
+        let merlin = io.make_bytes::<[u8; 32]>(b"ctx");
+        assert!(merlin[..] == io.make_bytes::<[u8; 32]>(b"ctx")[..]);
+        assert!(merlin[..] != io.make_bytes::<[u8; 32]>(b"other")[..]);
+
+        #[cfg(feature = "fast_transcript")] {
+            let blake3 = io.make_bytes_blake3::<[u8; 32]>(b"ctx");
+            assert!(blake3[..] == io.make_bytes_blake3::<[u8; 32]>(b"ctx")[..]);
+            assert!(blake3[..] != io.make_bytes_blake3::<[u8; 32]>(b"other")[..]);
+            assert!(blake3[..] != merlin[..]);
+        }
+    }
+
+    #[cfg(feature = "versioned_output")]
+    #[test]
+    fn versioned_outputs() {
+        let sk = crate::SecretKey::<Bls12>::from_rng(::rand_core::OsRng);
+        let out = VRFInput::<Bls12>::new_malleable(crate::signing_context(b"versioned").bytes(b"meow")).to_preout(&sk);
+        let mut bytes = Vec::new();
+        out.write_versioned(&mut bytes).unwrap();
+        assert_eq!(bytes.len(), 33);
+        assert_eq!(bytes[0], VRF_OUTPUT_VERSION);
+        assert!(VRFPreOut::<Bls12>::read_versioned(&bytes[..]).unwrap().as_point() == out.as_point());
+
+        // Simulate a version one encoding with the point bytes reversed.
+        let mut v1 = vec![1u8];
+        v1.extend(bytes[1..].iter().rev());
+        assert!(VRFPreOut::<Bls12>::read_versioned(&v1[..]).is_err());
+        let decoded = VRFPreOut::<Bls12>::read_versioned_with(&v1[..], |version, mut reader| {
+            assert_eq!(version, 1);
+            let mut point = [0u8; 32];
+            io::Read::read_exact(&mut reader, &mut point) ?;
+            point.reverse();
+            VRFPreOut::read(&point[..])
+        }).unwrap();
+        assert!(decoded.as_point() == out.as_point());
+    }
+
+    #[test]
+    fn inout_digests() {
+        let sk = crate::SecretKey::<Bls12>::from_rng(::rand_core::OsRng);
+        let input = |m: &[u8]| VRFInput::<Bls12>::new_malleable(crate::signing_context(b"digest").bytes(m));
+        let io = input(b"meow").to_inout(&sk);
+        assert_eq!(io.digest(), io.clone().digest());
+        assert_eq!(io.digest(), input(b"meow").to_inout(&sk).digest());
+        assert!(io.digest() != input(b"woof").to_inout(&sk).digest());
+        let other = crate::SecretKey::<Bls12>::from_rng(::rand_core::OsRng);
+        assert!(io.digest() != input(b"meow").to_inout(&other).digest());
+        assert!(io.digest()[..] != io.make_bytes::<[u8; 32]>(b"")[..]);
+    }
+
+    #[test]
+    fn merlin_rng_deterministic() {
+        let mut fake = ZeroFakeRng;
+        assert_eq!(fake.next_u32(), 0);
+        assert_eq!(fake.next_u64(), 0);
+
+        let sk = crate::SecretKey::<Bls12>::from_rng(::rand_core::OsRng);
+        let io = VRFInput::<Bls12>::new_malleable(crate::signing_context(b"merlin").bytes(b"meow")).to_inout(&sk);
+        let draw = |context: &[u8]| {
+            let mut bytes = [0u8; 32];
+            io.make_merlin_rng(context).fill_bytes(&mut bytes);
+            bytes
+        };
+        assert_eq!(draw(b"a"), draw(b"a"));
+        assert_ne!(draw(b"a"), draw(b"b"));
+        assert_eq!(io.make_merlin_rng(b"a").next_u64(), io.make_merlin_rng(b"a").next_u64());
+    }
+
+    #[test]
+    fn hex_display_roundtrip() {
+        let mut rng = ::rand_core::OsRng;
+        let sk = crate::SecretKey::<Bls12>::from_rng(&mut rng);
+        let input = VRFInput::<Bls12>::new_malleable(crate::signing_context(b"hex").bytes(b"meow"));
+        let out = input.to_preout(&sk);
+        let s = out.to_string();
+        assert_eq!(s.len(), 64);
+        assert_eq!(s, s.to_lowercase());
+        assert!(VRFPreOut::<Bls12>::from_hex(&s).unwrap().as_point() == out.as_point());
+        assert!(VRFPreOut::<Bls12>::from_hex(&s.to_uppercase()).is_ok());
+        assert!(VRFPreOut::<Bls12>::from_hex(&s[1..]).is_err());
+        assert!(VRFPreOut::<Bls12>::from_hex(&format!("{}00", s)).is_err());
+        assert!(VRFPreOut::<Bls12>::from_hex(&s.replacen(&s[..2], "zz", 1)).is_err());
+
+        let root = RingSecretCopath::<Bls12>::random(4, &mut rng).to_root(&sk.to_public());
+        let s = root.to_string();
+        assert!(RingRoot::<Bls12>::from_hex(&s).unwrap().0 == root.0);
+        assert!(RingRoot::<Bls12>::from_hex("0").is_err());
+        assert!(RingRoot::<Bls12>::from_hex(&"ff".repeat(32)).is_err());
+    }
+
+    #[test]
+    fn split_and_reassemble() {
+        let sk = crate::SecretKey::<Bls12>::from_rng(::rand_core::OsRng);
+        let io = VRFInput::<Bls12>::new_malleable(crate::signing_context(b"split").bytes(b"meow")).to_inout(&sk);
+        let (input, output) = io.parts();
+        assert!(input.as_point() == io.input.as_point() && output.as_point() == io.output.as_point());
+        let (input, output) = io.clone().split();
+        let io2 = VRFInOut { input, output };
+        assert!(io2.input.as_point() == io.input.as_point() && io2.output.as_point() == io.output.as_point());
+    }
+
+    #[test]
+    fn derive_many_matches_single() {
+        let ctx = crate::signing_context(b"many");
+        let items = (0..500u32).map( |i| i.to_le_bytes() ).collect::<Vec<_>>();
+
+        let t = start_timer!(|| "derive 500 inputs sequentially");
+        let seq = VRFInput::<Bls12>::derive_many(ctx.bytes(b"slot"), &items);
+        end_timer!(t);
+        assert_eq!(seq.len(), 500);
+        let mut single = ctx.bytes(b"slot");
+        single.commit_bytes(b"vrf-item", &items[7]);
+        assert!(seq[7].as_point() == VRFInput::<Bls12>::new_malleable(single).as_point());
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn derive_many_parallel_matches_sequential() {
+        let ctx = crate::signing_context(b"many");
+        let items = (0..500u32).map( |i| i.to_le_bytes() ).collect::<Vec<_>>();
+        let seq = VRFInput::<Bls12>::derive_many(ctx.bytes(b"slot"), &items);
+
+        let t = start_timer!(|| "derive 500 inputs in parallel");
+        let par = VRFInput::<Bls12>::derive_many_parallel(ctx.bytes(b"slot"), &items);
+        end_timer!(t);
+        assert_eq!(par.len(), 500);
+        assert!(seq.iter().zip(par.iter()).all( |(a,b)| a.as_point() == b.as_point() ));
+    }
+
+    #[test]
+    fn output_byte_order() {
+        let sk = crate::SecretKey::<Bls12>::from_rng(::rand_core::OsRng);
+        let io = VRFInput::<Bls12>::new_malleable(crate::signing_context(b"order").bytes(b"meow")).to_inout(&sk);
+        let le = io.make_bytes::<[u8; 32]>(b"ctx");
+        let mut be = io.make_bytes_be::<[u8; 32]>(b"ctx");
+        assert_eq!(le, io.make_bytes_with_order::<[u8; 32]>(b"ctx", ByteOrder::LittleEndian));
+        assert_eq!(be, io.make_bytes_with_order::<[u8; 32]>(b"ctx", ByteOrder::BigEndian));
+        be.reverse();
+        assert_eq!(le, be);
+
+        // Comparing big endian bytes lexicographically agrees with
+        // comparing little endian bytes from the most significant end.
+        let le16 = io.make_bytes::<[u8; 16]>(b"ctx");
+        let be16 = io.make_bytes_be::<[u8; 16]>(b"ctx");
+        for t in [0u128, 1, u128::max_value() / 3, u128::max_value()].iter() {
+            assert_eq!(
+                u128::from_le_bytes(le16) > *t,
+                &be16[..] > &t.to_be_bytes()[..]
+            );
+        }
+    }
+
+    #[test]
+    fn nullifiers_per_epoch() {
+        let sk = crate::SecretKey::<Bls12>::from_rng(::rand_core::OsRng);
+        let other = crate::SecretKey::<Bls12>::from_rng(::rand_core::OsRng);
+        let nullifier = |sk: &crate::SecretKey<Bls12>, epoch: &[u8]| VRFInput::<Bls12>::new_nullifier(epoch).to_inout(sk).nullifier();
+        assert_eq!(nullifier(&sk, b"epoch 1"), nullifier(&sk, b"epoch 1"));
+        assert!(nullifier(&sk, b"epoch 1") != nullifier(&sk, b"epoch 2"));
+        assert!(nullifier(&sk, b"epoch 1") != nullifier(&other, b"epoch 1"));
+    }
+
+    #[cfg(feature = "h2c")]
+    #[test]
+    fn h2c_inputs() {
+        let ctx = crate::signing_context(b"h2c");
+        let a = VRFInput::<Bls12>::new_malleable_h2c(ctx.bytes(b"meow"));
+        assert!(a.as_point() == VRFInput::<Bls12>::new_malleable_h2c(ctx.bytes(b"meow")).as_point());
+        assert!(a.as_point() != VRFInput::<Bls12>::new_malleable_h2c(ctx.bytes(b"woof")).as_point());
+        assert!(a.as_point() != VRFInput::<Bls12>::new_malleable(ctx.bytes(b"meow")).as_point());
+    }
+
+    #[test]
+    fn prehashed_extra() {
+        let payload = vec![0x5au8; 64 * 1024];
+        let prover = PreHashedExtra::new(&payload);
+        let verifier = PreHashedExtra::from_digest(*prover.digest());
+        assert_eq!(prover, PreHashedExtra::new(&payload));
+        let scalar = |extra: PreHashedExtra| extra.transcript().challenge_scalar::<<Bls12 as JubjubEngine>::Fr>(b"extra-msg");
+        assert!(scalar(prover) == scalar(verifier));
+
+        let mut other = payload.clone();
+        other[40000] ^= 1;
+        assert!(scalar(prover) != scalar(PreHashedExtra::new(&other)));
+        assert!(scalar(prover) != no_extra().challenge_scalar::<<Bls12 as JubjubEngine>::Fr>(b"extra-msg"));
+    }
+
+    #[test]
+    fn preout_consttime_matches_vartime() {
+        let mut rng = ::rand_core::OsRng;
+        let sk = crate::SecretKey::<Bls12>::from_rng(&mut rng);
+        for i in 0..4u8 {
+            let input = VRFInput::<Bls12>::new_malleable(crate::signing_context(b"ct").bytes(&[i]));
+            assert!(input.to_preout(&sk).0 == input.to_preout_vartime(&sk).0);
+        }
+    }
+
+    #[test]
+    fn input_cofactor_cleared_once() {
+        let params = Bls12::params();
+        let sk = crate::SecretKey::<Bls12>::from_rng(::rand_core::OsRng);
+        let input = VRFInput::<Bls12>::new_malleable(crate::signing_context(b"cofactor").bytes(b"meow"));
+        let unknown: Point<Bls12, Unknown> = input.as_point().clone().into();
+        assert!(unknown.as_prime_order(params).unwrap() == *input.as_point());
+
+        let t = start_timer!(|| "100 evaluations of one VRF input");
+        let outputs = (0..100).map( |_| input.to_preout(&sk) ).collect::<Vec<_>>();
+        end_timer!(t);
+        assert!(outputs.iter().all( |o| o.as_point() == outputs[0].as_point() ));
+        assert!(*outputs[0].as_point() == unknown.mul(sk.key.clone(), params));
+    }
+
+    /// Run via `cargo test --no-default-features` too, because core VRF
+    /// input derivation must never depend upon optional features.
+    #[test]
+    fn input_derivation_needs_no_features() {
+        let sk = crate::SecretKey::<Bls12>::from_seed([3u8; 32]);
+        let ctx = crate::signing_context(b"features");
+        let a = VRFInput::<Bls12>::new_malleable(ctx.bytes(b"meow")).to_inout(&sk);
+        let b = VRFInput::<Bls12>::new_malleable(ctx.bytes(b"meow")).to_inout(&sk);
+        assert_eq!(a.make_bytes::<[u8; 32]>(b""), b.make_bytes::<[u8; 32]>(b""));
+        #[cfg(feature = "chacharng")]
+        assert_eq!(a.make_chacharng(b"").next_u64(), b.make_chacharng(b"").next_u64());
+    }
+
+    #[test]
+    fn input_linkage() {
+        let sk = crate::SecretKey::<Bls12>::from_rng(::rand_core::OsRng);
+        let ctx = crate::signing_context(b"linkage");
+        let out = VRFInput::<Bls12>::new_malleable(ctx.bytes(b"meow")).to_preout(&sk);
+        let io = out.attach_input_malleable(ctx.bytes(b"meow"));
+        assert!( io.assert_input_matches(&VRFInput::new_malleable(ctx.bytes(b"meow"))).is_ok() );
+        assert!( io.assert_input_matches(&VRFInput::new_malleable(ctx.bytes(b"woof"))).is_err() );
+    }
+
+    #[test]
+    fn input_builder_requires_nonce() {
+        let ctx = crate::signing_context(b"builder");
+        let missing = VRFInputBuilder::new(ctx.bytes(b"meow")).field(b"slot", b"7").finalize::<Bls12>();
+        assert!(missing.is_err());
+
+        let built = VRFInputBuilder::new(ctx.bytes(b"meow")).field(b"slot", b"7").nonce(3).finalize::<Bls12>().unwrap();
+        let mut t = ctx.bytes(b"meow");
+        t.commit_bytes(b"slot", b"7");
+        t.commit_bytes(b"vrf-nonce", &3u64.to_le_bytes());
+        assert!(built.as_point() == VRFInput::<Bls12>::new_malleable(t).as_point());
+        let other = VRFInputBuilder::new(ctx.bytes(b"meow")).field(b"slot", b"7").nonce(4).finalize::<Bls12>().unwrap();
+        assert!(built.as_point() != other.as_point());
+    }
+
+    #[test]
+    fn input_spec_finalizations() {
+        let mut rng = ::rand_core::OsRng;
+        let sk = crate::SecretKey::<Bls12>::from_rng(&mut rng);
+        let root = RingSecretCopath::<Bls12>::random(4, &mut rng).to_root(&sk.to_public());
+        let ctx = crate::signing_context(b"spec");
+        let spec = VRFInputSpec::new(ctx.bytes(b"meow"));
+
+        let malleable = spec.clone().finalize_malleable::<Bls12>();
+        let ring = spec.finalize_ring_malleable(&root);
+        assert!(malleable.as_point() == VRFInput::<Bls12>::new_malleable(ctx.bytes(b"meow")).as_point());
+        assert!(ring.as_point() == VRFInput::<Bls12>::new_ring_malleable(ctx.bytes(b"meow"), &root).as_point());
+        assert!(malleable.as_point() != ring.as_point());
+    }
+
+    #[test]
+    fn input_bound_to_vk() {
+        let mut rng = ::rand_core::OsRng;
+        let sk = crate::SecretKey::<Bls12>::from_rng(&mut rng);
+        let root = RingSecretCopath::<Bls12>::random(4, &mut rng).to_root(&sk.to_public());
+        let ctx = crate::signing_context(b"bound");
+        let (vk_a, vk_b) = ([1u8; 32], [2u8; 32]);
+
+        let a = VRFInput::<Bls12>::new_ring_malleable_bound(ctx.bytes(b"meow"), &root, &vk_a);
+        let a2 = VRFInput::<Bls12>::new_ring_malleable_bound(ctx.bytes(b"meow"), &root, &vk_a);
+        let b = VRFInput::<Bls12>::new_ring_malleable_bound(ctx.bytes(b"meow"), &root, &vk_b);
+        let unbound = VRFInput::<Bls12>::new_ring_malleable(ctx.bytes(b"meow"), &root);
+        assert!(a.as_point() == a2.as_point());
+        assert!(a.as_point() != b.as_point());
+        assert!(a.as_point() != unbound.as_point());
+
+        // An output for CRS A attaches to a different input under CRS B.
+        let out = a.to_preout(&sk);
+        let io_b = out.attach_input_ring_malleable_bound(ctx.bytes(b"meow"), &root, &vk_b);
+        assert!(io_b.input.as_point() == b.as_point());
+        assert!(io_b.make_bytes::<[u8; 32]>(b"") != a.to_inout(&sk).make_bytes::<[u8; 32]>(b""));
+    }
+
+    #[test]
+    fn lottery_thresholds() {
+        let sk = crate::SecretKey::<Bls12>::from_rng(::rand_core::OsRng);
+        let io = VRFInput::<Bls12>::new_malleable(crate::signing_context(b"lottery").bytes(b"slot")).to_inout(&sk);
+        let wins = |p: f64| (0..2000u32).filter( |i|
+            ! io.exceeds_threshold(&i.to_le_bytes(), &threshold_from_probability(p))
+        ).count();
+        assert_eq!(wins(0.0), 0);
+        assert_eq!(wins(-1.0), 0);
+        assert_eq!(wins(1.0), 2000);
+        let quarter = wins(0.25);
+        assert!(400 < quarter && quarter < 600, "{} wins of 2000 at p = 0.25", quarter);
+
+        assert_eq!(threshold_from_probability(0.5)[23..], [0xff,0xff,0xff,0xff,0xff,0xff,0xff,0xff,0x7f]);
+        assert!( ! io.exceeds_threshold(b"", &[0xff; 32]) );
+    }
+
+    #[test]
+    fn lottery_threshold_little_endian() {
+        let sk = crate::SecretKey::<Bls12>::from_rng(::rand_core::OsRng);
+        let io = VRFInput::<Bls12>::new_malleable(crate::signing_context(b"lottery").bytes(b"order")).to_inout(&sk);
+        // Find an output whose first byte is below 0xff and last byte
+        // lies strictly between 0 and 0xff, so the byte order decides.
+        let context = (0..u32::max_value()).map( |i| i.to_le_bytes() ).find( |c| {
+            let bytes = io.make_bytes::<[u8; 32]>(c);
+            bytes[0] < 0xff && 0 < bytes[31] && bytes[31] < 0xff
+        } ).unwrap();
+        let bytes = io.make_bytes::<[u8; 32]>(&context);
+
+        let mut least = [0u8; 32];
+        least[0] = 0xff;
+        let mut most = [0u8; 32];
+        most[31] = 0xff;
+        assert!( io.exceeds_threshold(&context, &least) );
+        assert!( ! io.exceeds_threshold(&context, &most) );
+        assert!( ! io.exceeds_threshold(&context, &bytes) );
+        assert_eq!(io.make_bytes_with_order::<[u8; 32]>(&context, ByteOrder::LittleEndian), bytes);
+    }
+
+    #[test]
+    fn merge_matches_sequential() {
+        let mut rng = ::rand_core::OsRng;
+        let params = Bls12::params();
+        let sk = crate::SecretKey::<Bls12>::from_rng(&mut rng);
+        let ctx = crate::signing_context(b"merge");
+        let ps = (0..256u32).map( |i| {
+            VRFInput::<Bls12>::new_malleable(ctx.bytes(&i.to_le_bytes())).to_inout(&sk)
+        } ).collect::<Vec<_>>();
+
+        let batched = start_timer!(|| "vrfs_merge of 256 pairs, batched");
+        let merged = vrfs_merge(&ps);
+        end_timer!(batched);
+
+        let sequential = start_timer!(|| "vrfs_merge of 256 pairs, sequential");
+        let mut t = ::merlin::Transcript::new(b"MergeVRFs");
+        for p in ps.iter() {  p.commit(&mut t);  }
+        let (mut input, mut output) = (Point::zero(), Point::zero());
+        for p in ps.iter() {
+            let mut t0 = t.clone();
+            p.commit(&mut t0);
+            let mut s = [0u8; 16];
+            t0.challenge_bytes(b"", &mut s);
+            let z = crate::misc::scalar_from_u128::<Bls12>(s);
+            input = input.add(&p.input.as_point().mul(z, params), params);
+            output = output.add(&p.output.as_point().mul(z, params), params);
+        }
+        end_timer!(sequential);
+
+        let mut b0 = Vec::new();
+        let mut b1 = Vec::new();
+        merged.input.as_point().write(&mut b0).unwrap();
+        merged.output.as_point().write(&mut b0).unwrap();
+        input.write(&mut b1).unwrap();
+        output.write(&mut b1).unwrap();
+        assert_eq!(b0, b1);
+    }
+
+    #[test]
+    fn beacon_order_independent() {
+        let mut rng = ::rand_core::OsRng;
+        let input = VRFInput::<Bls12>::new_malleable(crate::signing_context(b"beacon").bytes(b"epoch 1"));
+        let mut members = (0..4).map( |_| {
+            let sk = crate::SecretKey::<Bls12>::from_rng(&mut rng);
+            (sk.to_public(), input.to_inout(&sk))
+        } ).collect::<Vec<_>>();
+
+        let beacon = aggregate_beacon(&members, b"ctx");
+        members.reverse();
+        assert_eq!(beacon, aggregate_beacon(&members, b"ctx"));
+        members.swap(0,2);
+        assert_eq!(beacon, aggregate_beacon(&members, b"ctx"));
+        assert_ne!(beacon, aggregate_beacon(&members, b"other ctx"));
+        members.pop();
+        assert_ne!(beacon, aggregate_beacon(&members, b"ctx"));
+    }
+
+    #[test]
+    fn test_ring_size_bound() {
+        let mut rng = ::rand_core::OsRng;
+        let fx = testing::RingFixture::shared();
+        let (depth, srs) = (fx.depth, fx.srs());
+
+        let sks = (0..3).map( |_| SecretKey::<Bls12>::from_rng(&mut rng) ).collect::<Vec<_>>();
+        let members = sks.iter().map( |sk| sk.to_public() ).collect::<Vec<_>>();
+        let (copath, auth_root) = RingSecretCopath::from_publickeys(&members, 0, depth as usize);
+        let t = || signing_context(b"sized").bytes(b"meow");
+
+        let input = VRFInput::<Bls12>::new_ring_malleable_sized(t(), &auth_root, 3);
+        assert!(input.as_point() != VRFInput::<Bls12>::new_ring_malleable_sized(t(), &auth_root, 4).as_point());
+        let (inout, proof) = sks[0].ring_vrf_sign_first(input, no_extra(), copath, srs).unwrap();
+
+        let verify = |member_count| {
+            let inout = inout.output.attach_input_ring_malleable_sized(t(), &auth_root, member_count);
+            auth_root.ring_vrf_verify(inout, no_extra(), proof.clone(), &fx.pvk).unwrap()
+        };
+        assert!(verify(3));
+        assert!(! verify(4));
+        assert!(! verify(1));
+    }
+}